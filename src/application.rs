@@ -1,24 +1,42 @@
-use std::{cell::OnceCell, time::Instant};
+use std::{
+    cell::{Cell, OnceCell},
+    time::{Duration, Instant},
+};
 
 use adw::{prelude::*, subclass::prelude::*};
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, ensure};
 use gettextrs::gettext;
 use gtk::{
     gio,
     glib::{self, clone},
 };
+use serde::{Serialize, de::DeserializeOwned};
 use soup::prelude::*;
 
 use crate::{
     about,
     album_art::AlbumArtStore,
     config::{APP_ID, PKGDATADIR, PROFILE, VERSION},
-    database::{self, EnvExt, Migrations},
+    credentials::Credentials,
+    database::{
+        self, EnvExt, Migrations, StorageUsage,
+        backups::{self, Backup},
+        quarantine,
+    },
+    date_time::DateTime,
+    i18n::{gettext_f, ngettext_f},
     inspector_page::InspectorPage,
+    integrity::{self, IntegrityReport},
     preferences_dialog::PreferencesDialog,
     recognizer::Recordings,
+    retention::{self, PruneSummary, RetentionPolicy},
     settings::Settings,
-    song_list::SongList,
+    song::Song,
+    song_clips::SongClips,
+    song_list::{SongList, SongListEntries},
+    song_trash::SongTrash,
+    uid::Uid,
+    utils,
     window::Window,
 };
 
@@ -29,8 +47,12 @@ mod imp {
     pub struct Application {
         pub(super) session: OnceCell<(soup::Session, soup::Cache)>,
         pub(super) album_art_store: OnceCell<AlbumArtStore>,
-        pub(super) env: OnceCell<(heed::Env, SongList, Recordings)>,
+        pub(super) env: OnceCell<(heed::Env, SongList, Recordings, SongClips, SongTrash)>,
         pub(super) settings: Settings,
+        pub(super) credentials: Credentials,
+        /// Whether a lot of data was removed from the db, so it must be
+        /// compacted on shutdown
+        pub(super) needs_compaction: Cell<bool>,
     }
 
     #[glib::object_subclass]
@@ -59,15 +81,26 @@ mod imp {
             let obj = self.obj();
             obj.setup_gactions();
             obj.setup_accels();
+            obj.load_credentials();
 
             setup_inspector_page();
         }
 
         fn shutdown(&self) {
-            if let Some((env, _, _)) = self.env.get()
-                && let Err(err) = env.force_sync()
-            {
-                tracing::error!("Failed to sync db env on shutdown: {:?}", err);
+            if let Some((env, song_history, _, _, _)) = self.env.get() {
+                if let Err(err) = song_history.flush() {
+                    tracing::error!("Failed to flush song history on shutdown: {:?}", err);
+                }
+
+                if let Err(err) = env.force_sync() {
+                    tracing::error!("Failed to sync db env on shutdown: {:?}", err);
+                }
+
+                if self.needs_compaction.get()
+                    && let Err(err) = database::compact(env)
+                {
+                    tracing::error!("Failed to compact db env on shutdown: {:?}", err);
+                }
             }
 
             if let Some((_, cache)) = self.session.get() {
@@ -126,22 +159,19 @@ impl Application {
     pub fn window(&self) -> Window {
         self.active_window().map_or_else(
             || {
-                let imp = self.imp();
-
                 let window = Window::new(self);
-
-                match init_env() {
-                    Ok((env, song_history, recordings)) => {
-                        tracing::debug!("db env initialized");
-                        window.bind_models(&song_history, &recordings);
-                        imp.env.set((env, song_history, recordings)).unwrap();
-                    }
-                    Err(err) => {
-                        tracing::error!("Failed to setup db env: {:?}", err);
-                        self.present_database_error_dialog(&window);
-                    }
-                }
-
+                utils::spawn(
+                    glib::Priority::DEFAULT,
+                    clone!(
+                        #[weak(rename_to = obj)]
+                        self,
+                        #[weak]
+                        window,
+                        async move {
+                            obj.load_env(&window).await;
+                        }
+                    ),
+                );
                 window
             },
             |w| w.downcast().unwrap(),
@@ -186,6 +216,187 @@ impl Application {
         &self.imp().settings
     }
 
+    pub fn credentials(&self) -> &Credentials {
+        &self.imp().credentials
+    }
+
+    /// Writes a backup of the db now, removing the oldest backups over the
+    /// limit.
+    pub async fn back_up_database(&self) -> Result<Backup> {
+        let (env, song_history, _, _, _) =
+            self.imp().env.get().context("Database is not loaded")?;
+
+        song_history
+            .flush()
+            .context("Failed to flush song history")?;
+
+        // Copying the env takes a while on large dbs, so do it off the main
+        // thread.
+        let env = env.clone();
+        let max_backups = self.settings().max_backups();
+        database::spawn_blocking(move || {
+            backups::create(
+                &env,
+                &backups::default_dir(),
+                &DateTime::now_utc(),
+                max_backups,
+            )
+        })
+        .await
+    }
+
+    /// Returns the backups of the db, from the newest to the oldest.
+    pub fn database_backups(&self) -> Result<Vec<Backup>> {
+        backups::list(&backups::default_dir())
+    }
+
+    /// Validates the backup and has it replace the db the next time it is
+    /// loaded.
+    pub fn restore_database_backup(&self, backup: &Backup) -> Result<()> {
+        backups::stage_restore(backup, &database::dir())
+    }
+
+    /// Checks all entries of the db and looks for orphaned data.
+    pub fn check_database_integrity(&self) -> Result<IntegrityReport> {
+        let (env, song_history, _, song_clips, song_trash) =
+            self.imp().env.get().context("Database is not loaded")?;
+
+        // Clips of songs that are not loaded yet would be reported as orphaned
+        ensure!(
+            !song_history.is_loading(),
+            "Song history is still being loaded"
+        );
+
+        integrity::check(env, song_history, song_trash, song_clips)
+    }
+
+    /// Fixes the problems found in the report.
+    pub fn repair_database(&self, report: &IntegrityReport) -> Result<()> {
+        let imp = self.imp();
+
+        let (env, _, _, song_clips, _) = imp.env.get().context("Database is not loaded")?;
+
+        integrity::repair(env, song_clips, report)?;
+
+        imp.needs_compaction.set(true);
+
+        Ok(())
+    }
+
+    /// Returns the number of entries that were set aside because they could
+    /// not be decoded.
+    pub fn n_quarantined_database_entries(&self) -> Result<u64> {
+        let (env, _, _, _, _) = self.imp().env.get().context("Database is not loaded")?;
+
+        quarantine::len(env)
+    }
+
+    /// Returns how much space the db takes.
+    pub fn database_storage_usage(&self) -> Result<StorageUsage> {
+        let (env, _, _, _, _) = self.imp().env.get().context("Database is not loaded")?;
+
+        database::storage_usage(env)
+    }
+
+    /// Whether the db will be compacted when the app quits
+    pub fn is_database_compaction_pending(&self) -> bool {
+        self.imp().needs_compaction.get()
+    }
+
+    /// Has the db compacted when the app quits, as it can't be while it is open.
+    pub fn compact_database_on_quit(&self) {
+        self.imp().needs_compaction.set(true);
+    }
+
+    /// Returns the song in the history with the given id, if the db is loaded.
+    pub fn history_song(&self, song_id: &Uid) -> Option<Song> {
+        let (_, song_history, _, _, _) = self.imp().env.get()?;
+        song_history.get(song_id)
+    }
+
+    /// Returns what pruning the history with the current retention policy
+    /// would remove, if the db is loaded.
+    pub fn preview_prune_history(&self) -> Option<PruneSummary> {
+        let (_, song_history, recordings, _, _) = self.imp().env.get()?;
+
+        Some(retention::preview(
+            RetentionPolicy::from_settings(self.settings()),
+            song_history,
+            recordings,
+            &DateTime::now_utc(),
+        ))
+    }
+
+    /// Removes the songs and saved recordings that are not to be kept with the
+    /// current retention policy.
+    pub fn prune_history(&self) -> Result<PruneSummary> {
+        let imp = self.imp();
+
+        let (_, song_history, recordings, _, song_trash) =
+            imp.env.get().context("Database is not loaded")?;
+
+        let summary = retention::prune(
+            RetentionPolicy::from_settings(self.settings()),
+            song_history,
+            song_trash,
+            recordings,
+            &DateTime::now_utc(),
+        )?;
+
+        if !summary.is_empty() {
+            imp.needs_compaction.set(true);
+        }
+
+        Ok(summary)
+    }
+
+    /// Permanently removes the songs in the trash, along with their clips,
+    /// returning the number of removed songs.
+    pub fn empty_trash(&self) -> Result<usize> {
+        let (_, _, _, _, song_trash) = self.imp().env.get().context("Database is not loaded")?;
+
+        let songs = song_trash.take_all().context("Failed to empty trash")?;
+        self.remove_trashed_song_clips(&songs)?;
+
+        Ok(songs.len())
+    }
+
+    /// Permanently removes the songs that have been in the trash for too long,
+    /// along with their clips.
+    fn purge_trash(&self) -> Result<()> {
+        let (_, _, _, _, song_trash) = self.imp().env.get().context("Database is not loaded")?;
+
+        let songs = song_trash
+            .take_expired(&DateTime::now_utc())
+            .context("Failed to take expired songs from trash")?;
+        self.remove_trashed_song_clips(&songs)?;
+
+        Ok(())
+    }
+
+    /// Removes the clips of songs removed from the trash, unless they were
+    /// added back to the history since.
+    fn remove_trashed_song_clips(&self, songs: &[Song]) -> Result<()> {
+        let imp = self.imp();
+
+        let (_, song_history, _, song_clips, _) =
+            imp.env.get().context("Database is not loaded")?;
+
+        for song in songs {
+            if !song_history.contains(song.id_ref()) {
+                song_clips
+                    .remove(song.id_ref())
+                    .context("Failed to remove song clip")?;
+            }
+        }
+
+        if !songs.is_empty() {
+            imp.needs_compaction.set(true);
+        }
+
+        Ok(())
+    }
+
     pub fn run(&self) -> glib::ExitCode {
         tracing::info!("Mousai ({})", APP_ID);
         tracing::info!("Version: {} ({})", VERSION, PROFILE);
@@ -202,8 +413,195 @@ impl Application {
         ApplicationExt::quit(self);
     }
 
-    fn present_database_error_dialog(&self, parent: &impl IsA<gtk::Widget>) {
+    /// Loads the db off the main thread, then binds it to the window, whose
+    /// song history is filled progressively. This presents the database error
+    /// dialog if it fails.
+    ///
+    /// This returns whether the db was loaded.
+    async fn load_env(&self, window: &Window) -> bool {
+        let imp = self.imp();
+
+        let load_start_time = Instant::now();
+
+        let (env, song_entries, n_quarantined_before, mut timings) = match open_env().await {
+            Ok(ret) => ret,
+            Err(err) => {
+                tracing::error!("Failed to setup db env: {:?}", err);
+                self.present_database_error_dialog(window);
+                return false;
+            }
+        };
+        tracing::debug!("db env initialized");
+
+        let models_load_start_time = Instant::now();
+        let (song_history, recordings, song_clips, song_trash) = match load_models(&env) {
+            Ok(ret) => ret,
+            Err(err) => {
+                tracing::error!("Failed to load db models: {:?}", err);
+                self.present_database_error_dialog(window);
+                return false;
+            }
+        };
+        timings.models = models_load_start_time.elapsed();
+
+        window.bind_models(&song_history, &recordings, &song_clips, &song_trash);
+        imp.env
+            .set((
+                env.clone(),
+                song_history.clone(),
+                recordings,
+                song_clips,
+                song_trash,
+            ))
+            .unwrap();
+
+        let n_songs = song_entries.len();
+        let songs_load_start_time = Instant::now();
+        if let Err(err) = song_history.load(song_entries).await {
+            tracing::error!("Failed to load song history: {:?}", err);
+            window.add_message_toast(&gettext("Failed to load history"));
+        }
+        timings.songs = songs_load_start_time.elapsed();
+
+        tracing::info!(
+            n_songs,
+            migrations = ?timings.migrations,
+            read = ?timings.read,
+            models = ?timings.models,
+            songs = ?timings.songs,
+            "Loaded db in {:?}",
+            load_start_time.elapsed()
+        );
+
+        let n_quarantined = match quarantine::len(&env) {
+            Ok(n_quarantined) => n_quarantined.saturating_sub(n_quarantined_before),
+            Err(err) => {
+                tracing::warn!("Failed to get number of quarantined entries: {:?}", err);
+                0
+            }
+        };
+        if n_quarantined > 0 {
+            tracing::warn!("Quarantined {} entries while loading", n_quarantined);
+            window.add_message_toast(&ngettext_f(
+                // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+                "{n_quarantined} damaged entry could not be loaded and was set aside",
+                "{n_quarantined} damaged entries could not be loaded and were set aside",
+                n_quarantined as u32,
+                &[("n_quarantined", &n_quarantined.to_string())],
+            ));
+        }
+
+        self.run_maintenance_daily();
+
+        true
+    }
+
+    /// Backs up the db if due, prunes the history, and purges the trash now,
+    /// then every day while running.
+    fn run_maintenance_daily(&self) {
+        const MAINTENANCE_INTERVAL: Duration = Duration::from_secs(24 * 60 * 60);
+
+        self.queue_maintenance();
+
+        glib::timeout_add_local(
+            MAINTENANCE_INTERVAL,
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                #[upgrade_or]
+                glib::ControlFlow::Break,
+                move || {
+                    obj.queue_maintenance();
+                    glib::ControlFlow::Continue
+                }
+            ),
+        );
+    }
+
+    /// Runs the maintenance once the main loop is idle, so it does not delay
+    /// loading or user interactions.
+    fn queue_maintenance(&self) {
+        utils::spawn(
+            glib::Priority::DEFAULT_IDLE,
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                async move {
+                    obj.run_maintenance().await;
+                }
+            ),
+        );
+    }
+
+    async fn run_maintenance(&self) {
+        // Back up first, so what is removed after can still be recovered.
+        if let Err(err) = self.back_up_database_if_due().await {
+            tracing::error!("Failed to back up database: {:?}", err);
+        }
+
+        if let Err(err) = self.prune_history() {
+            tracing::error!("Failed to prune history: {:?}", err);
+        }
+
+        if let Err(err) = self.purge_trash() {
+            tracing::error!("Failed to purge trash: {:?}", err);
+        }
+    }
+
+    async fn back_up_database_if_due(&self) -> Result<()> {
+        const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+        let interval_days = self.settings().backup_interval_days();
+
+        if interval_days == 0 {
+            return Ok(());
+        }
+
+        let now = DateTime::now_utc();
+        let is_due = self.database_backups()?.first().is_none_or(|last_backup| {
+            now.difference(last_backup.created_time()).as_seconds()
+                >= interval_days as i64 * SECONDS_PER_DAY
+        });
+
+        if is_due {
+            self.back_up_database().await?;
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the db with the newest backup that is valid, then loads it.
+    async fn restore_last_database_backup(&self, window: &Window) -> Result<()> {
+        let backups = self.database_backups()?;
+
+        let backup = backups
+            .iter()
+            .find(|backup| match self.restore_database_backup(backup) {
+                Ok(()) => true,
+                Err(err) => {
+                    tracing::warn!(path = ?backup.path(), "Skipping backup: {:?}", err);
+                    false
+                }
+            })
+            .context("No valid backup")?;
+
+        if self.load_env(window).await {
+            window.add_message_toast(&gettext_f(
+                // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+                "Restored backup from {created_time}",
+                &[(
+                    "created_time",
+                    &backup.created_time().to_local().fuzzy_display(),
+                )],
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn present_database_error_dialog(&self, window: &Window) {
         const QUIT_RESPONSE_ID: &str = "quit";
+        const RESTORE_RESPONSE_ID: &str = "restore";
 
         let dialog = adw::AlertDialog::builder()
             .heading(gettext("Critical Database Error"))
@@ -213,19 +611,52 @@ impl Application {
             .close_response(QUIT_RESPONSE_ID)
             .build();
         dialog.add_response(QUIT_RESPONSE_ID, &gettext("Quit"));
-        dialog.set_response_appearance(QUIT_RESPONSE_ID, adw::ResponseAppearance::Suggested);
+
+        let has_backups = self
+            .database_backups()
+            .inspect_err(|err| tracing::error!("Failed to list backups: {:?}", err))
+            .is_ok_and(|backups| !backups.is_empty());
+        if has_backups {
+            dialog.add_response(RESTORE_RESPONSE_ID, &gettext("_Restore Last Backup"));
+            dialog.set_response_appearance(RESTORE_RESPONSE_ID, adw::ResponseAppearance::Suggested);
+            dialog.set_default_response(Some(RESTORE_RESPONSE_ID));
+        } else {
+            dialog.set_response_appearance(QUIT_RESPONSE_ID, adw::ResponseAppearance::Suggested);
+        }
+
         dialog.connect_response(
-            Some(QUIT_RESPONSE_ID),
+            None,
             clone!(
                 #[weak(rename_to = obj)]
                 self,
+                #[weak]
+                window,
                 move |_, response| match response {
                     QUIT_RESPONSE_ID => obj.quit(),
+                    RESTORE_RESPONSE_ID => {
+                        utils::spawn(
+                            glib::Priority::DEFAULT,
+                            clone!(
+                                #[weak]
+                                obj,
+                                #[weak]
+                                window,
+                                async move {
+                                    if let Err(err) =
+                                        obj.restore_last_database_backup(&window).await
+                                    {
+                                        tracing::error!("Failed to restore last backup: {:?}", err);
+                                        obj.present_database_error_dialog(&window);
+                                    }
+                                }
+                            ),
+                        );
+                    }
                     _ => unreachable!(),
                 }
             ),
         );
-        dialog.present(Some(parent));
+        dialog.present(Some(window));
     }
 
     fn setup_gactions(&self) {
@@ -236,7 +667,7 @@ impl Application {
             .build();
         let show_preferences_action = gio::ActionEntry::builder("show-preferences")
             .activate(|obj: &Self, _, _| {
-                let dialog = PreferencesDialog::new(obj.settings());
+                let dialog = PreferencesDialog::new(obj.settings(), obj.credentials());
                 dialog.present(Some(&obj.window()));
             })
             .build();
@@ -255,6 +686,21 @@ impl Application {
         self.set_accels_for_action("win.toggle-playback", &["<Control>space"]);
         self.set_accels_for_action("win.toggle-recognize", &["<Control>r"]);
         self.set_accels_for_action("win.toggle-search", &["<Control>f"]);
+        self.set_accels_for_action("win.toggle-incognito", &["<Control><Shift>n"]);
+    }
+
+    fn load_credentials(&self) {
+        utils::spawn(
+            glib::Priority::DEFAULT,
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                async move {
+                    let imp = obj.imp();
+                    imp.credentials.load(&imp.settings).await;
+                }
+            ),
+        );
     }
 }
 
@@ -273,59 +719,263 @@ fn setup_inspector_page() {
     }
 }
 
-fn init_env() -> Result<(heed::Env, SongList, Recordings)> {
-    {
+/// How long each phase of loading the db took
+#[derive(Default)]
+struct LoadTimings {
+    migrations: Duration,
+    /// Reading and decoding the song history entries
+    read: Duration,
+    /// Loading everything else and creating the empty song history
+    models: Duration,
+    /// Appending the decoded song history entries to the history
+    songs: Duration,
+}
+
+/// Runs the migrations and reads and decodes the song history entries off the
+/// main thread.
+///
+/// This also returns the number of quarantined entries before loading.
+async fn open_env() -> Result<(heed::Env, SongListEntries, u64, LoadTimings)> {
+    gio::spawn_blocking(|| -> Result<_> {
+        let mut timings = LoadTimings::default();
+
+        let migrations_start_time = Instant::now();
+        run_migrations()?;
+        timings.migrations = migrations_start_time.elapsed();
+
         let env = database::new_env()?;
+        let n_quarantined = quarantine::len(&env)?;
 
-        env.with_write_txn(|wtxn| {
-            let mut migrations = Migrations::new();
-            migrations.add("SongList: SerdeBincode<Uid> -> UidCodec", |env, wtxn| {
-                use heed::types::SerdeBincode;
-
-                use crate::{
-                    database::SONG_LIST_DB_NAME,
-                    song::Song,
-                    uid::{Uid, UidCodec},
-                };
-
-                if let Some(db) = env.open_database::<SerdeBincode<Uid>, SerdeBincode<Song>>(
-                    wtxn,
-                    Some(SONG_LIST_DB_NAME),
-                )? {
-                    let new_items = db
-                        .iter(wtxn)
-                        .context("Failed to iter db")?
-                        .collect::<Result<Vec<_>, _>>()
-                        .context("Failed to collect items")?;
-
-                    db.clear(wtxn)?;
-
-                    let remapped_db = db.remap_key_type::<UidCodec>();
-
-                    for (uid, song) in new_items {
-                        remapped_db
-                            .put(wtxn, &uid, &song)
-                            .context("Failed to put item")?;
-                    }
+        let read_start_time = Instant::now();
+        let song_entries = SongListEntries::read(&env).context("Failed to read song history")?;
+        timings.read = read_start_time.elapsed();
+
+        Ok((env, song_entries, n_quarantined, timings))
+    })
+    .await
+    .unwrap_or_else(|payload| std::panic::resume_unwind(payload))
+}
+
+/// Loads the models from the db, with the song history still empty and to be
+/// loaded with [`SongList::load`].
+fn load_models(env: &heed::Env) -> Result<(SongList, Recordings, SongClips, SongTrash)> {
+    let song_history =
+        SongList::new_loading(env.clone()).context("Failed to create song history")?;
+    let recordings = Recordings::load_from_env(env.clone())?;
+    let song_clips = SongClips::load_from_env(env.clone())?;
+    let song_trash = SongTrash::load_from_env(env.clone())?;
+
+    Ok((song_history, recordings, song_clips, song_trash))
+}
+
+fn run_migrations() -> Result<()> {
+    let env = database::new_env()?;
+
+    env.with_write_txn(|wtxn| {
+        let mut migrations = Migrations::new();
+        migrations.add("SongList: SerdeBincode<Uid> -> UidCodec", |env, wtxn| {
+            use heed::types::SerdeBincode;
+
+            use crate::{
+                database::SONG_LIST_DB_NAME,
+                song::SongV1,
+                uid::{Uid, UidCodec},
+            };
+
+            // Songs had the layout of `SongV1` at that time
+            if let Some(db) = env.open_database::<SerdeBincode<Uid>, SerdeBincode<SongV1>>(
+                wtxn,
+                Some(SONG_LIST_DB_NAME),
+            )? {
+                let new_items = db
+                    .iter(wtxn)
+                    .context("Failed to iter db")?
+                    .collect::<Result<Vec<_>, _>>()
+                    .context("Failed to collect items")?;
+
+                db.clear(wtxn)?;
+
+                let remapped_db = db.remap_key_type::<UidCodec>();
+
+                for (uid, song) in new_items {
+                    remapped_db
+                        .put(wtxn, &uid, &song)
+                        .context("Failed to put item")?;
                 }
+            }
+
+            Ok(())
+        });
+        migrations.add("Song: Add original metadata", |env, wtxn| {
+            use crate::{
+                database::{RECORDINGS_DB_NAME, SONG_LIST_DB_NAME},
+                recognizer::{RecordingV1, RecordingV2},
+                song::{SongV1, SongV2},
+            };
+
+            convert_db_items::<SongV1, SongV2>(env, wtxn, SONG_LIST_DB_NAME, |song_v1| {
+                Ok(song_v1.into_song_v2())
+            })?;
+
+            // Recordings contain the recognized songs
+            convert_db_items::<RecordingV1, RecordingV2>(
+                env,
+                wtxn,
+                RECORDINGS_DB_NAME,
+                |recording_v1| Ok(recording_v1.into_recording_v2()),
+            )?;
+
+            Ok(())
+        });
+        migrations.add("Song: Add extended metadata", |env, wtxn| {
+            use crate::{
+                database::{RECORDINGS_DB_NAME, SONG_LIST_DB_NAME},
+                recognizer::{RecordingV2, RecordingV3},
+                song::{SongV2, SongV3},
+            };
+
+            convert_db_items::<SongV2, SongV3>(env, wtxn, SONG_LIST_DB_NAME, |song_v2| {
+                Ok(song_v2.into_song_v3())
+            })?;
+
+            // Recordings contain the recognized songs
+            convert_db_items::<RecordingV2, RecordingV3>(
+                env,
+                wtxn,
+                RECORDINGS_DB_NAME,
+                |recording_v2| Ok(recording_v2.into_recording_v3()),
+            )?;
+
+            Ok(())
+        });
+        migrations.add("Song: Versioned layout", |env, wtxn| {
+            use crate::{
+                database::{RECORDINGS_DB_NAME, SONG_LIST_DB_NAME, SONG_TRASH_DB_NAME, versioned},
+                recognizer::RecordingV3,
+                song::SongV3,
+                song_trash::TrashedSongV1,
+            };
+
+            // The values had these layouts at that time, which must not
+            // change even when the current ones do
+            versioned::wrap_db_items(env, wtxn, SONG_LIST_DB_NAME, SongV3::VERSION)?;
+            versioned::wrap_db_items(env, wtxn, RECORDINGS_DB_NAME, RecordingV3::VERSION)?;
+            versioned::wrap_db_items(env, wtxn, SONG_TRASH_DB_NAME, TrashedSongV1::VERSION)?;
+
+            Ok(())
+        });
+        migrations
+            .run(&env, wtxn)
+            .context("Failed to run migrations")
+    })?;
 
-                Ok(())
-            });
-            migrations
-                .run(&env, wtxn)
-                .context("Failed to run migrations")
-        })?;
+    // We might open a db in migrations and open the same db with different
+    // types later on, which is not allowed when done within the same env.
+    // To workaround this, we close the env and open a new one.
+    env.prepare_for_closing().wait();
 
-        // We might open a db in migrations and open the same db with different
-        // types later on, which is not allowed when done within the same env.
-        // To workaround this, we close the env and open a new one.
-        env.prepare_for_closing().wait();
+    Ok(())
+}
+
+/// Converts the values of the db with the given name, if it exists, from
+/// `Old` to `New`, which are both stored with bincode, keeping their keys.
+///
+/// Values that can't be decoded or converted are moved to the quarantine db
+/// instead of failing the whole migration.
+fn convert_db_items<Old, New>(
+    env: &heed::Env,
+    wtxn: &mut heed::RwTxn<'_>,
+    db_name: &str,
+    convert: impl Fn(Old) -> Result<New>,
+) -> Result<()>
+where
+    Old: DeserializeOwned,
+    New: Serialize,
+{
+    use heed::types::Bytes;
+
+    let Some(db) = env.open_database::<Bytes, Bytes>(wtxn, Some(db_name))? else {
+        return Ok(());
+    };
+
+    let mut new_items = Vec::new();
+    let mut invalid_keys = Vec::new();
+
+    for item in db.iter(wtxn).context("Failed to iter db")? {
+        let (key, value) = item.context("Failed to read item")?;
+
+        let new = bincode::deserialize::<Old>(value)
+            .context("Failed to deserialize item")
+            .and_then(&convert)
+            .and_then(|new| bincode::serialize(&new).context("Failed to serialize item"));
+        match new {
+            Ok(new) => new_items.push((key.to_vec(), new)),
+            Err(err) => invalid_keys.push((key.to_vec(), format!("{:#}", err))),
+        }
     }
 
-    let env = database::new_env()?;
-    let song_history =
-        SongList::load_from_env(env.clone()).context("Failed to load song history")?;
-    let recordings = Recordings::load_from_env(env.clone())?;
+    for (key, reason) in invalid_keys {
+        quarantine::move_to_quarantine(env, wtxn, db_name, &key, reason)?;
+    }
+
+    for (key, new) in new_items {
+        db.put(wtxn, &key, &new).context("Failed to put item")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use heed::types::{Bytes, SerdeBincode};
+    use serde::Deserialize;
+
+    use crate::uid::{Uid, UidCodec};
+
+    #[test]
+    fn convert_db_items_quarantines_invalid() {
+        #[derive(Serialize, Deserialize)]
+        struct Old {
+            a: u32,
+        }
+
+        #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+        struct New {
+            a: u32,
+            b: bool,
+        }
+
+        let (env, _tempdir) = database::new_test_env();
+        env.with_write_txn(|wtxn| {
+            let db = env.create_database::<UidCodec, SerdeBincode<Old>>(wtxn, Some("test"))?;
+            db.put(wtxn, &Uid::from("a"), &Old { a: 1 })?;
+            db.put(wtxn, &Uid::from("c"), &Old { a: 3 })?;
+            db.remap_data_type::<Bytes>()
+                .put(wtxn, &Uid::from("b"), &[1])?;
+
+            convert_db_items::<Old, New>(&env, wtxn, "test", |old| {
+                ensure!(old.a != 3, "Can't convert 3");
+                Ok(New { a: old.a, b: true })
+            })
+        })
+        .unwrap();
+
+        let rtxn = env.read_txn().unwrap();
+        let db = env
+            .open_database::<UidCodec, SerdeBincode<New>>(&rtxn, Some("test"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            db.iter(&rtxn)
+                .unwrap()
+                .collect::<Result<Vec<_>, _>>()
+                .unwrap(),
+            [(Uid::from("a"), New { a: 1, b: true })]
+        );
+        drop(rtxn);
 
-    Ok((env, song_history, recordings))
+        assert_eq!(quarantine::len(&env).unwrap(), 2);
+    }
 }
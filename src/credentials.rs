@@ -0,0 +1,319 @@
+use anyhow::{Context, Result};
+use async_lock::OnceCell as AsyncOnceCell;
+use gtk::{glib, prelude::*, subclass::prelude::*};
+
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    rc::Rc,
+};
+
+use crate::settings::Settings;
+
+const APPLICATION_ATTRIBUTE: &str = "application";
+const APPLICATION_ATTRIBUTE_VALUE: &str = "io.github.seadve.Mousai";
+const KIND_ATTRIBUTE: &str = "kind";
+
+const AUD_D_API_TOKEN_KIND: &str = "aud-d-api-token";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
+#[enum_type(name = "MousaiCredentialsState")]
+pub enum CredentialsState {
+    /// The keyring is still being opened
+    #[default]
+    Loading,
+    /// Credentials are read from and saved to the keyring
+    Available,
+    /// There is no keyring to read from or save to
+    Unavailable,
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, glib::Properties)]
+    #[properties(wrapper_type = super::Credentials)]
+    pub struct Credentials {
+        #[property(get, builder(CredentialsState::default()))]
+        pub(super) state: Cell<CredentialsState>,
+        /// Token used by AudD recognition, loaded from the keyring
+        #[property(get)]
+        pub(super) aud_d_api_token: RefCell<Option<String>>,
+
+        pub(super) keyring: RefCell<Option<Rc<oo7::Keyring>>>,
+        /// Set once the state is no longer [`CredentialsState::Loading`]
+        pub(super) loaded: AsyncOnceCell<()>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for Credentials {
+        const NAME: &'static str = "MousaiCredentials";
+        type Type = super::Credentials;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for Credentials {}
+}
+
+glib::wrapper! {
+    /// Provider credentials, stored in the user's keyring through the Secret
+    /// Service rather than in GSettings where anything with dconf access can
+    /// read them.
+    pub struct Credentials(ObjectSubclass<imp::Credentials>);
+}
+
+impl Credentials {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    /// Opens the keyring, moves the token stored in the settings by older
+    /// versions to it, then loads the credentials.
+    ///
+    /// If no keyring is available, the state becomes
+    /// [`CredentialsState::Unavailable`] and the token still in the settings, if
+    /// any, is used as is.
+    pub async fn load(&self, settings: &Settings) {
+        match oo7::Keyring::new().await {
+            Ok(keyring) => self.load_from_keyring(keyring, settings).await,
+            Err(err) => {
+                tracing::warn!("No keyring available: {:?}", err);
+                self.set_unavailable(settings);
+                self.finish_loading().await;
+            }
+        }
+    }
+
+    /// Same as [`Self::load`], but with an already opened keyring.
+    pub async fn load_from_keyring(&self, keyring: oo7::Keyring, settings: &Settings) {
+        let legacy_api_token = settings.aud_d_api_token();
+
+        if let Err(err) = migrate_from_settings(&keyring, &legacy_api_token).await {
+            tracing::error!("Failed to migrate credentials from settings: {:?}", err);
+        }
+
+        match lookup(&keyring, AUD_D_API_TOKEN_KIND).await {
+            Ok(api_token) => {
+                // Only clear the token in the settings once a token is read
+                // back from the keyring, so it is not lost if migrating or
+                // reading fails.
+                if !legacy_api_token.is_empty() && api_token.is_some() {
+                    settings.set_aud_d_api_token("");
+                }
+
+                let imp = self.imp();
+                imp.keyring.replace(Some(Rc::new(keyring)));
+                imp.aud_d_api_token.replace(api_token);
+                imp.state.set(CredentialsState::Available);
+                self.notify_aud_d_api_token();
+                self.notify_state();
+            }
+            Err(err) => {
+                tracing::warn!("Failed to read from keyring: {:?}", err);
+                self.set_unavailable(settings);
+            }
+        }
+
+        self.finish_loading().await;
+    }
+
+    /// Waits until the credentials are loaded, whether or not a keyring is
+    /// available.
+    ///
+    /// This must be awaited before the token is read for recognition, as it is
+    /// not known yet while loading.
+    pub async fn wait_loaded(&self) {
+        self.imp().loaded.wait().await;
+    }
+
+    /// Saves the token to the keyring, or removes it if it is empty.
+    pub async fn set_aud_d_api_token(&self, api_token: &str) -> Result<()> {
+        let keyring = self
+            .imp()
+            .keyring
+            .borrow()
+            .clone()
+            .context("No keyring available")?;
+
+        store(&keyring, AUD_D_API_TOKEN_KIND, api_token).await?;
+
+        self.imp()
+            .aud_d_api_token
+            .replace(Some(api_token).filter(|t| !t.is_empty()).map(String::from));
+        self.notify_aud_d_api_token();
+
+        Ok(())
+    }
+
+    async fn finish_loading(&self) {
+        let _ = self.imp().loaded.set(()).await;
+    }
+
+    fn set_unavailable(&self, settings: &Settings) {
+        let imp = self.imp();
+
+        let legacy_api_token = settings.aud_d_api_token();
+        imp.aud_d_api_token
+            .replace(Some(legacy_api_token).filter(|t| !t.is_empty()));
+        imp.state.set(CredentialsState::Unavailable);
+
+        self.notify_aud_d_api_token();
+        self.notify_state();
+    }
+}
+
+impl Default for Credentials {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Copies the token stored in the settings by older versions to the keyring.
+///
+/// A token already in the keyring is kept, as it is newer than the one in the
+/// settings.
+async fn migrate_from_settings(keyring: &oo7::Keyring, legacy_api_token: &str) -> Result<()> {
+    if legacy_api_token.is_empty() {
+        return Ok(());
+    }
+
+    if lookup(keyring, AUD_D_API_TOKEN_KIND).await?.is_none() {
+        store(keyring, AUD_D_API_TOKEN_KIND, legacy_api_token).await?;
+        tracing::debug!("Migrated AudD API token from settings to keyring");
+    }
+
+    Ok(())
+}
+
+async fn lookup(keyring: &oo7::Keyring, kind: &str) -> Result<Option<String>> {
+    keyring.unlock().await.context("Failed to unlock keyring")?;
+
+    let items = keyring
+        .search_items(&attributes(kind))
+        .await
+        .context("Failed to search keyring")?;
+
+    let Some(item) = items.first() else {
+        return Ok(None);
+    };
+
+    let secret = item.secret().await.context("Failed to read secret")?;
+    let value = String::from_utf8(secret.to_vec()).context("Secret is not valid UTF-8")?;
+
+    Ok(Some(value))
+}
+
+async fn store(keyring: &oo7::Keyring, kind: &str, value: &str) -> Result<()> {
+    keyring.unlock().await.context("Failed to unlock keyring")?;
+
+    if value.is_empty() {
+        keyring
+            .delete(&attributes(kind))
+            .await
+            .context("Failed to delete secret")?;
+    } else {
+        keyring
+            .create_item(&label(kind), &attributes(kind), value, true)
+            .await
+            .context("Failed to store secret")?;
+    }
+
+    Ok(())
+}
+
+fn attributes(kind: &str) -> HashMap<&str, &str> {
+    HashMap::from([
+        (APPLICATION_ATTRIBUTE, APPLICATION_ATTRIBUTE_VALUE),
+        (KIND_ATTRIBUTE, kind),
+    ])
+}
+
+fn label(kind: &str) -> String {
+    match kind {
+        AUD_D_API_TOKEN_KIND => "Mousai AudD API Token".to_string(),
+        _ => format!("Mousai {}", kind),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::sync::Arc;
+
+    /// Returns a keyring that is only kept in memory, so no Secret Service is
+    /// needed.
+    async fn test_keyring() -> oo7::Keyring {
+        let keyring = oo7::file::Keyring::temporary(oo7::Secret::from("test"))
+            .await
+            .unwrap();
+        oo7::Keyring::File(Arc::new(keyring))
+    }
+
+    #[test]
+    fn store_and_lookup() {
+        glib::MainContext::default().block_on(async {
+            let keyring = test_keyring().await;
+
+            assert_eq!(lookup(&keyring, "test-kind").await.unwrap(), None);
+
+            store(&keyring, "test-kind", "token").await.unwrap();
+            assert_eq!(
+                lookup(&keyring, "test-kind").await.unwrap().as_deref(),
+                Some("token")
+            );
+
+            store(&keyring, "test-kind", "other-token").await.unwrap();
+            assert_eq!(
+                lookup(&keyring, "test-kind").await.unwrap().as_deref(),
+                Some("other-token")
+            );
+
+            store(&keyring, "test-kind", "").await.unwrap();
+            assert_eq!(lookup(&keyring, "test-kind").await.unwrap(), None);
+        });
+    }
+
+    #[test]
+    fn load_migrates_from_settings() {
+        glib::MainContext::default().block_on(async {
+            let settings = Settings::new_in_memory();
+            settings.set_aud_d_api_token("legacy-token");
+
+            let credentials = Credentials::new();
+            credentials
+                .load_from_keyring(test_keyring().await, &settings)
+                .await;
+            credentials.wait_loaded().await;
+
+            assert_eq!(credentials.state(), CredentialsState::Available);
+            assert_eq!(
+                credentials.aud_d_api_token().as_deref(),
+                Some("legacy-token")
+            );
+            assert_eq!(settings.aud_d_api_token(), "");
+
+            credentials.set_aud_d_api_token("").await.unwrap();
+            assert_eq!(credentials.aud_d_api_token(), None);
+        });
+    }
+
+    #[test]
+    fn load_keeps_newer_token_in_keyring() {
+        glib::MainContext::default().block_on(async {
+            let settings = Settings::new_in_memory();
+            settings.set_aud_d_api_token("legacy-token");
+
+            let keyring = test_keyring().await;
+            store(&keyring, AUD_D_API_TOKEN_KIND, "token")
+                .await
+                .unwrap();
+
+            let credentials = Credentials::new();
+            credentials.load_from_keyring(keyring, &settings).await;
+
+            assert_eq!(credentials.aud_d_api_token().as_deref(), Some("token"));
+            assert_eq!(settings.aud_d_api_token(), "");
+        });
+    }
+}
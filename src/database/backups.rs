@@ -0,0 +1,276 @@
+use anyhow::{Context, Result, ensure};
+use gtk::glib;
+use heed::types::Bytes;
+
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::Instant,
+};
+
+use super::{
+    N_NAMED_DBS, RECORDINGS_DB_NAME, RESTORED_DATA_FILE_NAME, SONG_CLIPS_DB_NAME,
+    SONG_LIST_DB_NAME, SONG_TRASH_DB_NAME,
+};
+use crate::date_time::DateTime;
+
+const FILE_NAME_PREFIX: &str = "backup-";
+const FILE_NAME_SUFFIX: &str = ".mdb";
+
+/// ISO 8601 basic format, which can be parsed back and sorts chronologically
+const CREATED_TIME_FORMAT: &str = "%Y%m%dT%H%M%SZ";
+
+/// Returns the directory where backups are stored by default.
+pub fn default_dir() -> PathBuf {
+    glib::user_data_dir().join("mousai/backups")
+}
+
+/// A compacted snapshot of the env
+#[derive(Debug, Clone)]
+pub struct Backup {
+    path: PathBuf,
+    created_time: DateTime,
+}
+
+impl Backup {
+    fn from_path(path: PathBuf) -> Option<Self> {
+        let created_time = path
+            .file_name()?
+            .to_str()?
+            .strip_prefix(FILE_NAME_PREFIX)?
+            .strip_suffix(FILE_NAME_SUFFIX)?;
+        let created_time = DateTime::from_iso8601(created_time).ok()?;
+
+        Some(Self { path, created_time })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn created_time(&self) -> &DateTime {
+        &self.created_time
+    }
+}
+
+/// Writes a consistent, compacted snapshot of the env in `dir`, then removes
+/// the oldest backups so that at most `max_backups` are kept.
+pub fn create(env: &heed::Env, dir: &Path, now: &DateTime, max_backups: u32) -> Result<Backup> {
+    let start_time = Instant::now();
+
+    fs::create_dir_all(dir)
+        .with_context(|| format!("Failed to create backups dir at {}", dir.display()))?;
+
+    let file_name = format!(
+        "{}{}{}",
+        FILE_NAME_PREFIX,
+        now.format(CREATED_TIME_FORMAT)?,
+        FILE_NAME_SUFFIX
+    );
+    let path = dir.join(&file_name);
+
+    let tmp_path = dir.join(format!("{}.tmp", file_name));
+    let file = env
+        .copy_to_path(&tmp_path, heed::CompactionOption::Enabled)
+        .context("Failed to copy env")?;
+    file.sync_all().context("Failed to sync backup")?;
+
+    // Only have the backup listed once it is complete.
+    fs::rename(&tmp_path, &path).context("Failed to rename backup")?;
+
+    tracing::debug!(?path, "Created backup in {:?}", start_time.elapsed());
+
+    for backup in list(dir)?.into_iter().skip(max_backups.max(1) as usize) {
+        fs::remove_file(backup.path())
+            .with_context(|| format!("Failed to remove old backup at {}", backup.path.display()))?;
+        tracing::debug!(path = ?backup.path, "Removed old backup");
+    }
+
+    Ok(Backup {
+        path,
+        created_time: now.clone(),
+    })
+}
+
+/// Returns the backups in `dir`, from the newest to the oldest.
+pub fn list(dir: &Path) -> Result<Vec<Backup>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut backups = fs::read_dir(dir)
+        .with_context(|| format!("Failed to read backups dir at {}", dir.display()))?
+        .map(|entry| entry.map(|entry| Backup::from_path(entry.path())))
+        .collect::<Result<Vec<_>, _>>()
+        .context("Failed to read backups dir entry")?
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+    backups.sort_by(|a, b| b.created_time.cmp(&a.created_time));
+
+    Ok(backups)
+}
+
+/// Checks that the backup can be opened and that all of its entries can be
+/// read.
+pub fn validate(backup: &Backup) -> Result<()> {
+    let env = unsafe {
+        let mut options = heed::EnvOpenOptions::new();
+        options.max_dbs(N_NAMED_DBS).flags(
+            heed::EnvFlags::NO_SUB_DIR | heed::EnvFlags::READ_ONLY | heed::EnvFlags::NO_LOCK,
+        );
+        options
+            .open(&backup.path)
+            .with_context(|| format!("Failed to open backup at {}", backup.path.display()))?
+    };
+
+    let ret = validate_env(&env);
+    env.prepare_for_closing().wait();
+
+    ret
+}
+
+fn validate_env(env: &heed::Env) -> Result<()> {
+    let rtxn = env.read_txn().context("Failed to create read txn")?;
+
+    let mut has_song_list = false;
+
+    for db_name in [
+        SONG_LIST_DB_NAME,
+        RECORDINGS_DB_NAME,
+        SONG_CLIPS_DB_NAME,
+        SONG_TRASH_DB_NAME,
+    ] {
+        let Some(db) = env
+            .open_database::<Bytes, Bytes>(&rtxn, Some(db_name))
+            .with_context(|| format!("Failed to open `{}` db", db_name))?
+        else {
+            continue;
+        };
+
+        for item in db
+            .iter(&rtxn)
+            .with_context(|| format!("Failed to iter `{}` db", db_name))?
+        {
+            item.with_context(|| format!("Failed to read entry of `{}` db", db_name))?;
+        }
+
+        has_song_list |= db_name == SONG_LIST_DB_NAME;
+    }
+
+    ensure!(has_song_list, "Backup has no song list");
+
+    Ok(())
+}
+
+/// Validates the backup, then copies it to `db_dir`, where it replaces the
+/// env the next time it is opened.
+pub fn stage_restore(backup: &Backup, db_dir: &Path) -> Result<()> {
+    validate(backup).context("Backup is invalid")?;
+
+    let restored_path = db_dir.join(RESTORED_DATA_FILE_NAME);
+    let tmp_path = db_dir.join(format!("{}.tmp", RESTORED_DATA_FILE_NAME));
+    fs::copy(&backup.path, &tmp_path).context("Failed to copy backup")?;
+    fs::File::open(&tmp_path)
+        .and_then(|file| file.sync_all())
+        .context("Failed to sync copied backup")?;
+    fs::rename(&tmp_path, &restored_path).context("Failed to rename copied backup")?;
+
+    tracing::debug!(path = ?backup.path, "Staged backup for restore");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use heed::types::Str;
+
+    use crate::database::{self, EnvExt};
+
+    fn date_time(date: &str) -> DateTime {
+        DateTime::from_iso8601(&format!("{}T00:00:00Z", date)).unwrap()
+    }
+
+    fn new_test_env_with_song_list() -> (heed::Env, tempfile::TempDir) {
+        let (env, tempdir) = database::new_test_env();
+        env.with_write_txn(|wtxn| {
+            let db = env.create_database::<Str, Str>(wtxn, Some(SONG_LIST_DB_NAME))?;
+            db.put(wtxn, "a", "A")?;
+            Ok(())
+        })
+        .unwrap();
+        (env, tempdir)
+    }
+
+    #[test]
+    fn create_and_list() {
+        let (env, _tempdir) = new_test_env_with_song_list();
+        let backups_dir = tempfile::tempdir().unwrap();
+
+        assert!(list(backups_dir.path()).unwrap().is_empty());
+
+        for date in ["2024-01-01", "2024-01-03", "2024-01-02"] {
+            create(&env, backups_dir.path(), &date_time(date), 2).unwrap();
+        }
+
+        let backups = list(backups_dir.path()).unwrap();
+        assert_eq!(backups.len(), 2);
+        assert_eq!(backups[0].created_time(), &date_time("2024-01-03"));
+        assert_eq!(backups[1].created_time(), &date_time("2024-01-02"));
+
+        // Unrelated files are ignored
+        fs::write(backups_dir.path().join("notes.txt"), "").unwrap();
+        assert_eq!(list(backups_dir.path()).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn validate_and_stage_restore() {
+        let (env, tempdir) = new_test_env_with_song_list();
+        let backups_dir = tempfile::tempdir().unwrap();
+
+        let backup = create(&env, backups_dir.path(), &date_time("2024-01-01"), 5).unwrap();
+        validate(&backup).unwrap();
+
+        env.with_write_txn(|wtxn| {
+            let db = env
+                .open_database::<Str, Str>(wtxn, Some(SONG_LIST_DB_NAME))?
+                .unwrap();
+            db.put(wtxn, "b", "B")?;
+            Ok(())
+        })
+        .unwrap();
+        env.prepare_for_closing().wait();
+
+        stage_restore(&backup, tempdir.path()).unwrap();
+        database::replace_with_staged(tempdir.path()).unwrap();
+
+        let env = unsafe {
+            heed::EnvOpenOptions::new()
+                .max_dbs(1)
+                .open(&tempdir)
+                .unwrap()
+        };
+        let rtxn = env.read_txn().unwrap();
+        let db = env
+            .open_database::<Str, Str>(&rtxn, Some(SONG_LIST_DB_NAME))
+            .unwrap()
+            .unwrap();
+        assert_eq!(db.get(&rtxn, "a").unwrap(), Some("A"));
+        assert_eq!(db.get(&rtxn, "b").unwrap(), None);
+    }
+
+    #[test]
+    fn validate_invalid() {
+        let backups_dir = tempfile::tempdir().unwrap();
+
+        let path = backups_dir.path().join("backup-20240101T000000Z.mdb");
+        fs::write(&path, b"not a database").unwrap();
+
+        let backup = Backup::from_path(path).unwrap();
+        assert!(validate(&backup).is_err());
+        assert!(stage_restore(&backup, backups_dir.path()).is_err());
+        assert!(!backups_dir.path().join(RESTORED_DATA_FILE_NAME).exists());
+    }
+}
@@ -1,10 +1,15 @@
+pub mod backups;
 mod migrations;
+pub mod quarantine;
+pub mod versioned;
 
 use anyhow::{Context, Result};
-use gtk::glib;
+use gtk::{gio, glib};
 
 use std::{
     fs,
+    path::{Path, PathBuf},
+    sync::{PoisonError, RwLock},
     time::{Duration, Instant},
 };
 
@@ -12,18 +17,55 @@ pub use self::migrations::Migrations;
 
 pub const USER_VERSION_KEY: &str = "user_version";
 
-const N_NAMED_DBS: u32 = 2;
+/// Size of the map of the env when it is opened, which is grown when it gets
+/// full
+const DEFAULT_MAP_SIZE: usize = 100 * 1024 * 1024; // 100 MiB
+/// Number of times the map is grown for a single write txn before giving up
+const MAX_MAP_GROWS_PER_TXN: u32 = 4;
+
+const N_NAMED_DBS: u32 = 5;
 pub const SONG_LIST_DB_NAME: &str = "song_list";
 pub const RECORDINGS_DB_NAME: &str = "saved_recordings";
+pub const SONG_CLIPS_DB_NAME: &str = "song_clips";
+pub const SONG_TRASH_DB_NAME: &str = "song_trash";
+pub const QUARANTINE_DB_NAME: &str = "quarantine";
+pub const DB_NAMES: [&str; N_NAMED_DBS as usize] = [
+    SONG_LIST_DB_NAME,
+    RECORDINGS_DB_NAME,
+    SONG_CLIPS_DB_NAME,
+    SONG_TRASH_DB_NAME,
+    QUARANTINE_DB_NAME,
+];
+
+/// Name of the LMDB data file in the env directory
+const DATA_FILE_NAME: &str = "data.mdb";
+/// Name of the compacted copy of the data file that replaces it on next open
+const COMPACTED_DATA_FILE_NAME: &str = "data.mdb.compacted";
+/// Name of the backup to be restored that replaces the data file on next open
+const RESTORED_DATA_FILE_NAME: &str = "data.mdb.restored";
+
+/// Held for reading while txns are used off the main thread, and for writing
+/// while the map is resized, as it must not be resized while a txn is active
+static RESIZE_LOCK: RwLock<()> = RwLock::new(());
+
+/// Returns the directory of the env.
+pub fn dir() -> PathBuf {
+    glib::user_data_dir().join("mousai/db")
+}
 
 /// Note: This must be only called once.
 pub fn new_env() -> Result<heed::Env> {
-    let path = glib::user_data_dir().join("mousai/db");
+    let path = dir();
     fs::create_dir_all(&path)
         .with_context(|| format!("Failed to create db dir at {}", path.display()))?;
+
+    if let Err(err) = replace_with_staged(&path) {
+        tracing::error!("Failed to replace db with staged copy: {:?}", err);
+    }
+
     let env = unsafe {
         heed::EnvOpenOptions::new()
-            .map_size(100 * 1024 * 1024) // 100 MiB
+            .map_size(DEFAULT_MAP_SIZE)
             .max_dbs(N_NAMED_DBS)
             .open(&path)
             .with_context(|| format!("Failed to open heed env at {}", path.display()))?
@@ -40,15 +82,136 @@ pub fn new_env() -> Result<heed::Env> {
     Ok(env)
 }
 
-/// Create a new env for tests with 1 max named db and a
+/// Writes a compacted copy of the env, without the free pages, which replaces
+/// it the next time it is opened.
+///
+/// Note: Writes to the env after this are lost on next open, so this must be
+/// only called when closing the env.
+pub fn compact(env: &heed::Env) -> Result<()> {
+    let start_time = Instant::now();
+
+    let tmp_path = env.path().join(format!("{}.tmp", COMPACTED_DATA_FILE_NAME));
+    let file = env
+        .copy_to_path(&tmp_path, heed::CompactionOption::Enabled)
+        .context("Failed to copy env")?;
+    file.sync_all().context("Failed to sync compacted copy")?;
+
+    // Only have the copy replace the env once it is complete.
+    fs::rename(&tmp_path, env.path().join(COMPACTED_DATA_FILE_NAME))
+        .context("Failed to rename compacted copy")?;
+
+    tracing::debug!(
+        real_disk_size = ?env.real_disk_size(),
+        non_free_pages_size = ?env.non_free_pages_size(),
+        "Compacted db env in {:?}",
+        start_time.elapsed()
+    );
+
+    Ok(())
+}
+
+/// Replaces the data file in `dir` with the backup to be restored or, if there
+/// is none, its compacted copy, if there is one.
+fn replace_with_staged(dir: &Path) -> Result<()> {
+    let restored_path = dir.join(RESTORED_DATA_FILE_NAME);
+    let compacted_path = dir.join(COMPACTED_DATA_FILE_NAME);
+
+    if restored_path.exists() {
+        // The compacted copy has the data that is being replaced.
+        if compacted_path.exists() {
+            fs::remove_file(&compacted_path)?;
+        }
+
+        fs::rename(&restored_path, dir.join(DATA_FILE_NAME))?;
+        tracing::debug!("Replaced db with restored backup");
+
+        return Ok(());
+    }
+
+    if !compacted_path.exists() {
+        return Ok(());
+    }
+
+    fs::rename(&compacted_path, dir.join(DATA_FILE_NAME))?;
+    tracing::debug!("Replaced db with compacted copy");
+
+    Ok(())
+}
+
+/// How much space a table of the env takes
+#[derive(Debug)]
+pub struct TableUsage {
+    pub name: &'static str,
+    pub n_entries: u64,
+    /// Size of the pages of the table
+    pub size: u64,
+}
+
+/// How much space the env takes
+#[derive(Debug)]
+pub struct StorageUsage {
+    /// Size of the data file
+    pub disk_size: u64,
+    /// Size of the pages in use, without the free pages
+    pub used_size: u64,
+    /// Size the data file can grow to before the map has to be grown
+    pub map_size: u64,
+    /// Tables that exist in the env
+    pub tables: Vec<TableUsage>,
+}
+
+impl StorageUsage {
+    /// Returns the size of the free pages, which is reclaimed by compacting.
+    pub fn free_size(&self) -> u64 {
+        self.disk_size.saturating_sub(self.used_size)
+    }
+}
+
+/// Returns how much space the env and each of its tables take.
+pub fn storage_usage(env: &heed::Env) -> Result<StorageUsage> {
+    let rtxn = env.read_txn().context("Failed to create read txn")?;
+
+    let mut tables = Vec::new();
+    for name in DB_NAMES {
+        let Some(db) = env
+            .open_database::<heed::types::Bytes, heed::types::Bytes>(&rtxn, Some(name))
+            .with_context(|| format!("Failed to open `{}` db", name))?
+        else {
+            continue;
+        };
+
+        let stat = db
+            .stat(&rtxn)
+            .with_context(|| format!("Failed to get stat of `{}` db", name))?;
+        let n_pages = stat.branch_pages + stat.leaf_pages + stat.overflow_pages;
+        tables.push(TableUsage {
+            name,
+            n_entries: stat.entries as u64,
+            size: n_pages as u64 * stat.page_size as u64,
+        });
+    }
+
+    drop(rtxn);
+
+    Ok(StorageUsage {
+        disk_size: env.real_disk_size().context("Failed to get disk size")?,
+        used_size: env
+            .non_free_pages_size()
+            .context("Failed to get non-free pages size")?,
+        map_size: env.info().map_size as u64,
+        tables,
+    })
+}
+
+/// Create a new env for tests with as many max named dbs as the app and a
 /// path to a temporary directory.
 #[cfg(test)]
 pub fn new_test_env() -> (heed::Env, tempfile::TempDir) {
     let tempdir = tempfile::tempdir().unwrap();
     let env = unsafe {
         heed::EnvOpenOptions::new()
-            .map_size(100 * 1024 * 1024) // 100 MiB
-            .max_dbs(1)
+            .map_size(DEFAULT_MAP_SIZE)
+            .max_dbs(N_NAMED_DBS)
             .open(&tempdir)
             .unwrap()
     };
@@ -57,25 +220,192 @@ pub fn new_test_env() -> (heed::Env, tempfile::TempDir) {
 
 pub trait EnvExt {
     /// Run a func with a write txn and commit it.
-    fn with_write_txn<T>(&self, func: impl FnOnce(&mut heed::RwTxn<'_>) -> Result<T>) -> Result<T>;
+    ///
+    /// If the map gets full, it is grown and the func is run again with a new
+    /// write txn.
+    fn with_write_txn<T>(&self, func: impl FnMut(&mut heed::RwTxn<'_>) -> Result<T>) -> Result<T>;
 }
 
 impl EnvExt for heed::Env {
-    fn with_write_txn<T>(&self, func: impl FnOnce(&mut heed::RwTxn<'_>) -> Result<T>) -> Result<T> {
-        let start_time = Instant::now();
-
-        let mut wtxn = self.write_txn().context("Failed to create write txn")?;
-        let ret = func(&mut wtxn)?;
-        wtxn.commit().context("Failed to commit write txn")?;
-
-        // There are 16.67 ms in a 60 Hz frame, so warn if the write txn
-        // takes longer than that.
-        if start_time.elapsed() > Duration::from_millis(15) {
-            tracing::warn!("Database write txn took {:?}", start_time.elapsed());
-        } else {
-            tracing::trace!("Database write txn took {:?}", start_time.elapsed());
+    fn with_write_txn<T>(
+        &self,
+        mut func: impl FnMut(&mut heed::RwTxn<'_>) -> Result<T>,
+    ) -> Result<T> {
+        let mut n_grows = 0;
+
+        loop {
+            match run_write_txn(self, &mut func) {
+                Err(err) if is_map_full(&err) && n_grows < MAX_MAP_GROWS_PER_TXN => {
+                    grow_map(self)?;
+                    n_grows += 1;
+                }
+                ret => return ret,
+            }
         }
+    }
+}
+
+fn run_write_txn<T>(
+    env: &heed::Env,
+    func: &mut impl FnMut(&mut heed::RwTxn<'_>) -> Result<T>,
+) -> Result<T> {
+    let start_time = Instant::now();
+
+    let mut wtxn = env.write_txn().context("Failed to create write txn")?;
+    let ret = func(&mut wtxn)?;
+    wtxn.commit().context("Failed to commit write txn")?;
+
+    // There are 16.67 ms in a 60 Hz frame, so warn if the write txn
+    // takes longer than that.
+    if start_time.elapsed() > Duration::from_millis(15) {
+        tracing::warn!("Database write txn took {:?}", start_time.elapsed());
+    } else {
+        tracing::trace!("Database write txn took {:?}", start_time.elapsed());
+    }
+
+    Ok(ret)
+}
+
+fn is_map_full(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<heed::Error>(),
+            Some(heed::Error::Mdb(heed::MdbError::MapFull))
+        )
+    })
+}
+
+/// Runs `func` on a thread pool, where it may use txns of an env that is
+/// also used on the main thread.
+///
+/// The map is not grown while `func` is running, so growing it waits for
+/// `func` to finish.
+pub async fn spawn_blocking<T>(func: impl FnOnce() -> T + Send + 'static) -> T
+where
+    T: Send + 'static,
+{
+    gio::spawn_blocking(move || {
+        let _guard = RESIZE_LOCK.read().unwrap_or_else(PoisonError::into_inner);
+        func()
+    })
+    .await
+    .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+}
+
+/// Doubles the size of the map of the env.
+fn grow_map(env: &heed::Env) -> Result<()> {
+    let map_size = env.info().map_size;
+    let new_map_size = map_size
+        .checked_mul(2)
+        .context("Map size is too large to be grown")?;
+
+    let _guard = RESIZE_LOCK.write().unwrap_or_else(PoisonError::into_inner);
+
+    // SAFETY: The write txn that failed was aborted. Other txns are
+    // short-lived and created on one thread at a time, i.e., the loading
+    // thread until the env is handed to the main thread, except those in
+    // `spawn_blocking`, which have finished as the lock is held. So there are
+    // no other active txns.
+    unsafe { env.resize(new_map_size) }.context("Failed to resize map")?;
+
+    tracing::warn!(map_size, new_map_size, "Grew db map as it was full");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use heed::types::Str;
+
+    #[test]
+    fn compact() {
+        let (env, tempdir) = new_test_env();
+
+        let db = env
+            .with_write_txn(|wtxn| {
+                let db = env.create_database::<Str, Str>(wtxn, None)?;
+                for index in 0..1000 {
+                    db.put(wtxn, &index.to_string(), &"a".repeat(1000))?;
+                }
+                Ok(db)
+            })
+            .unwrap();
+        env.with_write_txn(|wtxn| {
+            for index in 1..1000 {
+                db.delete(wtxn, &index.to_string())?;
+            }
+            Ok(())
+        })
+        .unwrap();
+
+        let size_before = env.real_disk_size().unwrap();
+        super::compact(&env).unwrap();
+        env.prepare_for_closing().wait();
+
+        replace_with_staged(tempdir.path()).unwrap();
+        assert!(!tempdir.path().join(COMPACTED_DATA_FILE_NAME).exists());
+
+        let env = unsafe { heed::EnvOpenOptions::new().open(&tempdir).unwrap() };
+        assert!(env.real_disk_size().unwrap() < size_before);
+
+        let rtxn = env.read_txn().unwrap();
+        let db = env.open_database::<Str, Str>(&rtxn, None).unwrap().unwrap();
+        assert_eq!(db.len(&rtxn).unwrap(), 1);
+        assert_eq!(db.get(&rtxn, "0").unwrap(), Some("a".repeat(1000).as_str()));
+    }
+
+    #[test]
+    fn grow_map_when_full() {
+        const MAP_SIZE: usize = 64 * 1024;
+
+        let tempdir = tempfile::tempdir().unwrap();
+        let env = unsafe {
+            heed::EnvOpenOptions::new()
+                .map_size(MAP_SIZE)
+                .open(&tempdir)
+                .unwrap()
+        };
+
+        let db = env
+            .with_write_txn(|wtxn| {
+                let db = env.create_database::<Str, Str>(wtxn, None)?;
+                for index in 0..20 {
+                    db.put(wtxn, &index.to_string(), &"a".repeat(10_000))?;
+                }
+                Ok(db)
+            })
+            .unwrap();
+        assert!(env.info().map_size > MAP_SIZE);
+
+        let rtxn = env.read_txn().unwrap();
+        assert_eq!(db.len(&rtxn).unwrap(), 20);
+    }
+
+    #[test]
+    fn storage_usage() {
+        let (env, _tempdir) = new_test_env();
+
+        env.with_write_txn(|wtxn| {
+            let db = env.create_database::<Str, Str>(wtxn, Some(SONG_LIST_DB_NAME))?;
+            for index in 0..100 {
+                db.put(wtxn, &index.to_string(), &"a".repeat(100))?;
+            }
+            env.create_database::<Str, Str>(wtxn, Some(SONG_TRASH_DB_NAME))?;
+            Ok(())
+        })
+        .unwrap();
+
+        let usage = super::storage_usage(&env).unwrap();
+        assert!(usage.disk_size >= usage.used_size);
+        assert_eq!(usage.map_size, DEFAULT_MAP_SIZE as u64);
 
-        Ok(ret)
+        assert_eq!(usage.tables.len(), 2);
+        assert_eq!(usage.tables[0].name, SONG_LIST_DB_NAME);
+        assert_eq!(usage.tables[0].n_entries, 100);
+        assert!(usage.tables[0].size > 0);
+        assert_eq!(usage.tables[1].name, SONG_TRASH_DB_NAME);
+        assert_eq!(usage.tables[1].n_entries, 0);
     }
 }
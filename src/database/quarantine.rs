@@ -0,0 +1,249 @@
+use anyhow::{Context, Result};
+use heed::{
+    BytesDecode,
+    types::{Bytes, SerdeBincode},
+};
+use serde::{Deserialize, Serialize};
+
+use super::{EnvExt, QUARANTINE_DB_NAME, versioned::NewerVersionError};
+use crate::{
+    date_time::DateTime,
+    uid::{Uid, UidCodec},
+};
+
+type QuarantineDatabase = heed::Database<UidCodec, SerdeBincode<QuarantinedEntry>>;
+
+/// An entry that was moved out of its db because it could not be decoded
+#[derive(Debug, Serialize, Deserialize)]
+pub struct QuarantinedEntry {
+    pub db_name: String,
+    pub key: Vec<u8>,
+    pub value: Vec<u8>,
+    /// Why the entry could not be decoded
+    pub reason: String,
+    pub quarantined_time: DateTime,
+}
+
+/// Decodes all entries of `db`, moving the ones that can't be decoded to the
+/// quarantine db instead of failing.
+///
+/// Entries stored by a newer release are skipped and kept as they are.
+pub fn decode_all<DC, T>(
+    env: &heed::Env,
+    wtxn: &mut heed::RwTxn<'_>,
+    db: heed::Database<UidCodec, DC>,
+    db_name: &str,
+) -> Result<Vec<(Uid, T)>>
+where
+    DC: for<'a> BytesDecode<'a, DItem = T> + 'static,
+{
+    let raw_db = db.remap_types::<Bytes, Bytes>();
+
+    let mut items = Vec::new();
+    let mut invalid_keys = Vec::new();
+
+    for item in raw_db.iter(wtxn).context("Failed to iter db")? {
+        let (key, value) = item.context("Failed to read entry")?;
+
+        match decode::<DC>(key, value) {
+            Ok(Some(item)) => items.push(item),
+            Ok(None) => {}
+            Err(reason) => invalid_keys.push((key.to_vec(), reason)),
+        }
+    }
+
+    for (key, reason) in invalid_keys {
+        move_to_quarantine(env, wtxn, db_name, &key, reason)?;
+    }
+
+    Ok(items)
+}
+
+/// Returns the keys of the entries of the db with the given name whose values
+/// can't be decoded with `DC`, along with why.
+///
+/// Entries stored by a newer release are not invalid, so they are not
+/// returned.
+pub fn find_invalid<DC>(env: &heed::Env, db_name: &str) -> Result<Vec<(Vec<u8>, String)>>
+where
+    DC: for<'a> BytesDecode<'a>,
+{
+    let rtxn = env.read_txn().context("Failed to create read txn")?;
+
+    let Some(db) = env
+        .open_database::<Bytes, Bytes>(&rtxn, Some(db_name))
+        .with_context(|| format!("Failed to open `{}` db", db_name))?
+    else {
+        return Ok(Vec::new());
+    };
+
+    let mut invalid_keys = Vec::new();
+
+    for item in db
+        .iter(&rtxn)
+        .with_context(|| format!("Failed to iter `{}` db", db_name))?
+    {
+        let (key, value) = item.with_context(|| format!("Failed to read `{}` entry", db_name))?;
+
+        if let Err(reason) = decode::<DC>(key, value) {
+            invalid_keys.push((key.to_vec(), reason));
+        }
+    }
+
+    Ok(invalid_keys)
+}
+
+/// Moves the entry with the given key out of the db with the given name to the
+/// quarantine db.
+pub fn move_to_quarantine(
+    env: &heed::Env,
+    wtxn: &mut heed::RwTxn<'_>,
+    db_name: &str,
+    key: &[u8],
+    reason: String,
+) -> Result<()> {
+    let db = env
+        .open_database::<Bytes, Bytes>(wtxn, Some(db_name))
+        .with_context(|| format!("Failed to open `{}` db", db_name))?
+        .with_context(|| format!("No `{}` db", db_name))?;
+
+    let Some(value) = db
+        .get(wtxn, key)
+        .context("Failed to get entry")?
+        .map(|value| value.to_vec())
+    else {
+        return Ok(());
+    };
+
+    let quarantine_db: QuarantineDatabase = env
+        .create_database(wtxn, Some(QUARANTINE_DB_NAME))
+        .context("Failed to create quarantine db")?;
+
+    tracing::warn!(
+        db_name,
+        key = %String::from_utf8_lossy(key),
+        "Quarantining entry: {}",
+        reason
+    );
+
+    let entry = QuarantinedEntry {
+        db_name: db_name.to_string(),
+        key: key.to_vec(),
+        value,
+        reason,
+        quarantined_time: DateTime::now_utc(),
+    };
+    quarantine_db
+        .put(wtxn, &Uid::generate(), &entry)
+        .context("Failed to put entry to quarantine db")?;
+
+    db.delete(wtxn, key).context("Failed to delete entry")?;
+
+    Ok(())
+}
+
+/// Returns the number of quarantined entries.
+pub fn len(env: &heed::Env) -> Result<u64> {
+    let rtxn = env.read_txn().context("Failed to create read txn")?;
+
+    let Some(db) = env
+        .open_database::<UidCodec, SerdeBincode<QuarantinedEntry>>(&rtxn, Some(QUARANTINE_DB_NAME))
+        .context("Failed to open quarantine db")?
+    else {
+        return Ok(0);
+    };
+
+    db.len(&rtxn).context("Failed to get db len")
+}
+
+/// Removes all quarantined entries.
+pub fn clear(env: &heed::Env) -> Result<()> {
+    env.with_write_txn(|wtxn| {
+        if let Some(db) = env
+            .open_database::<UidCodec, SerdeBincode<QuarantinedEntry>>(
+                wtxn,
+                Some(QUARANTINE_DB_NAME),
+            )
+            .context("Failed to open quarantine db")?
+        {
+            db.clear(wtxn).context("Failed to clear quarantine db")?;
+        }
+        Ok(())
+    })
+}
+
+/// Decodes an entry read as raw bytes, returning why it can't be decoded on
+/// failure.
+///
+/// This returns `None` if the value was stored by a newer release, as it is
+/// not invalid and must not be quarantined.
+pub fn decode<'a, DC>(key: &'a [u8], value: &'a [u8]) -> Result<Option<(Uid, DC::DItem)>, String>
+where
+    DC: BytesDecode<'a>,
+{
+    let key = UidCodec::bytes_decode(key).map_err(|err| format!("Invalid key: {}", err))?;
+    match DC::bytes_decode(value) {
+        Ok(value) => Ok(Some((key, value))),
+        Err(err) if err.is::<NewerVersionError>() => {
+            tracing::debug!(?key, "Skipping entry stored by a newer release: {}", err);
+            Ok(None)
+        }
+        Err(err) => Err(format!("Invalid value: {}", err)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::database;
+
+    const TEST_DB_NAME: &str = "test";
+
+    #[test]
+    fn decode_all_quarantines_invalid() {
+        let (env, _tempdir) = database::new_test_env();
+
+        let db = env
+            .with_write_txn(|wtxn| {
+                let db =
+                    env.create_database::<UidCodec, SerdeBincode<u32>>(wtxn, Some(TEST_DB_NAME))?;
+                db.put(wtxn, &Uid::from("a"), &1)?;
+                db.put(wtxn, &Uid::from("c"), &3)?;
+
+                let raw_db = db.remap_types::<Bytes, Bytes>();
+                raw_db.put(wtxn, b"b", b"")?;
+                raw_db.put(wtxn, b"\xff", &3_u32.to_le_bytes())?;
+
+                Ok(db)
+            })
+            .unwrap();
+
+        assert_eq!(
+            find_invalid::<SerdeBincode<u32>>(&env, TEST_DB_NAME)
+                .unwrap()
+                .len(),
+            2
+        );
+        assert_eq!(len(&env).unwrap(), 0);
+
+        let items = env
+            .with_write_txn(|wtxn| decode_all(&env, wtxn, db, TEST_DB_NAME))
+            .unwrap();
+        assert_eq!(items, [(Uid::from("a"), 1), (Uid::from("c"), 3)]);
+
+        assert!(
+            find_invalid::<SerdeBincode<u32>>(&env, TEST_DB_NAME)
+                .unwrap()
+                .is_empty()
+        );
+        assert_eq!(len(&env).unwrap(), 2);
+
+        let rtxn = env.read_txn().unwrap();
+        assert_eq!(db.len(&rtxn).unwrap(), 2);
+        drop(rtxn);
+
+        clear(&env).unwrap();
+        assert_eq!(len(&env).unwrap(), 0);
+    }
+}
@@ -0,0 +1,305 @@
+use anyhow::{Context, Result};
+use heed::types::Bytes;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+
+use std::{borrow::Cow, error, fmt, marker::PhantomData};
+
+/// Size of the schema version that prefixes the stored values
+const VERSION_LEN: usize = 2;
+
+/// A value stored with the version of its layout, so that values stored with
+/// an older layout can still be read after the layout changes.
+///
+/// When changing the layout, increment `VERSION`, keep the previous layout as
+/// its own type, and handle its version in `upgrade`. Values stored with an
+/// older layout are upgraded when decoded, and stored with the current layout
+/// the next time they are written.
+pub trait Versioned: Serialize + DeserializeOwned {
+    /// Version of the current layout
+    const VERSION: u16;
+
+    /// Converts a value stored with an older layout `version` to the current
+    /// layout.
+    fn upgrade(version: u16, bytes: &[u8]) -> Result<Self>;
+}
+
+/// Encodes a value as its layout version, in big-endian, followed by the value
+/// in bincode.
+///
+/// Values of older versions are upgraded when decoded, while values of newer
+/// versions, written by a newer release, fail to decode with
+/// [`NewerVersionError`] instead of being misread.
+pub struct VersionedCodec<T>(PhantomData<T>);
+
+/// The value was stored by a newer release with a layout that is not known
+/// yet, so it must be kept as is instead of being treated as invalid.
+#[derive(Debug)]
+pub struct NewerVersionError {
+    pub version: u16,
+    pub supported_version: u16,
+}
+
+impl fmt::Display for NewerVersionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Value has version {}, which is newer than the supported version {}",
+            self.version, self.supported_version
+        )
+    }
+}
+
+impl error::Error for NewerVersionError {}
+
+impl<T: Versioned> heed::BytesEncode<'_> for VersionedCodec<T> {
+    type EItem = T;
+
+    fn bytes_encode(item: &Self::EItem) -> Result<Cow<'_, [u8]>, heed::BoxedError> {
+        Ok(Cow::Owned(encode(item)?))
+    }
+}
+
+impl<T: Versioned> heed::BytesDecode<'_> for VersionedCodec<T> {
+    type DItem = T;
+
+    fn bytes_decode(bytes: &[u8]) -> Result<Self::DItem, heed::BoxedError> {
+        // Keep the newer version error as is, so it can be told apart
+        decode(bytes).map_err(|err| match err.downcast::<NewerVersionError>() {
+            Ok(err) => err.into(),
+            Err(err) => err.into(),
+        })
+    }
+}
+
+fn encode<T: Versioned>(item: &T) -> Result<Vec<u8>> {
+    let mut ret = T::VERSION.to_be_bytes().to_vec();
+    bincode::serialize_into(&mut ret, item).context("Failed to serialize value")?;
+    Ok(ret)
+}
+
+/// Decodes a value encoded with [`VersionedCodec`], upgrading it if it is
+/// stored with an older layout.
+pub fn decode<T: Versioned>(bytes: &[u8]) -> Result<T> {
+    let (version, bytes) = split_version(bytes)?;
+
+    if version == T::VERSION {
+        return bincode::deserialize(bytes).context("Failed to deserialize value");
+    }
+
+    if version > T::VERSION {
+        return Err(NewerVersionError {
+            version,
+            supported_version: T::VERSION,
+        }
+        .into());
+    }
+
+    T::upgrade(version, bytes)
+        .with_context(|| format!("Failed to upgrade value from version {}", version))
+}
+
+/// Returns the layout version of the encoded value and the rest of its bytes.
+fn split_version(bytes: &[u8]) -> Result<(u16, &[u8])> {
+    let (version, rest) = bytes
+        .split_first_chunk::<VERSION_LEN>()
+        .context("Value is shorter than the version")?;
+    Ok((u16::from_be_bytes(*version), rest))
+}
+
+/// Prefixes the values of the db with the given name, if it exists, with
+/// `version`.
+///
+/// This is only used for migrating values stored before they were versioned.
+pub fn wrap_db_items(
+    env: &heed::Env,
+    wtxn: &mut heed::RwTxn<'_>,
+    db_name: &str,
+    version: u16,
+) -> Result<()> {
+    let Some(db) = env.open_database::<Bytes, Bytes>(wtxn, Some(db_name))? else {
+        return Ok(());
+    };
+
+    let new_items = db
+        .iter(wtxn)
+        .context("Failed to iter db")?
+        .map(|item| {
+            let (key, value) = item?;
+            let mut new_value = version.to_be_bytes().to_vec();
+            new_value.extend_from_slice(value);
+            Ok((key.to_vec(), new_value))
+        })
+        .collect::<Result<Vec<_>, heed::Error>>()
+        .context("Failed to collect items")?;
+
+    for (key, value) in new_items {
+        db.put(wtxn, &key, &value).context("Failed to put item")?;
+    }
+
+    Ok(())
+}
+
+/// Decodes only the leading fields of a value stored with the current layout of
+/// `T` as `Prefix`, which must have the same leading fields as `T`, so the rest
+/// is not decoded.
+///
+/// This returns `None` if the value is stored with another layout.
+pub fn decode_prefix<'a, T, Prefix>(bytes: &'a [u8]) -> Result<Option<Prefix>>
+where
+    T: Versioned,
+    Prefix: Deserialize<'a>,
+{
+    let (version, bytes) = split_version(bytes)?;
+
+    if version != T::VERSION {
+        return Ok(None);
+    }
+
+    let prefix = bincode::deserialize(bytes).context("Failed to deserialize value prefix")?;
+    Ok(Some(prefix))
+}
+
+/// Decodes `bytes`, stored with the layout `Old`, with bincode, then converts
+/// it with `convert`.
+///
+/// This is a helper for implementing [`Versioned::upgrade`].
+pub fn upgrade_from<Old, New>(bytes: &[u8], convert: impl FnOnce(Old) -> Result<New>) -> Result<New>
+where
+    Old: DeserializeOwned,
+{
+    let old = bincode::deserialize::<Old>(bytes).context("Failed to decode old value")?;
+    convert(old)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use heed::{BytesDecode, BytesEncode};
+
+    use crate::{
+        database::{self, EnvExt},
+        uid::{Uid, UidCodec},
+    };
+
+    const TEST_DB_NAME: &str = "test";
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct TestV1 {
+        a: u32,
+    }
+
+    #[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+    struct Test {
+        a: u32,
+        b: String,
+    }
+
+    impl Versioned for Test {
+        const VERSION: u16 = 2;
+
+        fn upgrade(version: u16, bytes: &[u8]) -> Result<Self> {
+            match version {
+                1 => upgrade_from(bytes, |old: TestV1| {
+                    Ok(Test {
+                        a: old.a,
+                        b: String::new(),
+                    })
+                }),
+                _ => anyhow::bail!("Unknown version {}", version),
+            }
+        }
+    }
+
+    fn encode_v1(old: &TestV1) -> Vec<u8> {
+        let mut bytes = 1_u16.to_be_bytes().to_vec();
+        bytes.extend(bincode::serialize(old).unwrap());
+        bytes
+    }
+
+    #[test]
+    fn encode_and_decode() {
+        let val = Test {
+            a: 1,
+            b: "b".to_string(),
+        };
+        let bytes = VersionedCodec::<Test>::bytes_encode(&val).unwrap();
+        assert_eq!(bytes[..VERSION_LEN], 2_u16.to_be_bytes());
+        assert_eq!(VersionedCodec::<Test>::bytes_decode(&bytes).unwrap(), val);
+
+        let bytes = encode_v1(&TestV1 { a: 2 });
+        assert_eq!(
+            VersionedCodec::<Test>::bytes_decode(&bytes).unwrap(),
+            Test {
+                a: 2,
+                b: String::new()
+            }
+        );
+
+        let mut bytes = 3_u16.to_be_bytes().to_vec();
+        bytes.extend(bincode::serialize(&val).unwrap());
+        let err = VersionedCodec::<Test>::bytes_decode(&bytes).unwrap_err();
+        assert!(err.is::<NewerVersionError>());
+
+        let bytes = 0_u16.to_be_bytes();
+        assert!(VersionedCodec::<Test>::bytes_decode(&bytes).is_err());
+
+        assert!(VersionedCodec::<Test>::bytes_decode(&[2]).is_err());
+    }
+
+    #[test]
+    fn decode_prefix_of_current_version() {
+        #[derive(Debug, PartialEq, Eq, Deserialize)]
+        struct TestPrefix {
+            a: u32,
+        }
+
+        let bytes = VersionedCodec::<Test>::bytes_encode(&Test {
+            a: 1,
+            b: "b".to_string(),
+        })
+        .unwrap();
+        assert_eq!(
+            decode_prefix::<Test, TestPrefix>(&bytes).unwrap(),
+            Some(TestPrefix { a: 1 })
+        );
+
+        let bytes = encode_v1(&TestV1 { a: 2 });
+        assert_eq!(decode_prefix::<Test, TestPrefix>(&bytes).unwrap(), None);
+
+        assert!(decode_prefix::<Test, TestPrefix>(&[2]).is_err());
+    }
+
+    #[test]
+    fn wrap_db_items_then_decode() {
+        let (env, _tempdir) = database::new_test_env();
+
+        env.with_write_txn(|wtxn| {
+            let db = env.create_database::<UidCodec, Bytes>(wtxn, Some(TEST_DB_NAME))?;
+            db.put(
+                wtxn,
+                &Uid::from("a"),
+                &bincode::serialize(&TestV1 { a: 1 })?,
+            )?;
+
+            wrap_db_items(&env, wtxn, TEST_DB_NAME, 1)?;
+
+            assert_eq!(
+                db.get(wtxn, &Uid::from("a"))?,
+                Some(&encode_v1(&TestV1 { a: 1 })[..])
+            );
+
+            let db = db.remap_data_type::<VersionedCodec<Test>>();
+            assert_eq!(
+                db.get(wtxn, &Uid::from("a"))?,
+                Some(Test {
+                    a: 1,
+                    b: String::new()
+                })
+            );
+
+            Ok(())
+        })
+        .unwrap();
+    }
+}
@@ -38,6 +38,17 @@ impl DateTime {
         .expect("format must be correct")
     }
 
+    /// Returns the time span from `other` to this.
+    pub fn difference(&self, other: &Self) -> glib::TimeSpan {
+        self.0.difference(&other.0)
+    }
+
+    /// Returns the number of microseconds since the Unix epoch, which is
+    /// cheaper to keep and compare than this.
+    pub fn to_unix_usec(&self) -> i64 {
+        self.0.to_unix() * 1_000_000 + self.0.microsecond() as i64
+    }
+
     pub fn format_iso8601(&self) -> glib::GString {
         self.0.format_iso8601().unwrap()
     }
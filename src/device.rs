@@ -1,5 +1,9 @@
 use anyhow::{Context, Result, anyhow, ensure};
 use gst::prelude::*;
+use gtk::{gio, glib};
+use serde::Deserialize;
+
+use std::ffi::OsStr;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DeviceClass {
@@ -55,3 +59,216 @@ fn validate_device(device: &gst::Device, class: DeviceClass) -> Result<()> {
 
     Ok(())
 }
+
+/// An audio stream currently being played by an application
+#[derive(Debug, Clone, PartialEq, Eq, glib::Boxed)]
+#[boxed_type(name = "MousaiAppStream", nullable)]
+pub struct AppStream {
+    /// PipeWire object serial, used to target the stream
+    pub serial: u64,
+    pub app_name: String,
+    pub media_name: Option<String>,
+}
+
+impl AppStream {
+    pub fn display_name(&self) -> String {
+        match &self.media_name {
+            Some(media_name) => format!("{} — {}", self.app_name, media_name),
+            None => self.app_name.clone(),
+        }
+    }
+}
+
+/// Returns the streams that are currently being played by applications,
+/// retrieved from `pw-dump`.
+pub async fn find_app_streams() -> Result<Vec<AppStream>> {
+    ensure_pipewire_accessible()?;
+
+    let subprocess = gio::Subprocess::newv(
+        &[OsStr::new("pw-dump")],
+        gio::SubprocessFlags::STDOUT_PIPE | gio::SubprocessFlags::STDERR_SILENCE,
+    )
+    .context("Failed to spawn pw-dump")?;
+    let (stdout, _) = subprocess
+        .communicate_future(None)
+        .await
+        .context("Failed to communicate with pw-dump")?;
+
+    ensure!(
+        subprocess.is_successful(),
+        "pw-dump exited with status {}",
+        subprocess.exit_status()
+    );
+
+    let stdout = stdout.context("No stdout")?;
+    let app_streams = parse_app_streams(&stdout)?;
+
+    tracing::debug!("Found {} app streams", app_streams.len());
+
+    Ok(app_streams)
+}
+
+/// Fails with why the streams can't be found or recorded if PipeWire is not
+/// accessible, such as when the sandbox does not allow
+/// `--filesystem=xdg-run/pipewire-0`.
+fn ensure_pipewire_accessible() -> Result<()> {
+    ensure!(
+        glib::find_program_in_path("pw-dump").is_some(),
+        "`pw-dump` is not installed"
+    );
+
+    let remote = glib::getenv("PIPEWIRE_REMOTE").unwrap_or_else(|| "pipewire-0".into());
+    let socket_path = glib::user_runtime_dir().join(remote);
+    ensure!(
+        socket_path.exists(),
+        "No access to the PipeWire socket at `{}`",
+        socket_path.display()
+    );
+
+    Ok(())
+}
+
+fn parse_app_streams(pw_dump_output: &[u8]) -> Result<Vec<AppStream>> {
+    #[derive(Deserialize)]
+    struct Object {
+        #[serde(rename = "type")]
+        type_: String,
+        info: Option<Info>,
+    }
+
+    #[derive(Deserialize)]
+    struct Info {
+        state: Option<String>,
+        #[serde(default)]
+        props: Props,
+    }
+
+    #[derive(Default, Deserialize)]
+    struct Props {
+        #[serde(rename = "media.class")]
+        media_class: Option<String>,
+        #[serde(rename = "object.serial")]
+        object_serial: Option<u64>,
+        #[serde(rename = "application.name")]
+        application_name: Option<String>,
+        #[serde(rename = "node.name")]
+        node_name: Option<String>,
+        #[serde(rename = "media.name")]
+        media_name: Option<String>,
+    }
+
+    let objects = serde_json::from_slice::<Vec<Object>>(pw_dump_output)
+        .context("Failed to parse pw-dump output")?;
+
+    let app_streams = objects
+        .into_iter()
+        .filter(|object| object.type_ == "PipeWire:Interface:Node")
+        .filter_map(|object| object.info)
+        .filter(|info| {
+            info.state.as_deref() == Some("running")
+                && info.props.media_class.as_deref() == Some("Stream/Output/Audio")
+        })
+        .filter_map(|info| {
+            let props = info.props;
+            Some(AppStream {
+                serial: props.object_serial?,
+                app_name: props.application_name.or(props.node_name)?,
+                media_name: props.media_name.filter(|name| !name.is_empty()),
+            })
+        })
+        .collect();
+
+    Ok(app_streams)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_app_streams_ok() {
+        let output = br#"[
+            {
+                "id": 30,
+                "type": "PipeWire:Interface:Node",
+                "info": {
+                    "state": "running",
+                    "props": {
+                        "media.class": "Audio/Sink",
+                        "node.name": "alsa_output.pci",
+                        "object.serial": 30
+                    }
+                }
+            },
+            {
+                "id": 81,
+                "type": "PipeWire:Interface:Node",
+                "info": {
+                    "state": "running",
+                    "props": {
+                        "media.class": "Stream/Output/Audio",
+                        "application.name": "Firefox",
+                        "media.name": "Some Video - YouTube",
+                        "object.serial": 812
+                    }
+                }
+            },
+            {
+                "id": 82,
+                "type": "PipeWire:Interface:Node",
+                "info": {
+                    "state": "idle",
+                    "props": {
+                        "media.class": "Stream/Output/Audio",
+                        "application.name": "Paused Player",
+                        "object.serial": 813
+                    }
+                }
+            },
+            {
+                "id": 83,
+                "type": "PipeWire:Interface:Node",
+                "info": {
+                    "state": "running",
+                    "props": {
+                        "media.class": "Stream/Output/Audio",
+                        "node.name": "mpv",
+                        "media.name": "",
+                        "object.serial": 814
+                    }
+                }
+            },
+            {
+                "id": 84,
+                "type": "PipeWire:Interface:Link",
+                "info": null
+            }
+        ]"#;
+
+        assert_eq!(
+            parse_app_streams(output).unwrap(),
+            vec![
+                AppStream {
+                    serial: 812,
+                    app_name: "Firefox".to_string(),
+                    media_name: Some("Some Video - YouTube".to_string()),
+                },
+                AppStream {
+                    serial: 814,
+                    app_name: "mpv".to_string(),
+                    media_name: None,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_app_streams_empty() {
+        assert_eq!(parse_app_streams(b"[]").unwrap(), vec![]);
+    }
+
+    #[test]
+    fn parse_app_streams_invalid() {
+        assert!(parse_app_streams(b"{}").is_err());
+    }
+}
@@ -1,6 +1,8 @@
 use gtk::glib::{self, subclass::prelude::*};
 
-use std::cell::OnceCell;
+use std::{cell::OnceCell, str::FromStr};
+
+use crate::external_links::ExternalLinkKey;
 
 mod imp {
     use super::*;
@@ -39,4 +41,18 @@ impl ExternalLink {
     pub fn value(&self) -> &str {
         self.imp().value.get().unwrap().as_str()
     }
+
+    /// Returns the URI to open this with in the browser, or `None` if the key
+    /// is unknown.
+    pub fn web_uri(&self) -> Option<String> {
+        let key = ExternalLinkKey::from_str(self.key()).ok()?;
+        Some(key.web_uri(self.value()))
+    }
+
+    /// Returns the URI to open this with in the service's own app, if there is
+    /// one.
+    pub fn native_uri(&self) -> Option<String> {
+        let key = ExternalLinkKey::from_str(self.key()).ok()?;
+        key.native_uri(self.value())
+    }
 }
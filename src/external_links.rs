@@ -12,10 +12,71 @@ use super::external_link::ExternalLink;
 pub enum ExternalLinkKey {
     AppleMusicUrl,
     AudDUrl,
+    MusicBrainzUrl,
     SpotifyUrl,
     YoutubeSearchTerm,
 }
 
+/// Path segments of Spotify URLs that identify the kind of item.
+const SPOTIFY_ITEM_TYPES: [&str; 6] = ["track", "album", "artist", "playlist", "episode", "show"];
+
+impl ExternalLinkKey {
+    /// Returns the URI to open the link with in the browser.
+    pub fn web_uri(self, value: &str) -> String {
+        match self {
+            Self::AppleMusicUrl | Self::AudDUrl | Self::MusicBrainzUrl | Self::SpotifyUrl => {
+                value.to_string()
+            }
+            Self::YoutubeSearchTerm => format!(
+                "https://www.youtube.com/results?search_query={}",
+                glib::Uri::escape_string(value, None, true)
+            ),
+        }
+    }
+
+    /// Returns the URI to open the link with in the service's own app, or
+    /// `None` if the service has no app or the value can't be converted.
+    ///
+    /// For example, `https://open.spotify.com/track/abc` becomes
+    /// `spotify:track:abc`.
+    pub fn native_uri(self, value: &str) -> Option<String> {
+        match self {
+            Self::AppleMusicUrl => {
+                let uri = glib::Uri::parse(value, glib::UriFlags::NONE).ok()?;
+
+                if uri.host().as_deref() != Some("music.apple.com") {
+                    return None;
+                }
+
+                let (_scheme, rest) = value.split_once("://")?;
+                Some(format!("music://{}", rest))
+            }
+            Self::SpotifyUrl => {
+                let uri = glib::Uri::parse(value, glib::UriFlags::NONE).ok()?;
+
+                if uri.host().as_deref() != Some("open.spotify.com") {
+                    return None;
+                }
+
+                // Localized URLs have an extra `intl-xx` segment before the item type
+                let path = uri.path();
+                let mut segments = path
+                    .split('/')
+                    .filter(|segment| !segment.is_empty() && !segment.starts_with("intl-"));
+                let item_type = segments.next()?;
+                let id = segments.next()?;
+
+                if !SPOTIFY_ITEM_TYPES.contains(&item_type) || id.is_empty() {
+                    return None;
+                }
+
+                Some(format!("spotify:{}:{}", item_type, id))
+            }
+            Self::AudDUrl | Self::MusicBrainzUrl | Self::YoutubeSearchTerm => None,
+        }
+    }
+}
+
 mod imp {
     use super::*;
 
@@ -88,6 +149,17 @@ impl ExternalLinks {
     pub fn get(&self, key: ExternalLinkKey) -> Option<String> {
         self.imp().map.borrow().get(key.as_ref()).cloned()
     }
+
+    pub fn from_map(map: IndexMap<String, String>) -> Self {
+        let this = Self::new();
+        this.imp().map.replace(map);
+        this
+    }
+
+    /// Returns the values by their keys.
+    pub fn to_map(&self) -> IndexMap<String, String> {
+        self.imp().map.borrow().clone()
+    }
 }
 
 impl Default for ExternalLinks {
@@ -105,11 +177,7 @@ impl Serialize for ExternalLinks {
 impl<'de> Deserialize<'de> for ExternalLinks {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         let map = IndexMap::<String, String>::deserialize(deserializer)?;
-
-        let this = Self::new();
-        this.imp().map.replace(map);
-
-        Ok(this)
+        Ok(Self::from_map(map))
     }
 }
 
@@ -119,6 +187,89 @@ mod tests {
 
     use std::{cell::Cell, rc::Rc};
 
+    #[test]
+    fn web_uri() {
+        assert_eq!(
+            ExternalLinkKey::AppleMusicUrl.web_uri("https://music.apple.com/us/album/a/1?i=2"),
+            "https://music.apple.com/us/album/a/1?i=2"
+        );
+        assert_eq!(
+            ExternalLinkKey::AudDUrl.web_uri("https://lis.tn/a"),
+            "https://lis.tn/a"
+        );
+        assert_eq!(
+            ExternalLinkKey::MusicBrainzUrl.web_uri("https://musicbrainz.org/recording/a"),
+            "https://musicbrainz.org/recording/a"
+        );
+        assert_eq!(
+            ExternalLinkKey::SpotifyUrl.web_uri("https://open.spotify.com/track/a"),
+            "https://open.spotify.com/track/a"
+        );
+        assert_eq!(
+            ExternalLinkKey::YoutubeSearchTerm.web_uri("Someone - Some song"),
+            "https://www.youtube.com/results?search_query=Someone%20-%20Some%20song"
+        );
+    }
+
+    #[test]
+    fn native_uri_apple_music() {
+        assert_eq!(
+            ExternalLinkKey::AppleMusicUrl
+                .native_uri("https://music.apple.com/us/album/warriors/1440831203?i=1440831624")
+                .as_deref(),
+            Some("music://music.apple.com/us/album/warriors/1440831203?i=1440831624")
+        );
+        assert_eq!(
+            ExternalLinkKey::AppleMusicUrl.native_uri("https://apple_music.link"),
+            None
+        );
+        assert_eq!(ExternalLinkKey::AppleMusicUrl.native_uri("not a url"), None);
+    }
+
+    #[test]
+    fn native_uri_spotify() {
+        assert_eq!(
+            ExternalLinkKey::SpotifyUrl
+                .native_uri("https://open.spotify.com/track/3JvKfv6T31zO0ini8iNItO")
+                .as_deref(),
+            Some("spotify:track:3JvKfv6T31zO0ini8iNItO")
+        );
+        assert_eq!(
+            ExternalLinkKey::SpotifyUrl
+                .native_uri("https://open.spotify.com/intl-de/album/abc?si=xyz")
+                .as_deref(),
+            Some("spotify:album:abc")
+        );
+        assert_eq!(
+            ExternalLinkKey::SpotifyUrl.native_uri("https://open.spotify.com/user/abc"),
+            None
+        );
+        assert_eq!(
+            ExternalLinkKey::SpotifyUrl.native_uri("https://open.spotify.com/track"),
+            None
+        );
+        assert_eq!(
+            ExternalLinkKey::SpotifyUrl.native_uri("https://spotify.link"),
+            None
+        );
+    }
+
+    #[test]
+    fn native_uri_unsupported() {
+        assert_eq!(
+            ExternalLinkKey::AudDUrl.native_uri("https://lis.tn/a"),
+            None
+        );
+        assert_eq!(
+            ExternalLinkKey::MusicBrainzUrl.native_uri("https://musicbrainz.org/recording/a"),
+            None
+        );
+        assert_eq!(
+            ExternalLinkKey::YoutubeSearchTerm.native_uri("Someone - Some song"),
+            None
+        );
+    }
+
     #[test]
     fn item() {
         let links = ExternalLinks::default();
@@ -6,7 +6,11 @@ use gtk::{
 
 use std::{cell::RefCell, time::Duration};
 
-use crate::recognizer::{ProviderSettings, ProviderType, TestProviderMode};
+use crate::{
+    Application,
+    integrity::IntegrityReport,
+    recognizer::{ProviderSettings, ProviderType, RecognitionCache, TestProviderMode},
+};
 
 const INSPECTOR_TITLE: &str = "Mousai";
 
@@ -39,6 +43,39 @@ mod imp {
         pub(super) test_listen_duration_row: TemplateChild<adw::SpinRow>,
         #[template_child]
         pub(super) test_recognize_duration_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) clear_recognition_cache_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub(super) recognition_cache_hits_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub(super) recognition_cache_misses_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub(super) repair_database_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub(super) check_database_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub(super) invalid_entries_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub(super) orphaned_clips_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub(super) quarantined_entries_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub(super) compact_database_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub(super) refresh_storage_usage_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub(super) disk_size_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub(super) used_size_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub(super) free_size_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub(super) map_size_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub(super) tables_row: TemplateChild<adw::ExpanderRow>,
+
+        pub(super) integrity_report: RefCell<Option<IntegrityReport>>,
+        pub(super) table_rows: RefCell<Vec<adw::ActionRow>>,
     }
 
     #[glib::object_subclass]
@@ -69,6 +106,8 @@ mod imp {
             obj.setup_rows();
 
             obj.update_test_rows_sensitivity();
+            obj.update_quarantined_entries_row();
+            obj.update_storage_rows();
         }
 
         fn dispose(&self) {
@@ -107,6 +146,106 @@ impl InspectorPage {
         imp.test_recognize_duration_row.set_sensitive(is_test);
     }
 
+    fn check_database(&self) {
+        let imp = self.imp();
+
+        let report = match Application::get().check_database_integrity() {
+            Ok(report) => report,
+            Err(err) => {
+                tracing::error!("Failed to check database integrity: {:?}", err);
+                imp.invalid_entries_row.set_subtitle("Check Failed");
+                imp.orphaned_clips_row.set_subtitle("Check Failed");
+                imp.repair_database_button.set_sensitive(false);
+                imp.integrity_report.replace(None);
+                return;
+            }
+        };
+
+        imp.invalid_entries_row
+            .set_subtitle(&report.invalid_entries.len().to_string());
+        imp.orphaned_clips_row
+            .set_subtitle(&report.orphaned_clip_ids.len().to_string());
+        imp.repair_database_button.set_sensitive(!report.is_ok());
+        imp.integrity_report.replace(Some(report));
+
+        self.update_quarantined_entries_row();
+    }
+
+    fn repair_database(&self) {
+        let Some(report) = self.imp().integrity_report.take() else {
+            return;
+        };
+
+        if let Err(err) = Application::get().repair_database(&report) {
+            tracing::error!("Failed to repair database: {:?}", err);
+        }
+
+        self.check_database();
+    }
+
+    fn update_quarantined_entries_row(&self) {
+        let subtitle = match Application::get().n_quarantined_database_entries() {
+            Ok(n_quarantined) => n_quarantined.to_string(),
+            Err(err) => {
+                tracing::warn!("Failed to get number of quarantined entries: {:?}", err);
+                "Unknown".to_string()
+            }
+        };
+        self.imp().quarantined_entries_row.set_subtitle(&subtitle);
+    }
+
+    fn update_storage_rows(&self) {
+        let imp = self.imp();
+
+        imp.compact_database_button
+            .set_sensitive(!Application::get().is_database_compaction_pending());
+
+        for row in imp.table_rows.take() {
+            imp.tables_row.remove(&row);
+        }
+
+        let usage = match Application::get().database_storage_usage() {
+            Ok(usage) => usage,
+            Err(err) => {
+                tracing::warn!("Failed to get database storage usage: {:?}", err);
+                for row in [
+                    &*imp.disk_size_row,
+                    &*imp.used_size_row,
+                    &*imp.free_size_row,
+                    &*imp.map_size_row,
+                ] {
+                    row.set_subtitle("Unknown");
+                }
+                imp.tables_row.set_subtitle("");
+                return;
+            }
+        };
+
+        imp.disk_size_row
+            .set_subtitle(&glib::format_size(usage.disk_size));
+        imp.used_size_row
+            .set_subtitle(&glib::format_size(usage.used_size));
+        imp.free_size_row
+            .set_subtitle(&glib::format_size(usage.free_size()));
+        imp.map_size_row
+            .set_subtitle(&glib::format_size(usage.map_size));
+        imp.tables_row.set_subtitle(&usage.tables.len().to_string());
+
+        for table in &usage.tables {
+            let row = adw::ActionRow::builder()
+                .title(table.name)
+                .subtitle(format!(
+                    "{} · {} entries",
+                    glib::format_size(table.size),
+                    table.n_entries
+                ))
+                .css_classes(["property"])
+                .build();
+            imp.tables_row.add_row(&row);
+            imp.table_rows.borrow_mut().push(row);
+        }
+    }
+
     fn setup_rows(&self) {
         let imp = self.imp();
 
@@ -187,5 +326,50 @@ impl InspectorPage {
                 ProviderSettings::lock().test_recognize_duration =
                     Duration::from_secs(spin_button.value() as u64);
             });
+
+        let recognition_cache = RecognitionCache::instance();
+        recognition_cache
+            .bind_property("n-hits", &*imp.recognition_cache_hits_row, "subtitle")
+            .transform_to(|_, n_hits: u32| Some(n_hits.to_string()))
+            .sync_create()
+            .build();
+        recognition_cache
+            .bind_property("n-misses", &*imp.recognition_cache_misses_row, "subtitle")
+            .transform_to(|_, n_misses: u32| Some(n_misses.to_string()))
+            .sync_create()
+            .build();
+        imp.clear_recognition_cache_button
+            .connect_clicked(move |_| recognition_cache.clear());
+
+        imp.check_database_button.connect_clicked(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.check_database();
+            }
+        ));
+        imp.repair_database_button.connect_clicked(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.repair_database();
+            }
+        ));
+
+        imp.compact_database_button.connect_clicked(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                Application::get().compact_database_on_quit();
+                obj.update_storage_rows();
+            }
+        ));
+        imp.refresh_storage_usage_button.connect_clicked(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.update_storage_rows();
+            }
+        ));
     }
 }
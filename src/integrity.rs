@@ -0,0 +1,173 @@
+use anyhow::{Context, Result};
+
+use crate::{
+    database::{
+        EnvExt, RECORDINGS_DB_NAME, SONG_CLIPS_DB_NAME, SONG_LIST_DB_NAME, SONG_TRASH_DB_NAME,
+        quarantine, versioned::VersionedCodec,
+    },
+    recognizer::Recording,
+    song::Song,
+    song_clips::{SongClipCodec, SongClips},
+    song_list::SongList,
+    song_trash::{SongTrash, TrashedSong},
+    uid::Uid,
+};
+
+/// An entry that can't be decoded
+#[derive(Debug)]
+pub struct InvalidEntry {
+    pub db_name: &'static str,
+    pub key: Vec<u8>,
+    /// Why the entry can't be decoded
+    pub reason: String,
+}
+
+/// Problems found in the db
+#[derive(Debug, Default)]
+pub struct IntegrityReport {
+    pub invalid_entries: Vec<InvalidEntry>,
+    /// Ids of the clips whose songs are neither in the history nor in the
+    /// trash
+    pub orphaned_clip_ids: Vec<Uid>,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.invalid_entries.is_empty() && self.orphaned_clip_ids.is_empty()
+    }
+}
+
+/// Checks that all entries of the db can be decoded and that there are no
+/// orphaned clips.
+pub fn check(
+    env: &heed::Env,
+    song_list: &SongList,
+    song_trash: &SongTrash,
+    song_clips: &SongClips,
+) -> Result<IntegrityReport> {
+    let mut invalid_entries = Vec::new();
+
+    let mut push_invalid = |db_name: &'static str, invalid: Vec<(Vec<u8>, String)>| {
+        invalid_entries.extend(invalid.into_iter().map(|(key, reason)| InvalidEntry {
+            db_name,
+            key,
+            reason,
+        }));
+    };
+    push_invalid(
+        SONG_LIST_DB_NAME,
+        quarantine::find_invalid::<VersionedCodec<Song>>(env, SONG_LIST_DB_NAME)?,
+    );
+    push_invalid(
+        RECORDINGS_DB_NAME,
+        quarantine::find_invalid::<VersionedCodec<Recording>>(env, RECORDINGS_DB_NAME)?,
+    );
+    push_invalid(
+        SONG_CLIPS_DB_NAME,
+        quarantine::find_invalid::<SongClipCodec>(env, SONG_CLIPS_DB_NAME)?,
+    );
+    push_invalid(
+        SONG_TRASH_DB_NAME,
+        quarantine::find_invalid::<VersionedCodec<TrashedSong>>(env, SONG_TRASH_DB_NAME)?,
+    );
+
+    let mut orphaned_clip_ids = Vec::new();
+    for song_id in song_clips.ids()? {
+        if !song_list.contains(&song_id) && !song_trash.contains(&song_id)? {
+            orphaned_clip_ids.push(song_id);
+        }
+    }
+
+    let report = IntegrityReport {
+        invalid_entries,
+        orphaned_clip_ids,
+    };
+
+    tracing::debug!(?report, "Checked db integrity");
+
+    Ok(report)
+}
+
+/// Moves the invalid entries to the quarantine db and removes the orphaned
+/// clips found in the report.
+pub fn repair(env: &heed::Env, song_clips: &SongClips, report: &IntegrityReport) -> Result<()> {
+    env.with_write_txn(|wtxn| {
+        for entry in &report.invalid_entries {
+            quarantine::move_to_quarantine(
+                env,
+                wtxn,
+                entry.db_name,
+                &entry.key,
+                entry.reason.clone(),
+            )?;
+        }
+        Ok(())
+    })?;
+
+    for song_id in &report.orphaned_clip_ids {
+        song_clips
+            .remove(song_id)
+            .context("Failed to remove orphaned clip")?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use heed::types::Bytes;
+
+    use crate::{database, date_time::DateTime};
+
+    #[test]
+    fn check_and_repair() {
+        let (env, _tempdir) = database::new_test_env();
+        let song_list = SongList::load_from_env(env.clone()).unwrap();
+        let song_trash = SongTrash::load_from_env(env.clone()).unwrap();
+        let song_clips = SongClips::load_from_env(env.clone()).unwrap();
+
+        song_list
+            .insert(Song::builder(&Uid::from("a"), "A", "A", "A").build())
+            .unwrap();
+        song_trash
+            .insert_many(
+                &[Song::builder(&Uid::from("b"), "B", "B", "B").build()],
+                &DateTime::now_utc(),
+            )
+            .unwrap();
+        for id in ["a", "b"] {
+            song_clips.insert(&Uid::from(id), b"", 10).unwrap();
+        }
+
+        let report = check(&env, &song_list, &song_trash, &song_clips).unwrap();
+        assert!(report.is_ok());
+
+        env.with_write_txn(|wtxn| {
+            let db = env
+                .open_database::<Bytes, Bytes>(wtxn, Some(SONG_LIST_DB_NAME))?
+                .unwrap();
+            db.put(wtxn, b"d", b"not a song")?;
+            Ok(())
+        })
+        .unwrap();
+        song_clips.insert(&Uid::from("e"), b"", 10).unwrap();
+
+        let report = check(&env, &song_list, &song_trash, &song_clips).unwrap();
+        assert!(!report.is_ok());
+        assert_eq!(report.invalid_entries.len(), 1);
+        assert_eq!(report.invalid_entries[0].db_name, SONG_LIST_DB_NAME);
+        assert_eq!(report.invalid_entries[0].key, b"d");
+        assert_eq!(report.orphaned_clip_ids, [Uid::from("e")]);
+
+        repair(&env, &song_clips, &report).unwrap();
+        assert!(!song_clips.contains(&Uid::from("e")).unwrap());
+        assert!(song_clips.contains(&Uid::from("a")).unwrap());
+        assert!(song_clips.contains(&Uid::from("b")).unwrap());
+        assert_eq!(quarantine::len(&env).unwrap(), 1);
+
+        let report = check(&env, &song_list, &song_trash, &song_clips).unwrap();
+        assert!(report.is_ok());
+    }
+}
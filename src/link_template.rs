@@ -0,0 +1,187 @@
+use gtk::glib;
+
+use std::collections::HashMap;
+
+use crate::{settings::Settings, song::Song};
+
+const NAME_KEY: &str = "name";
+const ICON_NAME_KEY: &str = "icon-name";
+const TEMPLATE_KEY: &str = "template";
+
+/// A user-defined link that is filled in with the metadata of a song, such as
+/// `https://bandcamp.com/search?q={artist}+{title}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LinkTemplate {
+    pub name: String,
+    pub icon_name: Option<String>,
+    pub template: String,
+}
+
+impl LinkTemplate {
+    /// Loads the link templates stored in the settings, skipping malformed ones.
+    pub fn load_all(settings: &Settings) -> Vec<Self> {
+        settings
+            .link_templates()
+            .iter()
+            .filter_map(|map| {
+                let link_template = Self::from_map(map);
+
+                if link_template.is_none() {
+                    tracing::warn!("Skipping malformed link template: {:?}", map);
+                }
+
+                link_template
+            })
+            .collect()
+    }
+
+    pub fn save_all(settings: &Settings, link_templates: &[Self]) {
+        settings.set_link_templates(link_templates.iter().map(Self::to_map).collect());
+    }
+
+    /// Whether the template expands to a URI that can be launched.
+    pub fn is_valid_template(template: &str) -> bool {
+        let uri = expand(template, |_| Some(String::new()));
+        glib::Uri::parse(&uri, glib::UriFlags::NONE).is_ok()
+    }
+
+    /// Returns the URI of this link for the song.
+    ///
+    /// Placeholders are replaced with the escaped metadata, or with nothing if
+    /// the song does not have it. Unknown placeholders are kept as is.
+    pub fn uri_for(&self, song: &Song) -> String {
+        expand(&self.template, |placeholder| match placeholder {
+            "title" => Some(song.title()),
+            "artist" => Some(song.artist()),
+            "album" => Some(song.album()),
+            "isrc" => Some(song.isrc().unwrap_or_default()),
+            _ => None,
+        })
+    }
+
+    fn from_map(map: &HashMap<String, String>) -> Option<Self> {
+        let name = map.get(NAME_KEY).filter(|name| !name.is_empty())?;
+        let template = map
+            .get(TEMPLATE_KEY)
+            .filter(|template| !template.is_empty())?;
+        let icon_name = map
+            .get(ICON_NAME_KEY)
+            .filter(|icon_name| !icon_name.is_empty());
+
+        Some(Self {
+            name: name.clone(),
+            icon_name: icon_name.cloned(),
+            template: template.clone(),
+        })
+    }
+
+    fn to_map(&self) -> HashMap<String, String> {
+        let mut map = HashMap::new();
+        map.insert(NAME_KEY.to_string(), self.name.clone());
+        map.insert(TEMPLATE_KEY.to_string(), self.template.clone());
+        if let Some(ref icon_name) = self.icon_name {
+            map.insert(ICON_NAME_KEY.to_string(), icon_name.clone());
+        }
+        map
+    }
+}
+
+/// Replaces the `{placeholder}`s in the template with the escaped values
+/// returned by `value_of`.
+fn expand(template: &str, value_of: impl Fn(&str) -> Option<String>) -> String {
+    let mut ret = String::with_capacity(template.len());
+
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        ret.push_str(&rest[..start]);
+
+        let after_start = &rest[start + 1..];
+        let Some(end) = after_start.find('}') else {
+            rest = &rest[start..];
+            break;
+        };
+
+        let placeholder = &after_start[..end];
+        match value_of(placeholder) {
+            Some(value) => ret.push_str(&glib::Uri::escape_string(&value, None, true)),
+            None => ret.push_str(&rest[start..start + end + 2]),
+        }
+
+        rest = &after_start[end + 1..];
+    }
+    ret.push_str(rest);
+
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::uid::Uid;
+
+    fn new_test_link_template(template: &str) -> LinkTemplate {
+        LinkTemplate {
+            name: "Test".to_string(),
+            icon_name: None,
+            template: template.to_string(),
+        }
+    }
+
+    #[test]
+    fn uri_for() {
+        let song = Song::builder(&Uid::from("a"), "Hello World", "Queen & Co", "Album")
+            .isrc("USUM71703861")
+            .build();
+
+        assert_eq!(
+            new_test_link_template("https://bandcamp.com/search?q={artist}+{title}").uri_for(&song),
+            "https://bandcamp.com/search?q=Queen%20%26%20Co+Hello%20World"
+        );
+        assert_eq!(
+            new_test_link_template("https://example.com/{isrc}/{album}").uri_for(&song),
+            "https://example.com/USUM71703861/Album"
+        );
+        assert_eq!(
+            new_test_link_template("https://example.com/{unknown}/{title").uri_for(&song),
+            "https://example.com/{unknown}/{title"
+        );
+
+        let song = Song::builder(&Uid::from("b"), "Title", "Artist", "").build();
+        assert_eq!(
+            new_test_link_template("https://example.com/{isrc}?q={title}").uri_for(&song),
+            "https://example.com/?q=Title"
+        );
+    }
+
+    #[test]
+    fn is_valid_template() {
+        assert!(LinkTemplate::is_valid_template(
+            "https://www.discogs.com/search?q={artist}+{title}"
+        ));
+        assert!(LinkTemplate::is_valid_template("https://genius.com"));
+        assert!(!LinkTemplate::is_valid_template("{artist} {title}"));
+        assert!(!LinkTemplate::is_valid_template(""));
+    }
+
+    #[test]
+    fn map_roundtrip() {
+        let link_template = LinkTemplate {
+            name: "Bandcamp".to_string(),
+            icon_name: Some("folder-music-symbolic".to_string()),
+            template: "https://bandcamp.com/search?q={artist}+{title}".to_string(),
+        };
+        assert_eq!(
+            LinkTemplate::from_map(&link_template.to_map()),
+            Some(link_template)
+        );
+
+        let link_template = new_test_link_template("https://example.com");
+        assert_eq!(
+            LinkTemplate::from_map(&link_template.to_map()),
+            Some(link_template)
+        );
+
+        assert_eq!(LinkTemplate::from_map(&HashMap::new()), None);
+    }
+}
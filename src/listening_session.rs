@@ -0,0 +1,124 @@
+use std::time::Duration;
+
+use crate::{date_time::DateTime, uid::Uid};
+
+/// Span of time a song was continuously heard, made up of consecutive
+/// recognitions of it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListeningSession {
+    pub song_id: Uid,
+    /// When the song was last recognized in this session
+    pub end: DateTime,
+    pub n_recognitions: u32,
+}
+
+impl ListeningSession {
+    fn new(song_id: Uid, heard_time: DateTime) -> Self {
+        Self {
+            song_id,
+            end: heard_time,
+            n_recognitions: 1,
+        }
+    }
+
+    /// Whether the song was already recognized earlier in this session.
+    pub fn is_repeat(&self) -> bool {
+        self.n_recognitions > 1
+    }
+}
+
+/// Coalesces consecutive recognitions of the same song into a single
+/// [`ListeningSession`].
+#[derive(Debug, Default)]
+pub struct ListeningSessions {
+    current: Option<ListeningSession>,
+}
+
+impl ListeningSessions {
+    /// Records that the song was recognized at `heard_time`, returning the
+    /// session it belongs to.
+    ///
+    /// It continues the current session if it is of the same song and was
+    /// last recognized at most `gap` before `heard_time`. Otherwise, a new
+    /// session is started.
+    pub fn record(
+        &mut self,
+        song_id: &Uid,
+        heard_time: &DateTime,
+        gap: Duration,
+    ) -> &ListeningSession {
+        let continues_current = self.current.as_ref().is_some_and(|session| {
+            &session.song_id == song_id
+                && heard_time >= &session.end
+                && heard_time.difference(&session.end).as_seconds() <= gap.as_secs() as i64
+        });
+
+        if continues_current {
+            let session = self.current.as_mut().unwrap();
+            session.end = heard_time.clone();
+            session.n_recognitions += 1;
+        } else {
+            self.current = Some(ListeningSession::new(song_id.clone(), heard_time.clone()));
+        }
+
+        self.current.as_ref().unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAP: Duration = Duration::from_secs(5 * 60);
+
+    fn date_time(time: &str) -> DateTime {
+        DateTime::from_iso8601(&format!("2024-01-01T{}Z", time)).unwrap()
+    }
+
+    #[test]
+    fn record_coalesces_consecutive() {
+        let mut sessions = ListeningSessions::default();
+        let a = Uid::from("a");
+
+        let session = sessions.record(&a, &date_time("10:00:00"), GAP);
+        assert!(!session.is_repeat());
+
+        let session = sessions.record(&a, &date_time("10:03:00"), GAP);
+        assert!(session.is_repeat());
+
+        let session = sessions.record(&a, &date_time("10:07:00"), GAP);
+        assert!(session.is_repeat());
+        assert_eq!(session.end, date_time("10:07:00"));
+        assert_eq!(session.n_recognitions, 3);
+    }
+
+    #[test]
+    fn record_starts_new_after_gap() {
+        let mut sessions = ListeningSessions::default();
+        let a = Uid::from("a");
+
+        sessions.record(&a, &date_time("10:00:00"), GAP);
+
+        let session = sessions.record(&a, &date_time("10:06:00"), GAP);
+        assert!(!session.is_repeat());
+        assert_eq!(session.end, date_time("10:06:00"));
+        assert_eq!(session.n_recognitions, 1);
+    }
+
+    #[test]
+    fn record_starts_new_on_other_song() {
+        let mut sessions = ListeningSessions::default();
+        let a = Uid::from("a");
+        let b = Uid::from("b");
+
+        sessions.record(&a, &date_time("10:00:00"), GAP);
+
+        let session = sessions.record(&b, &date_time("10:01:00"), GAP);
+        assert!(!session.is_repeat());
+        assert_eq!(session.song_id, b);
+
+        // Not consecutive anymore
+        let session = sessions.record(&a, &date_time("10:02:00"), GAP);
+        assert!(!session.is_repeat());
+    }
+}
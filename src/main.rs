@@ -28,6 +28,7 @@ mod album_art;
 mod application;
 mod cancelled;
 mod config;
+mod credentials;
 mod database;
 mod date_time;
 mod device;
@@ -35,15 +36,24 @@ mod external_link;
 mod external_links;
 mod i18n;
 mod inspector_page;
+mod integrity;
+mod link_template;
+mod listening_session;
 mod player;
 mod preferences_dialog;
+mod radio_monitor;
+mod recent_songs;
 mod recognizer;
+mod retention;
 mod serde_helpers;
 mod settings;
 mod song;
+mod song_clips;
+mod song_duplicates;
 mod song_filter;
 mod song_list;
 mod song_sorter;
+mod song_trash;
 mod uid;
 mod utils;
 mod window;
@@ -2,15 +2,25 @@ use adw::{prelude::*, subclass::prelude::*};
 use gettextrs::gettext;
 use gtk::glib::{self, clone};
 
-use std::cell::OnceCell;
+use std::cell::{OnceCell, RefCell};
 
-use crate::settings::{AudioSourceType, Settings};
+use crate::{
+    Application,
+    credentials::{Credentials, CredentialsState},
+    database::backups::Backup,
+    i18n::{gettext_f, ngettext_f},
+    link_template::LinkTemplate,
+    recognizer::{AudD, ProviderUsage},
+    settings::{AudioSourceType, Settings},
+    utils,
+};
 
 impl AudioSourceType {
     fn from_position(index: u32) -> Self {
         match index {
             0 => Self::Microphone,
             1 => Self::DesktopAudio,
+            2 => Self::ApplicationAudio,
             _ => unreachable!(),
         }
     }
@@ -19,6 +29,7 @@ impl AudioSourceType {
         match self {
             Self::Microphone => 0,
             Self::DesktopAudio => 1,
+            Self::ApplicationAudio => 2,
         }
     }
 }
@@ -32,11 +43,68 @@ mod imp {
     pub struct PreferencesDialog {
         #[property(get, set, construct_only)]
         pub(super) settings: OnceCell<Settings>,
+        #[property(get, set, construct_only)]
+        pub(super) credentials: OnceCell<Credentials>,
 
         #[template_child]
         pub(super) audio_source_type_row: TemplateChild<adw::ComboRow>,
         #[template_child]
+        pub(super) audio_preprocessing_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub(super) listening_session_gap_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) recognition_cache_window_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) save_song_clips_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub(super) song_clips_limit_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) history_max_age_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) history_max_songs_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) prune_preview_row: TemplateChild<adw::ExpanderRow>,
+        #[template_child]
+        pub(super) prune_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub(super) backup_interval_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) max_backups_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) backups_row: TemplateChild<adw::ExpanderRow>,
+        #[template_child]
+        pub(super) back_up_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub(super) keyring_unavailable_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
         pub(super) aud_d_api_token_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) validate_aud_d_api_token_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub(super) aud_d_usage_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub(super) aud_d_daily_limit_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) radio_stations_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub(super) radio_station_url_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) open_spotify_links_in_app_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub(super) open_apple_music_links_in_app_row: TemplateChild<adw::SwitchRow>,
+        #[template_child]
+        pub(super) link_templates_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub(super) link_template_name_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) link_template_icon_name_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) link_template_row: TemplateChild<adw::EntryRow>,
+
+        pub(super) radio_station_rows: RefCell<Vec<adw::ActionRow>>,
+        pub(super) link_template_rows: RefCell<Vec<adw::ActionRow>>,
+        pub(super) prune_preview_rows: RefCell<Vec<adw::ActionRow>>,
+        pub(super) backup_rows: RefCell<Vec<adw::ActionRow>>,
     }
 
     #[glib::object_subclass]
@@ -83,9 +151,10 @@ glib::wrapper! {
 }
 
 impl PreferencesDialog {
-    pub fn new(settings: &Settings) -> Self {
+    pub fn new(settings: &Settings, credentials: &Credentials) -> Self {
         glib::Object::builder()
             .property("settings", settings)
+            .property("credentials", credentials)
             .build()
     }
 
@@ -102,6 +171,7 @@ impl PreferencesDialog {
             .set_model(Some(&gtk::StringList::new(&[
                 &gettext("Microphone"),
                 &gettext("Desktop Audio"),
+                &gettext("Single Application"),
             ])));
         imp.audio_source_type_row
             .set_selected(settings.audio_source_type().as_position());
@@ -114,14 +184,720 @@ impl PreferencesDialog {
             }
         ));
 
+        imp.audio_preprocessing_row
+            .set_active(settings.audio_preprocessing());
+        imp.audio_preprocessing_row.connect_active_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |row| {
+                obj.settings().set_audio_preprocessing(row.is_active());
+            }
+        ));
+
+        imp.listening_session_gap_row
+            .set_value((settings.listening_session_gap() / 60) as f64);
+        imp.listening_session_gap_row.connect_value_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |row| {
+                obj.settings()
+                    .set_listening_session_gap(row.value() as u32 * 60);
+            }
+        ));
+
+        imp.recognition_cache_window_row
+            .set_value((settings.recognition_cache_window() / 60) as f64);
+        imp.recognition_cache_window_row
+            .connect_value_notify(clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |row| {
+                    obj.settings()
+                        .set_recognition_cache_window(row.value() as u32 * 60);
+                }
+            ));
+
+        imp.save_song_clips_row
+            .set_active(settings.save_song_clips());
+        imp.save_song_clips_row.connect_active_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |row| {
+                obj.settings().set_save_song_clips(row.is_active());
+            }
+        ));
+
+        imp.song_clips_limit_row
+            .set_value(settings.song_clips_limit() as f64);
+        imp.song_clips_limit_row.connect_value_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |row| {
+                obj.settings().set_song_clips_limit(row.value() as u32);
+            }
+        ));
+        imp.save_song_clips_row
+            .bind_property("active", &*imp.song_clips_limit_row, "sensitive")
+            .sync_create()
+            .build();
+
+        imp.history_max_age_row
+            .set_value(settings.history_max_age_days() as f64);
+        imp.history_max_age_row.connect_value_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |row| {
+                obj.settings().set_history_max_age_days(row.value() as u32);
+            }
+        ));
+        imp.history_max_songs_row
+            .set_value(settings.history_max_songs() as f64);
+        imp.history_max_songs_row.connect_value_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |row| {
+                obj.settings().set_history_max_songs(row.value() as u32);
+            }
+        ));
+        for key in ["history-max-age-days", "history-max-songs"] {
+            settings.connect_changed(
+                Some(key),
+                clone!(
+                    #[weak(rename_to = obj)]
+                    self,
+                    move |_, _| {
+                        obj.update_prune_preview_row();
+                    }
+                ),
+            );
+        }
+        imp.prune_button.connect_clicked(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.prune_history();
+            }
+        ));
+        self.update_prune_preview_row();
+
+        imp.backup_interval_row
+            .set_value(settings.backup_interval_days() as f64);
+        imp.backup_interval_row.connect_value_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |row| {
+                obj.settings().set_backup_interval_days(row.value() as u32);
+            }
+        ));
+        imp.max_backups_row.set_value(settings.max_backups() as f64);
+        imp.max_backups_row.connect_value_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |row| {
+                obj.settings().set_max_backups(row.value() as u32);
+            }
+        ));
+        imp.back_up_button.connect_clicked(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                utils::spawn(
+                    glib::Priority::DEFAULT,
+                    clone!(
+                        #[weak]
+                        obj,
+                        async move {
+                            obj.back_up_database().await;
+                        }
+                    ),
+                );
+            }
+        ));
+        self.update_backups_row();
+
+        let credentials = self.credentials();
+        credentials.connect_aud_d_api_token_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |credentials| {
+                obj.imp()
+                    .aud_d_api_token_row
+                    .set_text(&credentials.aud_d_api_token().unwrap_or_default());
+            }
+        ));
         imp.aud_d_api_token_row
-            .set_text(&settings.aud_d_api_token());
+            .set_text(&credentials.aud_d_api_token().unwrap_or_default());
         imp.aud_d_api_token_row.connect_apply(clone!(
             #[weak(rename_to = obj)]
             self,
             move |row| {
-                obj.settings().set_aud_d_api_token(&row.text());
+                obj.save_aud_d_api_token(row.text().trim().to_string());
+            }
+        ));
+        imp.validate_aud_d_api_token_button.connect_clicked(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.validate_aud_d_api_token();
+            }
+        ));
+
+        imp.aud_d_daily_limit_row
+            .set_value(settings.aud_d_daily_limit() as f64);
+        imp.aud_d_daily_limit_row.connect_value_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |row| {
+                obj.settings().set_aud_d_daily_limit(row.value() as u32);
+            }
+        ));
+        for key in ["aud-d-daily-limit", "provider-calls", "provider-calls-day"] {
+            settings.connect_changed(
+                Some(key),
+                clone!(
+                    #[weak(rename_to = obj)]
+                    self,
+                    move |_, _| {
+                        obj.update_aud_d_usage_row();
+                    }
+                ),
+            );
+        }
+        credentials.connect_aud_d_api_token_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.update_aud_d_usage_row();
+            }
+        ));
+        self.update_aud_d_usage_row();
+
+        credentials.connect_state_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.update_keyring_unavailable_row();
+            }
+        ));
+        self.update_keyring_unavailable_row();
+
+        imp.radio_station_url_row.connect_apply(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |row| {
+                obj.add_radio_station_url(row.text().trim());
+                row.set_text("");
             }
         ));
+        settings.connect_radio_station_urls_changed(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.update_radio_station_rows();
+            }
+        ));
+
+        imp.open_spotify_links_in_app_row
+            .set_active(settings.open_spotify_links_in_app());
+        imp.open_spotify_links_in_app_row
+            .connect_active_notify(clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |row| {
+                    obj.settings()
+                        .set_open_spotify_links_in_app(row.is_active());
+                }
+            ));
+
+        imp.open_apple_music_links_in_app_row
+            .set_active(settings.open_apple_music_links_in_app());
+        imp.open_apple_music_links_in_app_row
+            .connect_active_notify(clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |row| {
+                    obj.settings()
+                        .set_open_apple_music_links_in_app(row.is_active());
+                }
+            ));
+
+        imp.link_template_row.connect_apply(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.add_link_template();
+            }
+        ));
+        settings.connect_link_templates_changed(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.update_link_template_rows();
+            }
+        ));
+
+        self.update_radio_station_rows();
+        self.update_link_template_rows();
+    }
+
+    fn save_aud_d_api_token(&self, api_token: String) {
+        utils::spawn(
+            glib::Priority::DEFAULT,
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                async move {
+                    if let Err(err) = obj.credentials().set_aud_d_api_token(&api_token).await {
+                        tracing::error!("Failed to save AudD API token: {:?}", err);
+                        obj.add_toast(adw::Toast::new(&gettext("Failed to save API token")));
+                    }
+                }
+            ),
+        );
+    }
+
+    fn validate_aud_d_api_token(&self) {
+        let imp = self.imp();
+
+        let api_token = imp.aud_d_api_token_row.text().trim().to_string();
+        if api_token.is_empty() {
+            self.add_toast(adw::Toast::new(&gettext(
+                "No API token given. A few trials per day are used instead",
+            )));
+            return;
+        }
+
+        imp.validate_aud_d_api_token_button.set_sensitive(false);
+
+        utils::spawn(
+            glib::Priority::DEFAULT,
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                async move {
+                    let res = AudD::new(Some(&api_token)).validate_token().await;
+
+                    let message = match res {
+                        Ok(()) => gettext("API token is valid"),
+                        Err(err) => {
+                            tracing::debug!("API token validation failed: {:?}", err);
+                            err.title()
+                        }
+                    };
+                    obj.add_toast(adw::Toast::new(&message));
+
+                    obj.imp()
+                        .validate_aud_d_api_token_button
+                        .set_sensitive(true);
+                }
+            ),
+        );
+    }
+
+    fn update_aud_d_usage_row(&self) {
+        let imp = self.imp();
+
+        let has_token = self.credentials().aud_d_api_token().is_some();
+        let usage = ProviderUsage::today(&self.settings(), has_token);
+
+        let calls = usage.calls.to_string();
+        let subtitle = if let Some(daily_limit) = usage.daily_limit {
+            gettext_f(
+                // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+                "{calls} of {daily_limit}",
+                &[("calls", &calls), ("daily_limit", &daily_limit.to_string())],
+            )
+        } else {
+            calls
+        };
+        imp.aud_d_usage_row.set_subtitle(&subtitle);
+
+        if usage.is_near_limit() {
+            imp.aud_d_usage_row.add_css_class("warning");
+        } else {
+            imp.aud_d_usage_row.remove_css_class("warning");
+        }
+
+        imp.aud_d_daily_limit_row.set_visible(has_token);
+    }
+
+    fn update_keyring_unavailable_row(&self) {
+        self.imp()
+            .keyring_unavailable_row
+            .set_visible(self.credentials().state() == CredentialsState::Unavailable);
+    }
+
+    fn add_radio_station_url(&self, url: &str) {
+        let is_valid = glib::Uri::parse(url, glib::UriFlags::NONE)
+            .is_ok_and(|uri| matches!(uri.scheme().as_str(), "http" | "https"));
+
+        if !is_valid {
+            self.add_toast(adw::Toast::new(&gettext("Invalid stream URL")));
+            return;
+        }
+
+        let settings = self.settings();
+
+        let mut urls = settings.radio_station_urls();
+
+        if urls.iter().any(|u| u == url) {
+            return;
+        }
+
+        urls.push(url.to_string());
+        settings.set_radio_station_urls(&urls.iter().map(|u| u.as_str()).collect::<Vec<_>>());
+    }
+
+    fn remove_radio_station_url(&self, url: &str) {
+        let settings = self.settings();
+
+        let urls = settings.radio_station_urls();
+        settings.set_radio_station_urls(
+            &urls
+                .iter()
+                .map(|u| u.as_str())
+                .filter(|u| *u != url)
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    fn update_radio_station_rows(&self) {
+        let imp = self.imp();
+
+        for row in imp.radio_station_rows.take() {
+            imp.radio_stations_group.remove(&row);
+        }
+
+        let rows = self
+            .settings()
+            .radio_station_urls()
+            .into_iter()
+            .map(|url| {
+                let remove_button = gtk::Button::builder()
+                    .icon_name("user-trash-symbolic")
+                    .tooltip_text(gettext("Remove"))
+                    .valign(gtk::Align::Center)
+                    .css_classes(["flat"])
+                    .build();
+                remove_button.connect_clicked(clone!(
+                    #[weak(rename_to = obj)]
+                    self,
+                    #[strong]
+                    url,
+                    move |_| {
+                        obj.remove_radio_station_url(&url);
+                    }
+                ));
+
+                let row = adw::ActionRow::builder()
+                    .title(glib::markup_escape_text(&url))
+                    .title_lines(1)
+                    .build();
+                row.add_suffix(&remove_button);
+                imp.radio_stations_group.add(&row);
+
+                row
+            })
+            .collect();
+        imp.radio_station_rows.replace(rows);
+    }
+
+    fn add_link_template(&self) {
+        let imp = self.imp();
+
+        let name = imp.link_template_name_row.text().trim().to_string();
+        let icon_name = imp.link_template_icon_name_row.text().trim().to_string();
+        let template = imp.link_template_row.text().trim().to_string();
+
+        if name.is_empty() {
+            self.add_toast(adw::Toast::new(&gettext("Link name is required")));
+            imp.link_template_name_row.grab_focus();
+            return;
+        }
+
+        if !LinkTemplate::is_valid_template(&template) {
+            self.add_toast(adw::Toast::new(&gettext("Invalid URL template")));
+            return;
+        }
+
+        let settings = self.settings();
+
+        let mut link_templates = LinkTemplate::load_all(&settings);
+        link_templates.push(LinkTemplate {
+            name,
+            icon_name: (!icon_name.is_empty()).then_some(icon_name),
+            template,
+        });
+        LinkTemplate::save_all(&settings, &link_templates);
+
+        imp.link_template_name_row.set_text("");
+        imp.link_template_icon_name_row.set_text("");
+        imp.link_template_row.set_text("");
+    }
+
+    fn remove_link_template(&self, index: usize) {
+        let settings = self.settings();
+
+        let mut link_templates = LinkTemplate::load_all(&settings);
+        if index < link_templates.len() {
+            link_templates.remove(index);
+            LinkTemplate::save_all(&settings, &link_templates);
+        }
+    }
+
+    fn prune_history(&self) {
+        match Application::get().prune_history() {
+            Ok(summary) => {
+                tracing::debug!("Pruned {} songs from preferences", summary.song_ids.len());
+            }
+            Err(err) => {
+                tracing::error!("Failed to prune history: {:?}", err);
+                self.add_toast(adw::Toast::new(&gettext("Failed to remove old songs")));
+            }
+        }
+
+        self.update_prune_preview_row();
+    }
+
+    fn update_prune_preview_row(&self) {
+        const MAX_SONG_ROWS: usize = 50;
+
+        let imp = self.imp();
+
+        for row in imp.prune_preview_rows.take() {
+            imp.prune_preview_row.remove(&row);
+        }
+
+        let Some(summary) = Application::get().preview_prune_history() else {
+            imp.prune_preview_row.set_visible(false);
+            return;
+        };
+
+        let n_songs = summary.song_ids.len() as u32;
+        let n_recordings = summary.n_recordings as u32;
+        let subtitle = if summary.is_empty() {
+            gettext("Nothing")
+        } else {
+            [
+                (n_songs > 0).then(|| {
+                    ngettext_f(
+                        // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+                        "{n} song",
+                        "{n} songs",
+                        n_songs,
+                        &[("n", &n_songs.to_string())],
+                    )
+                }),
+                (n_recordings > 0).then(|| {
+                    ngettext_f(
+                        // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+                        "{n} saved recording",
+                        "{n} saved recordings",
+                        n_recordings,
+                        &[("n", &n_recordings.to_string())],
+                    )
+                }),
+            ]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>()
+            .join(", ")
+        };
+        imp.prune_preview_row.set_subtitle(&subtitle);
+        imp.prune_preview_row.set_enable_expansion(n_songs > 0);
+        imp.prune_button.set_sensitive(!summary.is_empty());
+
+        // Only the songs that are shown are read
+        let app = Application::get();
+        let mut rows = summary
+            .song_ids
+            .iter()
+            .take(MAX_SONG_ROWS)
+            .filter_map(|song_id| app.history_song(song_id))
+            .map(|song| {
+                adw::ActionRow::builder()
+                    .title(glib::markup_escape_text(&song.title()))
+                    .subtitle(glib::markup_escape_text(&song.artist()))
+                    .build()
+            })
+            .collect::<Vec<_>>();
+        if summary.song_ids.len() > MAX_SONG_ROWS {
+            let n_more = (summary.song_ids.len() - MAX_SONG_ROWS) as u32;
+            rows.push(
+                adw::ActionRow::builder()
+                    .title(ngettext_f(
+                        // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+                        "And {n} more song",
+                        "And {n} more songs",
+                        n_more,
+                        &[("n", &n_more.to_string())],
+                    ))
+                    .build(),
+            );
+        }
+        for row in &rows {
+            imp.prune_preview_row.add_row(row);
+        }
+        imp.prune_preview_rows.replace(rows);
+    }
+
+    async fn back_up_database(&self) {
+        let imp = self.imp();
+
+        imp.back_up_button.set_sensitive(false);
+
+        if let Err(err) = Application::get().back_up_database().await {
+            tracing::error!("Failed to back up database: {:?}", err);
+            self.add_toast(adw::Toast::new(&gettext("Failed to back up")));
+        }
+
+        imp.back_up_button.set_sensitive(true);
+
+        self.update_backups_row();
+    }
+
+    fn present_restore_backup_dialog(&self, backup: Backup) {
+        const CANCEL_RESPONSE_ID: &str = "cancel";
+        const RESTORE_RESPONSE_ID: &str = "restore";
+
+        let dialog = adw::AlertDialog::builder()
+            .heading(gettext("Restore Backup?"))
+            .body(gettext_f(
+                // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+                "Your history will be replaced with the backup from {created_time}. Mousai will quit to finish restoring",
+                &[(
+                    "created_time",
+                    &backup.created_time().to_local().fuzzy_display(),
+                )],
+            ))
+            .default_response(CANCEL_RESPONSE_ID)
+            .close_response(CANCEL_RESPONSE_ID)
+            .build();
+        dialog.add_response(CANCEL_RESPONSE_ID, &gettext("Cancel"));
+        dialog.add_response(RESTORE_RESPONSE_ID, &gettext("_Restore and Quit"));
+        dialog.set_response_appearance(RESTORE_RESPONSE_ID, adw::ResponseAppearance::Destructive);
+        dialog.connect_response(
+            Some(RESTORE_RESPONSE_ID),
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |_, _| {
+                    let app = Application::get();
+
+                    match app.restore_database_backup(&backup) {
+                        Ok(()) => app.quit(),
+                        Err(err) => {
+                            tracing::error!("Failed to restore backup: {:?}", err);
+                            obj.add_toast(adw::Toast::new(&gettext("Failed to restore backup")));
+                        }
+                    }
+                }
+            ),
+        );
+        dialog.present(Some(self));
+    }
+
+    fn update_backups_row(&self) {
+        let imp = self.imp();
+
+        for row in imp.backup_rows.take() {
+            imp.backups_row.remove(&row);
+        }
+
+        let backups = Application::get().database_backups().unwrap_or_else(|err| {
+            tracing::error!("Failed to list backups: {:?}", err);
+            Vec::new()
+        });
+
+        imp.backups_row.set_subtitle(&backups.first().map_or_else(
+            || gettext("None"),
+            |last_backup| {
+                gettext_f(
+                    // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+                    "Last backed up {created_time}",
+                    &[(
+                        "created_time",
+                        &last_backup.created_time().to_local().fuzzy_display(),
+                    )],
+                )
+            },
+        ));
+        imp.backups_row.set_enable_expansion(!backups.is_empty());
+
+        let rows = backups
+            .into_iter()
+            .map(|backup| {
+                let size = backup
+                    .path()
+                    .metadata()
+                    .map(|metadata| glib::format_size(metadata.len()).to_string())
+                    .unwrap_or_default();
+                let row = adw::ActionRow::builder()
+                    .title(backup.created_time().to_local().fuzzy_display())
+                    .subtitle(size)
+                    .build();
+
+                let restore_button = gtk::Button::builder()
+                    .label(gettext("Restore"))
+                    .valign(gtk::Align::Center)
+                    .build();
+                restore_button.connect_clicked(clone!(
+                    #[weak(rename_to = obj)]
+                    self,
+                    move |_| {
+                        obj.present_restore_backup_dialog(backup.clone());
+                    }
+                ));
+                row.add_suffix(&restore_button);
+
+                row
+            })
+            .collect::<Vec<_>>();
+        for row in &rows {
+            imp.backups_row.add_row(row);
+        }
+        imp.backup_rows.replace(rows);
+    }
+
+    fn update_link_template_rows(&self) {
+        let imp = self.imp();
+
+        for row in imp.link_template_rows.take() {
+            imp.link_templates_group.remove(&row);
+        }
+
+        let rows = LinkTemplate::load_all(&self.settings())
+            .into_iter()
+            .enumerate()
+            .map(|(index, link_template)| {
+                let remove_button = gtk::Button::builder()
+                    .icon_name("user-trash-symbolic")
+                    .tooltip_text(gettext("Remove"))
+                    .valign(gtk::Align::Center)
+                    .css_classes(["flat"])
+                    .build();
+                remove_button.connect_clicked(clone!(
+                    #[weak(rename_to = obj)]
+                    self,
+                    move |_| {
+                        obj.remove_link_template(index);
+                    }
+                ));
+
+                let row = adw::ActionRow::builder()
+                    .title(glib::markup_escape_text(&link_template.name))
+                    .subtitle(glib::markup_escape_text(&link_template.template))
+                    .subtitle_lines(1)
+                    .build();
+                if let Some(ref icon_name) = link_template.icon_name {
+                    row.add_prefix(&gtk::Image::from_icon_name(icon_name));
+                }
+                row.add_suffix(&remove_button);
+                imp.link_templates_group.add(&row);
+
+                row
+            })
+            .collect();
+        imp.link_template_rows.replace(rows);
     }
 }
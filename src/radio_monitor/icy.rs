@@ -0,0 +1,410 @@
+use anyhow::{Context, Result, ensure};
+use gtk::{
+    gio::{self, prelude::*},
+    glib,
+};
+use soup::prelude::*;
+
+use std::time::Duration;
+
+const READ_CHUNK_SIZE: usize = 8192;
+
+/// Bitrate assumed for streams whose server does not advertise it, in kbps,
+/// which is the most common one for radio streams
+const DEFAULT_BITRATE_KBPS: u32 = 128;
+
+/// Audio and metadata read from an ICY stream
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct IcyChunk {
+    pub audio: Vec<u8>,
+    /// Raw metadata blocks, e.g., `StreamTitle='Artist - Title';`
+    pub metadata: Vec<String>,
+}
+
+/// An HTTP (Icecast or SHOUTcast) audio stream where ICY metadata blocks are
+/// interleaved within the audio data.
+pub struct IcyStream {
+    input_stream: gio::InputStream,
+    demuxer: IcyDemuxer,
+    name: Option<String>,
+    /// In kbps
+    bitrate: Option<u32>,
+}
+
+impl IcyStream {
+    pub async fn connect(session: &soup::Session, url: &str) -> Result<Self> {
+        let message = soup::Message::new("GET", url).context("Failed to create GET message")?;
+        message
+            .request_headers()
+            .context("No request headers")?
+            .append("Icy-MetaData", "1");
+
+        let input_stream = session
+            .send_future(&message, glib::Priority::default())
+            .await
+            .context("Failed to send message")?;
+
+        let status = message.status();
+        ensure!(status == soup::Status::Ok, "Got status `{:?}`", status);
+
+        let response_headers = message.response_headers().context("No response headers")?;
+        let metaint = response_headers
+            .one("icy-metaint")
+            .and_then(|metaint| metaint.parse::<usize>().ok())
+            .filter(|metaint| *metaint > 0);
+        let name = response_headers
+            .one("icy-name")
+            .map(|name| name.to_string())
+            .filter(|name| !name.is_empty());
+        let bitrate = response_headers
+            .one("icy-br")
+            .and_then(|bitrate| parse_bitrate(&bitrate));
+
+        tracing::debug!(url, ?metaint, ?name, ?bitrate, "Connected to stream");
+
+        Ok(Self {
+            input_stream,
+            demuxer: IcyDemuxer::new(metaint),
+            name,
+            bitrate,
+        })
+    }
+
+    /// Name of the station as advertised by the server
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Whether the server interleaves metadata within the audio
+    pub fn has_metadata(&self) -> bool {
+        self.demuxer.metaint.is_some()
+    }
+
+    /// Returns how long `n_bytes` of the audio plays for, from the bitrate
+    /// advertised by the server.
+    ///
+    /// Servers send a burst of buffered audio on connect, so this must be used
+    /// instead of how long it took to receive the audio.
+    pub fn audio_duration(&self, n_bytes: usize) -> Duration {
+        let bitrate = self.bitrate.unwrap_or(DEFAULT_BITRATE_KBPS);
+        Duration::from_secs_f64(n_bytes as f64 * 8.0 / (bitrate as f64 * 1000.0))
+    }
+
+    /// Returns `None` if the stream has ended.
+    pub async fn next_chunk(&mut self) -> Result<Option<IcyChunk>> {
+        let bytes = self
+            .input_stream
+            .read_bytes_future(READ_CHUNK_SIZE, glib::Priority::default())
+            .await
+            .context("Failed to read stream")?;
+
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(self.demuxer.push(&bytes)))
+    }
+}
+
+#[derive(Debug)]
+enum DemuxerState {
+    Audio { remaining: usize },
+    MetadataLength,
+    Metadata { remaining: usize, buf: Vec<u8> },
+}
+
+/// Splits ICY metadata blocks from the audio data.
+///
+/// Every `metaint` bytes of audio is followed by a byte, which when multiplied
+/// by 16 is the length of the metadata block that follows it.
+#[derive(Debug)]
+struct IcyDemuxer {
+    metaint: Option<usize>,
+    state: DemuxerState,
+}
+
+impl IcyDemuxer {
+    fn new(metaint: Option<usize>) -> Self {
+        Self {
+            metaint,
+            state: DemuxerState::Audio {
+                remaining: metaint.unwrap_or_default(),
+            },
+        }
+    }
+
+    fn push(&mut self, mut bytes: &[u8]) -> IcyChunk {
+        let mut chunk = IcyChunk::default();
+
+        let Some(metaint) = self.metaint else {
+            chunk.audio.extend_from_slice(bytes);
+            return chunk;
+        };
+
+        while !bytes.is_empty() {
+            match self.state {
+                DemuxerState::Audio { ref mut remaining } => {
+                    let n = (*remaining).min(bytes.len());
+                    chunk.audio.extend_from_slice(&bytes[..n]);
+                    bytes = &bytes[n..];
+                    *remaining -= n;
+
+                    if *remaining == 0 {
+                        self.state = DemuxerState::MetadataLength;
+                    }
+                }
+                DemuxerState::MetadataLength => {
+                    let len = bytes[0] as usize * 16;
+                    bytes = &bytes[1..];
+
+                    self.state = if len == 0 {
+                        DemuxerState::Audio { remaining: metaint }
+                    } else {
+                        DemuxerState::Metadata {
+                            remaining: len,
+                            buf: Vec::with_capacity(len),
+                        }
+                    };
+                }
+                DemuxerState::Metadata {
+                    ref mut remaining,
+                    ref mut buf,
+                } => {
+                    let n = (*remaining).min(bytes.len());
+                    buf.extend_from_slice(&bytes[..n]);
+                    bytes = &bytes[n..];
+                    *remaining -= n;
+
+                    if *remaining == 0 {
+                        // Metadata blocks are padded with NUL bytes
+                        let metadata = String::from_utf8_lossy(buf)
+                            .trim_end_matches('\0')
+                            .to_string();
+                        chunk.metadata.push(metadata);
+
+                        self.state = DemuxerState::Audio { remaining: metaint };
+                    }
+                }
+            }
+        }
+
+        chunk
+    }
+}
+
+/// Parses an `icy-br` header, which some servers set to a list of bitrates,
+/// e.g., `128,128`.
+///
+/// Returns `None` if it is not a positive bitrate.
+fn parse_bitrate(header: &str) -> Option<u32> {
+    header
+        .split(',')
+        .next()?
+        .trim()
+        .parse::<u32>()
+        .ok()
+        .filter(|bitrate| *bitrate > 0)
+}
+
+/// Extracts the `StreamTitle` from an ICY metadata block.
+///
+/// Returns `None` if there is no `StreamTitle` or if it is empty.
+pub fn parse_stream_title(metadata: &str) -> Option<&str> {
+    const PREFIX: &str = "StreamTitle='";
+
+    let start = metadata.find(PREFIX)? + PREFIX.len();
+    let rest = &metadata[start..];
+
+    // The title itself may contain `'`, so look for the field terminator.
+    let end = rest.find("';").or_else(|| rest.rfind('\''))?;
+    let title = rest[..end].trim();
+
+    (!title.is_empty()).then_some(title)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn metadata_block(metadata: &str) -> Vec<u8> {
+        let len = metadata.len().div_ceil(16);
+        let mut block = vec![len as u8];
+        block.extend_from_slice(metadata.as_bytes());
+        block.resize(1 + len * 16, 0);
+        block
+    }
+
+    fn icy_body(metaint: usize) -> (Vec<u8>, Vec<u8>) {
+        let audio_1 = vec![1; metaint];
+        let audio_2 = vec![2; metaint];
+        let audio_3 = vec![3; metaint / 2];
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&audio_1);
+        body.extend(metadata_block("StreamTitle='Some Artist - It's A Title';"));
+        body.extend_from_slice(&audio_2);
+        body.push(0);
+        body.extend_from_slice(&audio_3);
+
+        (body, [audio_1, audio_2, audio_3].concat())
+    }
+
+    #[test]
+    fn demuxer_no_metaint() {
+        let mut demuxer = IcyDemuxer::new(None);
+        assert_eq!(
+            demuxer.push(b"StreamTitle='A';"),
+            IcyChunk {
+                audio: b"StreamTitle='A';".to_vec(),
+                metadata: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn demuxer_whole() {
+        let (body, audio) = icy_body(16);
+
+        let mut demuxer = IcyDemuxer::new(Some(16));
+        assert_eq!(
+            demuxer.push(&body),
+            IcyChunk {
+                audio,
+                metadata: vec!["StreamTitle='Some Artist - It's A Title';".to_string()],
+            }
+        );
+    }
+
+    #[test]
+    fn demuxer_split() {
+        let (body, audio) = icy_body(16);
+
+        for chunk_size in 1..body.len() {
+            let mut demuxer = IcyDemuxer::new(Some(16));
+
+            let mut all_audio = Vec::new();
+            let mut all_metadata = Vec::new();
+            for bytes in body.chunks(chunk_size) {
+                let chunk = demuxer.push(bytes);
+                all_audio.extend(chunk.audio);
+                all_metadata.extend(chunk.metadata);
+            }
+
+            assert_eq!(all_audio, audio, "chunk size {}", chunk_size);
+            assert_eq!(
+                all_metadata,
+                vec!["StreamTitle='Some Artist - It's A Title';".to_string()],
+                "chunk size {}",
+                chunk_size
+            );
+        }
+    }
+
+    #[test]
+    fn stream_title() {
+        assert_eq!(
+            parse_stream_title("StreamTitle='Artist - Title';"),
+            Some("Artist - Title")
+        );
+        assert_eq!(
+            parse_stream_title("StreamTitle='Artist - Title';StreamUrl='https://example.com';"),
+            Some("Artist - Title")
+        );
+        assert_eq!(
+            parse_stream_title("StreamTitle='Guns N' Roses - Don't Cry';"),
+            Some("Guns N' Roses - Don't Cry")
+        );
+        assert_eq!(
+            parse_stream_title("StreamTitle=' Artist - Title '"),
+            Some("Artist - Title")
+        );
+        assert_eq!(parse_stream_title("StreamTitle='';"), None);
+        assert_eq!(parse_stream_title("StreamUrl='https://example.com';"), None);
+        assert_eq!(parse_stream_title(""), None);
+    }
+
+    #[test]
+    fn bitrate() {
+        assert_eq!(parse_bitrate("128"), Some(128));
+        assert_eq!(parse_bitrate(" 64,64"), Some(64));
+        assert_eq!(parse_bitrate("0"), None);
+        assert_eq!(parse_bitrate("high"), None);
+        assert_eq!(parse_bitrate(""), None);
+    }
+
+    fn serve(
+        metaint: Option<usize>,
+        bitrate: Option<u32>,
+        body: Vec<u8>,
+    ) -> (soup::Server, String) {
+        let server = glib::Object::new::<soup::Server>();
+        server.add_handler(None, move |_, message, _, _| {
+            let response_headers = message.response_headers().unwrap();
+            if let Some(metaint) = metaint {
+                response_headers.append("icy-metaint", &metaint.to_string());
+            }
+            if let Some(bitrate) = bitrate {
+                response_headers.append("icy-br", &bitrate.to_string());
+            }
+            response_headers.append("icy-name", "Test Radio");
+            message.set_status(200, None);
+            message.set_response(Some("audio/mpeg"), soup::MemoryUse::Copy, &body);
+        });
+        server
+            .listen_local(0, soup::ServerListenOptions::IPV4_ONLY)
+            .unwrap();
+        let url = server.uris()[0].to_str().to_string();
+        (server, url)
+    }
+
+    async fn read_all(stream: &mut IcyStream) -> IcyChunk {
+        let mut all = IcyChunk::default();
+        while let Some(chunk) = stream.next_chunk().await.unwrap() {
+            all.audio.extend(chunk.audio);
+            all.metadata.extend(chunk.metadata);
+        }
+        all
+    }
+
+    #[test]
+    fn stream_from_server() {
+        let (body, audio) = icy_body(16_000);
+        let (_server, url) = serve(Some(16_000), Some(64), body);
+
+        glib::MainContext::default().block_on(async move {
+            let session = soup::Session::new();
+            let mut stream = IcyStream::connect(&session, &url).await.unwrap();
+            assert!(stream.has_metadata());
+            assert_eq!(stream.name(), Some("Test Radio"));
+            assert_eq!(stream.audio_duration(16_000), Duration::from_secs(2));
+
+            assert_eq!(
+                read_all(&mut stream).await,
+                IcyChunk {
+                    audio,
+                    metadata: vec!["StreamTitle='Some Artist - It's A Title';".to_string()],
+                }
+            );
+        });
+    }
+
+    #[test]
+    fn stream_from_server_no_metadata() {
+        let (_server, url) = serve(None, None, vec![7; 20_000]);
+
+        glib::MainContext::default().block_on(async move {
+            let session = soup::Session::new();
+            let mut stream = IcyStream::connect(&session, &url).await.unwrap();
+            assert!(!stream.has_metadata());
+            assert_eq!(stream.audio_duration(16_000), Duration::from_secs(1));
+
+            assert_eq!(
+                read_all(&mut stream).await,
+                IcyChunk {
+                    audio: vec![7; 20_000],
+                    metadata: Vec::new(),
+                }
+            );
+        });
+    }
+}
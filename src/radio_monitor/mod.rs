@@ -0,0 +1,339 @@
+mod icy;
+
+use anyhow::Result;
+use gtk::{
+    glib::{self, clone, closure_local},
+    prelude::*,
+    subclass::prelude::*,
+};
+
+use std::{
+    cell::{OnceCell, RefCell},
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+use self::icy::IcyStream;
+use crate::{
+    Application,
+    date_time::DateTime,
+    external_links::ExternalLinkKey,
+    recognizer::{ProviderSettings, top_song},
+    settings::Settings,
+    song::Song,
+    uid::Uid,
+    utils,
+};
+
+/// Minimum time between provider calls on streams without usable metadata
+const RECOGNIZE_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Time to wait before reconnecting to a stream that failed or ended
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+mod imp {
+    use glib::subclass::Signal;
+    use once_cell::sync::Lazy;
+
+    use super::*;
+
+    #[derive(Default)]
+    pub struct RadioMonitor {
+        pub(super) session: OnceCell<soup::Session>,
+        pub(super) stations: RefCell<HashMap<String, glib::JoinHandle<()>>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for RadioMonitor {
+        const NAME: &'static str = "MousaiRadioMonitor";
+        type Type = super::RadioMonitor;
+    }
+
+    impl ObjectImpl for RadioMonitor {
+        fn dispose(&self) {
+            for (_, handle) in self.stations.take() {
+                handle.abort();
+            }
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    Signal::builder("song-recognized")
+                        .param_types([Song::static_type()])
+                        .build(),
+                ]
+            });
+
+            SIGNALS.as_ref()
+        }
+    }
+}
+
+glib::wrapper! {
+    /// Monitors internet radio streams in the background and reports the
+    /// songs played on them.
+    pub struct RadioMonitor(ObjectSubclass<imp::RadioMonitor>);
+}
+
+impl RadioMonitor {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    pub fn connect_song_recognized<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &Song) + 'static,
+    {
+        self.connect_closure(
+            "song-recognized",
+            false,
+            closure_local!(|obj: &Self, song: &Song| {
+                f(obj, song);
+            }),
+        )
+    }
+
+    fn emit_song_recognized(&self, song: &Song) {
+        self.emit_by_name::<()>("song-recognized", &[song]);
+    }
+
+    /// Starts monitoring the stations in the settings, and keeps them in sync
+    /// as the settings change.
+    ///
+    /// Must only be called once.
+    pub fn bind_settings(&self, settings: &Settings) {
+        settings.connect_radio_station_urls_changed(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |settings| {
+                obj.update_stations(&settings.radio_station_urls());
+            }
+        ));
+
+        self.update_stations(&settings.radio_station_urls());
+    }
+
+    fn session(&self) -> &soup::Session {
+        // A separate session is used so never-ending responses won't go
+        // through the app's cache.
+        self.imp().session.get_or_init(soup::Session::new)
+    }
+
+    fn update_stations(&self, urls: &[String]) {
+        let mut stations = self.imp().stations.borrow_mut();
+
+        stations.retain(|url, handle| {
+            let is_retained = urls.contains(url);
+
+            if !is_retained {
+                handle.abort();
+                tracing::debug!(url, "Stopped monitoring station");
+            }
+
+            is_retained
+        });
+
+        for url in urls {
+            if stations.contains_key(url) {
+                continue;
+            }
+
+            let handle = utils::spawn(
+                glib::Priority::default_idle(),
+                monitor_station(
+                    self.session().clone(),
+                    url.clone(),
+                    clone!(
+                        #[weak(rename_to = obj)]
+                        self,
+                        move |song| {
+                            obj.emit_song_recognized(song);
+                        }
+                    ),
+                ),
+            );
+            stations.insert(url.clone(), handle);
+
+            tracing::debug!(url, "Started monitoring station");
+        }
+    }
+}
+
+impl Default for RadioMonitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Keeps reading the station's stream, reconnecting whenever it fails or ends.
+async fn monitor_station(session: soup::Session, url: String, on_song: impl Fn(&Song)) {
+    // Providers created before this would miss the stored token
+    Application::get().credentials().wait_loaded().await;
+
+    loop {
+        match monitor_station_inner(&session, &url, &on_song).await {
+            Ok(()) => tracing::debug!(url, "Station stream ended"),
+            Err(err) => tracing::warn!(url, "Failed to monitor station: {:?}", err),
+        }
+
+        glib::timeout_future(RECONNECT_DELAY).await;
+    }
+}
+
+async fn monitor_station_inner(
+    session: &soup::Session,
+    url: &str,
+    on_song: &impl Fn(&Song),
+) -> Result<()> {
+    let mut stream = IcyStream::connect(session, url).await?;
+
+    if stream.has_metadata() {
+        tracing::debug!(url, name = stream.name(), "Monitoring station");
+    } else {
+        tracing::debug!(
+            url,
+            name = stream.name(),
+            "Monitoring station without metadata, only recognizing its audio"
+        );
+    }
+
+    let mut last_stream_title = None;
+    let mut is_stream_title_usable = false;
+
+    let mut last_song_id = None;
+    let mut next_recognize_time = Instant::now();
+    let mut clip = Vec::new();
+
+    while let Some(chunk) = stream.next_chunk().await? {
+        for metadata in &chunk.metadata {
+            let Some(stream_title) = icy::parse_stream_title(metadata) else {
+                continue;
+            };
+
+            if last_stream_title.as_deref() == Some(stream_title) {
+                continue;
+            }
+
+            last_stream_title = Some(stream_title.to_string());
+
+            match song_from_stream_title(stream_title) {
+                Some(song) => {
+                    tracing::debug!(url, stream_title, "Got song from stream title");
+
+                    is_stream_title_usable = true;
+
+                    if last_song_id.as_ref() != Some(song.id_ref()) {
+                        last_song_id = Some(song.id());
+                        on_song(&song);
+                    }
+                }
+                None => {
+                    // Likely an ad or a station jingle; let the provider decide.
+                    tracing::debug!(url, stream_title, "Stream title is not a song");
+
+                    is_stream_title_usable = false;
+                }
+            }
+        }
+
+        if is_stream_title_usable || Instant::now() < next_recognize_time {
+            continue;
+        }
+
+        clip.extend_from_slice(&chunk.audio);
+
+        let provider = ProviderSettings::lock().active.to_provider();
+
+        if stream.audio_duration(clip.len()) < provider.listen_duration() {
+            continue;
+        }
+
+        let bytes = std::mem::take(&mut clip);
+        next_recognize_time = Instant::now() + RECOGNIZE_INTERVAL;
+
+        match provider.recognize(&bytes).await.and_then(top_song) {
+            Ok(song) => {
+                tracing::debug!(url, ?song, "Recognized song from stream audio");
+
+                if last_song_id.as_ref() != Some(song.id_ref()) {
+                    song.set_last_heard(DateTime::now_utc());
+                    last_song_id = Some(song.id());
+                    on_song(&song);
+                }
+            }
+            Err(err) => {
+                tracing::debug!(url, "Failed to recognize stream audio: {:?}", err);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Builds a song from an ICY `StreamTitle` in the conventional
+/// `Artist - Title` format.
+///
+/// Returns `None` if the stream title is not in that format.
+fn song_from_stream_title(stream_title: &str) -> Option<Song> {
+    let (artist, title) = stream_title.split_once(" - ")?;
+    let artist = artist.trim();
+    let title = title.trim();
+
+    if artist.is_empty() || title.is_empty() {
+        return None;
+    }
+
+    let song = Song::builder(
+        &Uid::from_prefixed("Icy", &format!("{} - {}", artist, title).to_lowercase()),
+        title,
+        artist,
+        "",
+    )
+    .external_link(
+        ExternalLinkKey::YoutubeSearchTerm,
+        format!("{} - {}", artist, title),
+    )
+    .build();
+    song.set_last_heard(DateTime::now_utc());
+
+    Some(song)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn song_from_stream_title_ok() {
+        let song = song_from_stream_title("Some Artist - Some Title").unwrap();
+        assert_eq!(song.artist(), "Some Artist");
+        assert_eq!(song.title(), "Some Title");
+        assert_eq!(song.album(), "");
+        assert!(song.last_heard().is_some());
+
+        let song = song_from_stream_title("  Artist  -  Title - Radio Edit ").unwrap();
+        assert_eq!(song.artist(), "Artist");
+        assert_eq!(song.title(), "Title - Radio Edit");
+    }
+
+    #[test]
+    fn song_from_stream_title_same_id() {
+        assert_eq!(
+            song_from_stream_title("Artist - Title").unwrap().id(),
+            song_from_stream_title("ARTIST - title").unwrap().id()
+        );
+        assert_ne!(
+            song_from_stream_title("Artist - Title").unwrap().id(),
+            song_from_stream_title("Artist - Other Title").unwrap().id()
+        );
+    }
+
+    #[test]
+    fn song_from_stream_title_invalid() {
+        assert!(song_from_stream_title("Station Jingle").is_none());
+        assert!(song_from_stream_title(" - Title").is_none());
+        assert!(song_from_stream_title("Artist - ").is_none());
+        assert!(song_from_stream_title("").is_none());
+    }
+}
@@ -0,0 +1,289 @@
+use gtk::{gio, glib, prelude::*, subclass::prelude::*};
+
+use std::{cell::RefCell, cmp::Reverse, collections::BTreeSet};
+
+use crate::{song::Song, song_list::SongList, uid::Uid, utils};
+
+/// Songs that were heard more recently come first, and songs that were never
+/// heard come last.
+type SortKey = Reverse<Option<i64>>;
+
+mod imp {
+    use super::*;
+
+    #[derive(Default)]
+    pub struct RecentSongs {
+        pub(super) song_list: glib::WeakRef<SongList>,
+        /// Ids of the songs, sorted by when they were last heard
+        pub(super) entries: RefCell<Vec<(SortKey, Uid)>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for RecentSongs {
+        const NAME: &'static str = "MousaiRecentSongs";
+        type Type = super::RecentSongs;
+        type Interfaces = (gio::ListModel,);
+    }
+
+    impl ObjectImpl for RecentSongs {}
+
+    impl ListModelImpl for RecentSongs {
+        fn item_type(&self) -> glib::Type {
+            Song::static_type()
+        }
+
+        fn n_items(&self) -> u32 {
+            self.entries.borrow().len() as u32
+        }
+
+        fn item(&self, position: u32) -> Option<glib::Object> {
+            let song_id = self
+                .entries
+                .borrow()
+                .get(position as usize)
+                .map(|(_, song_id)| song_id.clone())?;
+            self.song_list
+                .upgrade()?
+                .get_or_placeholder(&song_id)
+                .map(|song| song.upcast())
+        }
+    }
+}
+
+glib::wrapper! {
+    /// The songs of a [`SongList`], from the most recently heard, whose items
+    /// are only created when accessed.
+    ///
+    /// This is kept in sync by the song list with when the songs were last
+    /// heard, so that the songs don't have to be created to be sorted.
+    pub struct RecentSongs(ObjectSubclass<imp::RecentSongs>)
+        @implements gio::ListModel;
+}
+
+// These are only called by the song list, which keeps this in sync.
+impl RecentSongs {
+    pub fn new(song_list: &SongList) -> Self {
+        let this = glib::Object::new::<Self>();
+        this.imp().song_list.set(Some(song_list));
+        this
+    }
+
+    pub fn insert(&self, song_id: &Uid, last_heard: Option<i64>) {
+        let position = self.insert_inner(song_id, last_heard);
+        self.items_changed(position as u32, 0, 1);
+    }
+
+    /// This is more efficient than [`RecentSongs::insert`] since it emits
+    /// `items-changed` only once for all inserted songs.
+    pub fn insert_many(&self, songs: Vec<(Uid, Option<i64>)>) {
+        if songs.is_empty() {
+            return;
+        }
+
+        let (first_changed, n_removed, n_added) = {
+            let mut entries = self.imp().entries.borrow_mut();
+
+            let mut new_entries = songs
+                .into_iter()
+                .map(|(song_id, last_heard)| (Reverse(last_heard), song_id))
+                .collect::<Vec<_>>();
+            // This is stable, so the inserted songs with the same key keep
+            // their order.
+            new_entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+            let first_changed = entries.partition_point(|(k, _)| *k <= new_entries[0].0);
+            let old_len = entries.len();
+
+            // Only the entries after the first inserted one have to be merged,
+            // where existing entries stay before the inserted ones with the
+            // same key.
+            let mut old_entries = entries.split_off(first_changed).into_iter().peekable();
+            let mut new_entries = new_entries.into_iter().peekable();
+            entries.reserve(old_entries.len() + new_entries.len());
+            while let (Some((old_key, _)), Some((new_key, _))) =
+                (old_entries.peek(), new_entries.peek())
+            {
+                let entry = if old_key <= new_key {
+                    old_entries.next()
+                } else {
+                    new_entries.next()
+                };
+                entries.extend(entry);
+            }
+            entries.extend(old_entries);
+            entries.extend(new_entries);
+
+            (
+                first_changed,
+                old_len - first_changed,
+                entries.len() - first_changed,
+            )
+        };
+
+        self.items_changed(first_changed as u32, n_removed as u32, n_added as u32);
+    }
+
+    /// Moves the song to where it must be after it was last heard at a
+    /// different time.
+    pub fn update(&self, song_id: &Uid, old_last_heard: Option<i64>, new_last_heard: Option<i64>) {
+        if old_last_heard == new_last_heard {
+            return;
+        }
+
+        let Some(old_position) = self.remove_inner(song_id, old_last_heard) else {
+            tracing::warn!("Song to update is not in recent songs");
+            return;
+        };
+        let new_position = self.insert_inner(song_id, new_last_heard);
+
+        if old_position == new_position {
+            self.items_changed(old_position as u32, 1, 1);
+        } else {
+            // Emit as if it was removed before it was inserted, so the number of
+            // items always matches what was emitted.
+            let (first, last) = if old_position < new_position {
+                (old_position, new_position)
+            } else {
+                (new_position, old_position)
+            };
+            let n_changed = (last - first + 1) as u32;
+            self.items_changed(first as u32, n_changed, n_changed);
+        }
+    }
+
+    pub fn remove_many(&self, songs: &[(Uid, Option<i64>)]) {
+        let to_remove_indices = songs
+            .iter()
+            .filter_map(|(song_id, last_heard)| self.position(song_id, *last_heard))
+            .collect::<BTreeSet<_>>();
+
+        // Reverse the iterations so we don't shift the indices
+        for &(first, count) in utils::consecutive_groups(&to_remove_indices).iter().rev() {
+            self.imp().entries.borrow_mut().drain(first..first + count);
+            self.items_changed(first as u32, count as u32, 0);
+        }
+    }
+
+    fn position(&self, song_id: &Uid, last_heard: Option<i64>) -> Option<usize> {
+        let key = Reverse(last_heard);
+
+        let entries = self.imp().entries.borrow();
+        let start = entries.partition_point(|(k, _)| *k < key);
+        entries[start..]
+            .iter()
+            .take_while(|(k, _)| *k == key)
+            .position(|(_, id)| id == song_id)
+            .map(|offset| start + offset)
+    }
+
+    /// Returns where the song was inserted, after the songs with the same key.
+    fn insert_inner(&self, song_id: &Uid, last_heard: Option<i64>) -> usize {
+        let key = Reverse(last_heard);
+
+        let mut entries = self.imp().entries.borrow_mut();
+        let position = entries.partition_point(|(k, _)| *k <= key);
+        entries.insert(position, (key, song_id.clone()));
+        position
+    }
+
+    /// Returns where the song was before it was removed.
+    fn remove_inner(&self, song_id: &Uid, last_heard: Option<i64>) -> Option<usize> {
+        let position = self.position(song_id, last_heard)?;
+        self.imp().entries.borrow_mut().remove(position);
+        Some(position)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use gtk::glib::clone;
+    use std::{cell::Cell, rc::Rc};
+
+    use crate::{database, date_time::DateTime, song::test::new_test_song};
+
+    fn new_heard_song(id: &str, last_heard: Option<i64>) -> Song {
+        let song = new_test_song(id);
+        if let Some(last_heard) = last_heard {
+            song.set_last_heard(DateTime::from(
+                glib::DateTime::from_unix_utc(last_heard).unwrap(),
+            ));
+        }
+        song
+    }
+
+    #[track_caller]
+    fn assert_order(recent_songs: &RecentSongs, expected_ids: &[&str]) {
+        let ids = (0..recent_songs.n_items())
+            .map(|position| {
+                recent_songs
+                    .item(position)
+                    .and_downcast::<Song>()
+                    .unwrap()
+                    .id()
+            })
+            .collect::<Vec<_>>();
+        let expected_ids = expected_ids
+            .iter()
+            .map(|&id| Uid::from(id))
+            .collect::<Vec<_>>();
+        assert_eq!(ids, expected_ids);
+    }
+
+    #[test]
+    fn sorted_by_last_heard() {
+        let (env, _tempdir) = database::new_test_env();
+        let song_list = SongList::load_from_env(env).unwrap();
+        let recent_songs = song_list.recent_songs();
+
+        // The emitted changes must always add up to the number of items
+        let n_items = Rc::new(Cell::new(0));
+        recent_songs.connect_items_changed(clone!(
+            #[strong]
+            n_items,
+            move |recent_songs, position, removed, added| {
+                assert!(position + removed <= n_items.get());
+                n_items.set(n_items.get() - removed + added);
+                assert_eq!(n_items.get(), recent_songs.n_items());
+            }
+        ));
+
+        song_list
+            .insert_many(vec![
+                new_heard_song("a", Some(1)),
+                new_heard_song("b", None),
+                new_heard_song("c", Some(3)),
+                new_heard_song("d", Some(1)),
+            ])
+            .unwrap();
+        assert_order(&recent_songs, &["c", "a", "d", "b"]);
+
+        let a = song_list.get(&Uid::from("a")).unwrap();
+        a.set_last_heard(DateTime::from(glib::DateTime::from_unix_utc(4).unwrap()));
+        assert_order(&recent_songs, &["a", "c", "d", "b"]);
+
+        let b = song_list.get(&Uid::from("b")).unwrap();
+        b.set_last_heard(DateTime::from(glib::DateTime::from_unix_utc(2).unwrap()));
+        assert_order(&recent_songs, &["a", "c", "b", "d"]);
+
+        song_list.remove_many(&[&Uid::from("c")]).unwrap();
+        assert_order(&recent_songs, &["a", "b", "d"]);
+
+        song_list.insert(new_heard_song("e", Some(1))).unwrap();
+        assert_order(&recent_songs, &["a", "b", "d", "e"]);
+
+        song_list.insert(new_heard_song("d", Some(5))).unwrap();
+        assert_order(&recent_songs, &["d", "a", "b", "e"]);
+
+        // Existing songs stay before the inserted ones with the same key
+        song_list
+            .insert_many(vec![
+                new_heard_song("f", Some(1)),
+                new_heard_song("g", Some(6)),
+                new_heard_song("h", None),
+            ])
+            .unwrap();
+        assert_order(&recent_songs, &["g", "d", "a", "b", "e", "f", "h"]);
+    }
+}
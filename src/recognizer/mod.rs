@@ -13,19 +13,32 @@ use gtk::{
 use std::{
     cell::{Cell, OnceCell, RefCell},
     rc::Rc,
+    time::Instant,
 };
 
+use self::{
+    provider::close_candidates,
+    recorder::{EncoderConfig, Recorder},
+    recording::BoxedRecognizeResult,
+};
 pub use self::{
     provider::{
-        ProviderSettings, ProviderType, RecognizeError, RecognizeErrorKind, TestProviderMode,
+        AudD, BoxedCandidates, Candidate, ProviderSettings, ProviderType, ProviderUsage,
+        RecognitionCache, RecognizeError, RecognizeErrorKind, TestProviderMode, top_song,
     },
+    recording::{Recording, RecordingV1, RecordingV2, RecordingV3},
     recordings::Recordings,
 };
-use self::{
-    recorder::Recorder,
-    recording::{BoxedRecognizeResult, Recording},
+use crate::{
+    Application,
+    cancelled::Cancelled,
+    date_time::DateTime,
+    device::{self, AppStream},
+    settings::AudioSourceType,
+    song::Song,
+    song_clips::SongClips,
+    utils,
 };
-use crate::{Application, cancelled::Cancelled, date_time::DateTime, song::Song, utils};
 
 const MAX_SAVED_RECORDING_RECOGNIZE_RETRIES: u8 = 3;
 
@@ -53,11 +66,23 @@ mod imp {
         /// Whether offline mode is active
         #[property(get)]
         pub(super) is_offline_mode: Cell<bool>,
+        /// Whether recognized songs and saved recordings are kept only in
+        /// memory
+        #[property(get, set = Self::set_is_incognito, explicit_notify)]
+        pub(super) is_incognito: Cell<bool>,
+        /// Application stream to record when the audio source type is
+        /// application audio
+        #[property(get, set = Self::set_app_stream, explicit_notify, nullable)]
+        pub(super) app_stream: RefCell<Option<AppStream>>,
 
         pub(super) recorder: Recorder,
+        pub(super) recording_start_time: Cell<Option<Instant>>,
+        /// Recording of the candidates waiting for one to be chosen
+        pub(super) pending_song_clip: RefCell<Option<glib::Bytes>>,
         pub(super) cancellable: RefCell<Option<gio::Cancellable>>,
 
         pub(super) saved_recordings: OnceCell<Recordings>,
+        pub(super) song_clips: OnceCell<SongClips>,
     }
 
     #[glib::object_subclass]
@@ -77,15 +102,52 @@ mod imp {
                     Signal::builder("song-recognized")
                         .param_types([Song::static_type()])
                         .build(),
+                    Signal::builder("candidates-recognized")
+                        .param_types([BoxedCandidates::static_type()])
+                        .build(),
                     Signal::builder("recording-saved")
                         .param_types([RecognizeError::static_type()])
                         .build(),
+                    Signal::builder("usage-limit-near")
+                        .param_types([u32::static_type()])
+                        .build(),
                 ]
             });
 
             SIGNALS.as_ref()
         }
     }
+
+    impl Recognizer {
+        fn set_is_incognito(&self, is_incognito: bool) {
+            let obj = self.obj();
+
+            if is_incognito == obj.is_incognito() {
+                return;
+            }
+
+            self.is_incognito.set(is_incognito);
+            obj.notify_is_incognito();
+
+            tracing::debug!(is_incognito, "Incognito mode changed");
+        }
+
+        fn set_app_stream(&self, app_stream: Option<AppStream>) {
+            if app_stream == *self.app_stream.borrow() {
+                return;
+            }
+
+            let obj = self.obj();
+            self.app_stream.replace(app_stream);
+            obj.notify_app_stream();
+
+            if self.recorder.is_recording()
+                && let Err(err) = obj.restart_recording()
+            {
+                tracing::error!("Failed to restart recording: {:?}", err);
+            }
+        }
+    }
 }
 
 glib::wrapper! {
@@ -131,6 +193,46 @@ impl Recognizer {
         self.emit_by_name::<()>("song-recognized", &[song]);
     }
 
+    /// Emitted instead of `song-recognized` when the top candidates are too
+    /// close to each other to pick one automatically. `choose_candidate` must
+    /// be called once one of them is chosen.
+    pub fn connect_candidates_recognized<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &[Candidate]) + 'static,
+    {
+        self.connect_closure(
+            "candidates-recognized",
+            false,
+            closure_local!(|obj: &Self, candidates: &BoxedCandidates| {
+                f(obj, &candidates.0);
+            }),
+        )
+    }
+
+    fn emit_candidates_recognized(&self, candidates: &[Candidate]) {
+        self.emit_by_name::<()>(
+            "candidates-recognized",
+            &[&BoxedCandidates(candidates.to_vec())],
+        );
+    }
+
+    /// Marks the song as the chosen one among the last recognized candidates.
+    pub fn choose_candidate(&self, song: &Song) {
+        let Some(bytes) = self.imp().pending_song_clip.take() else {
+            tracing::warn!("Chose a candidate without pending candidates");
+            return;
+        };
+
+        if !self.is_incognito() {
+            self.save_song_clip(song, &bytes);
+        }
+    }
+
+    /// Lets go of the last recognized candidates when none was chosen.
+    pub fn dismiss_candidates(&self) {
+        self.imp().pending_song_clip.take();
+    }
+
     pub fn connect_recording_saved<F>(&self, f: F) -> glib::SignalHandlerId
     where
         F: Fn(&Self, &RecognizeError) + 'static,
@@ -148,6 +250,25 @@ impl Recognizer {
         self.emit_by_name::<()>("recording-saved", &[&cause]);
     }
 
+    /// Emitted after recognizing when only a few calls are left before the
+    /// provider's known daily limit is reached.
+    pub fn connect_usage_limit_near<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, u32) + 'static,
+    {
+        self.connect_closure(
+            "usage-limit-near",
+            false,
+            closure_local!(|obj: &Self, remaining_calls: u32| {
+                f(obj, remaining_calls);
+            }),
+        )
+    }
+
+    fn emit_usage_limit_near(&self, remaining_calls: u32) {
+        self.emit_by_name::<()>("usage-limit-near", &[&remaining_calls]);
+    }
+
     pub fn bind_saved_recordings(&self, recordings: &Recordings) {
         self.imp()
             .saved_recordings
@@ -177,6 +298,13 @@ impl Recognizer {
         self.try_recognize_saved_recordings();
     }
 
+    pub fn bind_song_clips(&self, song_clips: &SongClips) {
+        self.imp()
+            .song_clips
+            .set(song_clips.clone())
+            .unwrap_or_else(|_| panic!("song clips must be bound only once"));
+    }
+
     pub fn saved_recordings(&self) -> &Recordings {
         self.imp()
             .saved_recordings
@@ -233,7 +361,7 @@ impl Recognizer {
             fn drop(&mut self) {
                 if let Some(instance) = self.weak.upgrade() {
                     instance.set_state(RecognizerState::Null);
-                    let _ = instance.imp().recorder.stop();
+                    let _ = instance.stop_recording();
                 }
             }
         }
@@ -251,17 +379,32 @@ impl Recognizer {
 
         let imp = self.imp();
 
-        imp.recorder
-            .start(
-                Application::get().settings().audio_source_type(),
-                clone!(
-                    #[weak(rename_to = obj)]
-                    self,
-                    move |peak| {
-                        obj.emit_recording_peak_changed(peak);
-                    }
-                ),
-            )
+        // The provider reads the token from the credentials when created
+        gio::CancellableFuture::new(
+            Application::get().credentials().wait_loaded(),
+            cancellable.clone(),
+        )
+        .await
+        .map_err(|_| Cancelled::new("listening while loading credentials"))?;
+
+        let provider = ProviderSettings::lock().active.to_provider();
+        let listen_duration = provider.listen_duration();
+        let encoder_config = provider.encoder_config();
+        tracing::debug!(?provider, ?listen_duration, ?encoder_config);
+
+        if Application::get().settings().audio_source_type() == AudioSourceType::ApplicationAudio {
+            let app_streams = device::find_app_streams()
+                .await
+                .context("Failed to find application streams")?;
+            let app_stream = self
+                .app_stream()
+                .filter(|app_stream| app_streams.contains(app_stream))
+                .or_else(|| app_streams.into_iter().next())
+                .context("No applications are currently playing audio")?;
+            self.set_app_stream(Some(app_stream));
+        }
+
+        self.start_recording(encoder_config)
             .context("Failed to start recording")?;
         let recorded_time = DateTime::now_utc();
 
@@ -273,15 +416,20 @@ impl Recognizer {
             }
         ));
 
-        let provider = ProviderSettings::lock().active.to_provider();
-        let listen_duration = provider.listen_duration();
-        tracing::debug!(?provider, ?listen_duration);
-
-        gio::CancellableFuture::new(glib::timeout_future(listen_duration), cancellable.clone())
-            .await
-            .map_err(|_| Cancelled::new("recognizing while recording"))?;
+        // The recording is restarted when the app stream is changed, so keep
+        // waiting until it has been recorded for the whole listen duration.
+        while let Some(remaining) = imp
+            .recording_start_time
+            .get()
+            .and_then(|start_time| listen_duration.checked_sub(start_time.elapsed()))
+            .filter(|remaining| !remaining.is_zero())
+        {
+            gio::CancellableFuture::new(glib::timeout_future(remaining), cancellable.clone())
+                .await
+                .map_err(|_| Cancelled::new("recognizing while recording"))?;
+        }
 
-        let recording_bytes = imp.recorder.stop().context("Failed to stop recording")?;
+        let recording_bytes = self.stop_recording().context("Failed to stop recording")?;
         tracing::debug!(
             "Stopped recording with size {}",
             glib::format_size_full(
@@ -292,7 +440,7 @@ impl Recognizer {
 
         if self.is_offline_mode() {
             self.saved_recordings()
-                .insert(Recording::new(&recording_bytes, &recorded_time))
+                .insert(self.new_recording(&recording_bytes, &recorded_time))
                 .context("Failed to insert recording")?;
             self.emit_recording_saved(&RecognizeError::new(RecognizeErrorKind::Connection, None));
             tracing::debug!("Offline mode is active; saved recording for later recognition");
@@ -306,11 +454,50 @@ impl Recognizer {
                 .await
                 .map_err(|_| Cancelled::new("recognizing while calling provider"))?;
 
+        if !provider.is_test() {
+            let app = Application::get();
+            let usage = ProviderUsage::today(
+                app.settings(),
+                app.credentials().aud_d_api_token().is_some(),
+            );
+            if usage.is_near_limit() {
+                self.emit_usage_limit_near(usage.remaining_calls().unwrap_or_default());
+            }
+        }
+
+        // Providers must return `NoMatches` instead of no candidates, but
+        // don't panic later on if one doesn't
+        let res = res.and_then(|candidates| {
+            if candidates.is_empty() {
+                Err(RecognizeError::new(RecognizeErrorKind::NoMatches, None))
+            } else {
+                Ok(candidates)
+            }
+        });
+
         match res {
-            Ok(song) => {
-                song.set_last_heard(recorded_time);
+            Ok(candidates) => {
+                for candidate in &candidates {
+                    candidate.song.set_last_heard(recorded_time.clone());
+                }
+
+                let close_candidates = close_candidates(&candidates);
+                if close_candidates.len() > 1 {
+                    tracing::debug!(
+                        "Recognized {} close candidates; letting the user choose",
+                        close_candidates.len()
+                    );
+
+                    imp.pending_song_clip.replace(Some(recording_bytes));
+                    self.emit_candidates_recognized(close_candidates);
+                } else {
+                    let song = &close_candidates[0].song;
 
-                self.emit_song_recognized(&song);
+                    if !self.is_incognito() {
+                        self.save_song_clip(song, &recording_bytes);
+                    }
+                    self.emit_song_recognized(song);
+                }
             }
             Err(err) => {
                 if err.is_permanent() {
@@ -318,7 +505,7 @@ impl Recognizer {
                 }
 
                 self.saved_recordings()
-                    .insert(Recording::new(&recording_bytes, &recorded_time))
+                    .insert(self.new_recording(&recording_bytes, &recorded_time))
                     .context("Failed to insert recording")?;
                 self.emit_recording_saved(&err);
                 tracing::debug!(
@@ -331,6 +518,80 @@ impl Recognizer {
         Ok(())
     }
 
+    fn start_recording(&self, encoder_config: EncoderConfig) -> Result<()> {
+        let imp = self.imp();
+
+        let app = Application::get();
+        let settings = app.settings();
+        imp.recorder.start(
+            settings.audio_source_type(),
+            imp.app_stream.borrow().as_ref(),
+            encoder_config,
+            settings.audio_preprocessing(),
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |peak| {
+                    obj.emit_recording_peak_changed(peak);
+                }
+            ),
+        )?;
+        imp.recording_start_time.set(Some(Instant::now()));
+
+        Ok(())
+    }
+
+    fn stop_recording(&self) -> Result<glib::Bytes> {
+        let imp = self.imp();
+
+        imp.recording_start_time.set(None);
+        imp.recorder.stop()
+    }
+
+    /// Discards the recorded audio so far and starts recording again from the
+    /// current source.
+    fn restart_recording(&self) -> Result<()> {
+        self.stop_recording()?;
+
+        let encoder_config = ProviderSettings::lock()
+            .active
+            .to_provider()
+            .encoder_config();
+        self.start_recording(encoder_config)?;
+
+        tracing::debug!("Restarted recording");
+
+        Ok(())
+    }
+
+    /// Creates a recording to be saved, which is kept only in memory in
+    /// incognito mode.
+    fn new_recording(&self, bytes: &glib::Bytes, recorded_time: &DateTime) -> Recording {
+        if self.is_incognito() {
+            Recording::new_incognito(bytes, recorded_time)
+        } else {
+            Recording::new(bytes, recorded_time)
+        }
+    }
+
+    /// Keeps the recording of the song, if enabled in the settings.
+    fn save_song_clip(&self, song: &Song, bytes: &[u8]) {
+        let app = Application::get();
+        let settings = app.settings();
+
+        if !settings.save_song_clips() {
+            return;
+        }
+
+        let Some(song_clips) = self.imp().song_clips.get() else {
+            return;
+        };
+
+        if let Err(err) = song_clips.insert(song.id_ref(), bytes, settings.song_clips_limit()) {
+            tracing::error!("Failed to save song clip: {:?}", err);
+        }
+    }
+
     fn set_state(&self, state: RecognizerState) {
         if state == self.state() {
             return;
@@ -369,6 +630,8 @@ impl Recognizer {
     }
 
     async fn try_recognize_saved_recordings_inner(&self) {
+        Application::get().credentials().wait_loaded().await;
+
         let provider = ProviderSettings::lock().active.to_provider();
         tracing::debug!("Recognizing saved recordings with provider: {:?}", provider);
 
@@ -395,9 +658,18 @@ impl Recognizer {
                 continue;
             }
 
-            match provider.recognize(recording.bytes().as_ref()).await {
+            // There is no one to choose among the candidates when recognizing in
+            // the background, so just take the top one.
+            match provider
+                .recognize(recording.bytes().as_ref())
+                .await
+                .and_then(top_song)
+            {
                 Ok(song) => {
                     song.set_last_heard(recording.recorded_time());
+                    if !recording.is_incognito() {
+                        self.save_song_clip(&song, &recording.bytes());
+                    }
                     recording.set_recognize_result(Some(BoxedRecognizeResult(Ok(song))));
                 }
                 Err(err) => {
@@ -1,8 +1,8 @@
 use async_trait::async_trait;
 use gtk::glib;
 
-use super::{AudD, Song};
-use crate::recognizer::provider::{RecognizeError, TestProvider, TestProviderMode};
+use super::AudD;
+use crate::recognizer::provider::{Candidate, RecognizeError, TestProvider, TestProviderMode};
 
 #[derive(Debug)]
 pub struct AudDMock;
@@ -13,11 +13,11 @@ impl TestProvider for AudDMock {
         &self,
         _: &[u8],
         mode: TestProviderMode,
-    ) -> Result<Song, RecognizeError> {
+    ) -> Result<Vec<Candidate>, RecognizeError> {
         let response_str = random_response_str(mode);
         tracing::trace!(response_str);
 
-        AudD::build_song_from_response_bytes(response_str.as_bytes())
+        AudD::build_candidates_from_response_bytes(response_str.as_bytes())
     }
 }
 
@@ -9,9 +9,15 @@ use serde_json::json;
 use soup::prelude::*;
 
 pub use self::mock::AudDMock;
-use self::response::Response;
-use super::{Provider, RecognizeError, RecognizeErrorKind};
-use crate::{Application, external_links::ExternalLinkKey, song::Song, uid::Uid};
+use self::response::{Data, Response, StatusResponse};
+use super::{Candidate, Provider, ProviderUsage, RecognizeError, RecognizeErrorKind};
+use crate::{
+    Application, external_links::ExternalLinkKey, recognizer::recorder::EncoderConfig, song::Song,
+    uid::Uid,
+};
+
+const RECOGNIZE_URL: &str = "https://api.audd.io/";
+const VALIDATE_TOKEN_URL: &str = "https://api.audd.io/getCallbackUrl/";
 
 #[derive(Debug)]
 pub struct AudD {
@@ -25,13 +31,64 @@ impl AudD {
         }
     }
 
-    fn build_song_from_response_bytes(response_bytes: &[u8]) -> Result<Song, RecognizeError> {
-        let data = serde_json::from_slice::<Response>(response_bytes)
+    /// Checks whether AudD accepts the token, using a request that does not
+    /// recognize anything and thus does not use up the token's limit.
+    pub async fn validate_token(&self) -> Result<(), RecognizeError> {
+        let data = json!({ "api_token": self.api_token });
+        let response_bytes = send(VALIDATE_TOKEN_URL, &data).await?;
+        Self::validate_token_from_response_bytes(&response_bytes)
+    }
+
+    fn validate_token_from_response_bytes(response_bytes: &[u8]) -> Result<(), RecognizeError> {
+        let result = serde_json::from_slice::<StatusResponse>(response_bytes)
+            .map_err(|err| {
+                RecognizeError::new(RecognizeErrorKind::OtherPermanent, err.to_string())
+            })?
+            .result();
+
+        match result {
+            Err(err)
+                if matches!(
+                    err.kind(),
+                    RecognizeErrorKind::InvalidToken | RecognizeErrorKind::TokenLimitReached
+                ) =>
+            {
+                Err(err)
+            }
+            // Other errors, such as not having a callback URL set, can only
+            // happen after the token is accepted.
+            _ => Ok(()),
+        }
+    }
+
+    fn build_candidates_from_response_bytes(
+        response_bytes: &[u8],
+    ) -> Result<Vec<Candidate>, RecognizeError> {
+        let mut candidates = serde_json::from_slice::<Response>(response_bytes)
             .map_err(|err| {
                 RecognizeError::new(RecognizeErrorKind::OtherPermanent, err.to_string())
             })?
-            .data()?;
+            .data()?
+            .into_iter()
+            .map(|data| Candidate {
+                confidence: data.score.map(|score| (score / 100.0).clamp(0.0, 1.0)),
+                timecode: data.timecode.as_deref().and_then(parse_timecode),
+                song: Self::build_song_from_data(data),
+            })
+            .collect::<Vec<_>>();
+
+        // Candidates without confidence are ranked last, while keeping the
+        // order of the ones with equal confidence.
+        candidates.sort_by(|a, b| {
+            b.confidence
+                .unwrap_or(f64::NEG_INFINITY)
+                .total_cmp(&a.confidence.unwrap_or(f64::NEG_INFINITY))
+        });
 
+        Ok(candidates)
+    }
+
+    fn build_song_from_data(data: Data) -> Song {
         let mut song_builder = Song::builder(
             &Uid::from_prefixed("AudD", data.info_link.trim_start_matches("https://lis.tn/")), // Info link is unique to every song
             &data.title,
@@ -43,8 +100,21 @@ impl AudD {
             song_builder.release_date(release_date);
         }
 
+        if let Some(ref label) = data.label
+            && !label.is_empty()
+        {
+            song_builder.label(label);
+        }
+
+        if let Some(timecode) = data.timecode.as_deref().and_then(parse_timecode) {
+            song_builder.timecode(timecode);
+        }
+
         let mut playback_links = Vec::new();
         let mut album_images = Vec::new();
+        let mut isrcs = Vec::new();
+        let mut durations_ms = Vec::new();
+        let mut genres = Vec::new();
 
         song_builder.external_link(ExternalLinkKey::AudDUrl, data.info_link);
 
@@ -62,6 +132,12 @@ impl AudD {
                 playback_links.push(spotify_data.preview_url);
             }
 
+            if let Some(isrc) = spotify_data.external_ids.and_then(|ids| ids.isrc) {
+                isrcs.push(isrc);
+            }
+
+            durations_ms.extend(spotify_data.duration_ms);
+
             song_builder.external_link(
                 ExternalLinkKey::SpotifyUrl,
                 spotify_data.external_urls.spotify,
@@ -82,6 +158,29 @@ impl AudD {
                     .replace("{w}", "600")
                     .replace("{h}", "600"),
             );
+
+            isrcs.extend(apple_music_data.isrc);
+            durations_ms.extend(apple_music_data.duration_ms);
+
+            // "Music" is a catch-all genre that is present on every song
+            genres.extend(
+                apple_music_data
+                    .genre_names
+                    .into_iter()
+                    .filter(|genre| genre != "Music"),
+            );
+        }
+
+        if let Some(musicbrainz_data) = data.musicbrainz_data.into_iter().next() {
+            song_builder.musicbrainz_id(&musicbrainz_data.id);
+            song_builder.external_link(
+                ExternalLinkKey::MusicBrainzUrl,
+                format!("https://musicbrainz.org/recording/{}", musicbrainz_data.id),
+            );
+
+            isrcs.extend(musicbrainz_data.isrcs);
+            durations_ms.extend(musicbrainz_data.length);
+            genres.extend(musicbrainz_data.tags.into_iter().map(|tag| tag.name));
         }
 
         if let Some(lyrics_data) = data.lyrics_data
@@ -98,50 +197,94 @@ impl AudD {
             song_builder.playback_link(playback_link);
         }
 
-        Ok(song_builder.build())
+        if let Some(isrc) = isrcs.iter().find(|isrc| !isrc.is_empty()) {
+            song_builder.isrc(isrc);
+        }
+
+        if let Some(&duration_ms) = durations_ms.iter().find(|&&duration_ms| duration_ms != 0) {
+            song_builder.duration(Duration::from_millis(duration_ms));
+        }
+
+        if let Some(genre) = genres.iter().find(|genre| !genre.is_empty()) {
+            song_builder.genre(genre);
+        }
+
+        song_builder.build()
+    }
+}
+
+/// Parses timecodes in format of `mm:ss` or `hh:mm:ss`.
+fn parse_timecode(timecode: &str) -> Option<Duration> {
+    let mut secs = 0;
+    let mut n_parts = 0;
+
+    for part in timecode.split(':') {
+        secs = secs * 60 + part.parse::<u64>().ok()?;
+        n_parts += 1;
     }
+
+    (2..=3)
+        .contains(&n_parts)
+        .then_some(Duration::from_secs(secs))
+}
+
+async fn send(url: &str, data: &serde_json::Value) -> Result<glib::Bytes, RecognizeError> {
+    let message = soup::Message::new("POST", url).map_err(|err| {
+        RecognizeError::new(
+            RecognizeErrorKind::OtherPermanent,
+            format!("Failed to create POST message: {}", err),
+        )
+    })?;
+    message.set_request_body_from_bytes(None, Some(&glib::Bytes::from_owned(data.to_string())));
+    message.set_priority(soup::MessagePriority::High);
+
+    let response_bytes = Application::get()
+        .session()
+        .send_and_read_future(&message, glib::Priority::default())
+        .await
+        .map_err(|err| {
+            if err.matches(gio::ResolverError::NotFound)
+                || err.matches(gio::ResolverError::TemporaryFailure)
+            {
+                RecognizeError::new(RecognizeErrorKind::Connection, err.to_string())
+            } else {
+                RecognizeError::new(RecognizeErrorKind::OtherPermanent, err.to_string())
+            }
+        })?;
+
+    tracing::trace!(server_response = ?std::str::from_utf8(&response_bytes));
+
+    Ok(response_bytes)
 }
 
 #[async_trait(?Send)]
 impl Provider for AudD {
-    async fn recognize(&self, bytes: &[u8]) -> Result<Song, RecognizeError> {
+    async fn recognize(&self, bytes: &[u8]) -> Result<Vec<Candidate>, RecognizeError> {
+        // The standard API has no option to return more than its best match,
+        // so this is usually a single candidate. A list of matches is still
+        // handled in case it is returned, and picking between close
+        // candidates is for providers that return several.
         let data = json!({
             "api_token": self.api_token,
             "return": "spotify,apple_music,musicbrainz,lyrics",
             "audio": glib::base64_encode(bytes).as_str(),
         });
 
-        let message = soup::Message::new("POST", "https://api.audd.io/").map_err(|err| {
-            RecognizeError::new(
-                RecognizeErrorKind::OtherPermanent,
-                format!("Failed to create POST message: {}", err),
-            )
-        })?;
-        message.set_request_body_from_bytes(None, Some(&glib::Bytes::from_owned(data.to_string())));
-        message.set_priority(soup::MessagePriority::High);
+        let response_bytes = send(RECOGNIZE_URL, &data).await?;
+        ProviderUsage::record_call(Application::get().settings());
 
-        let response_bytes = Application::get()
-            .session()
-            .send_and_read_future(&message, glib::Priority::default())
-            .await
-            .map_err(|err| {
-                if err.matches(gio::ResolverError::NotFound)
-                    || err.matches(gio::ResolverError::TemporaryFailure)
-                {
-                    RecognizeError::new(RecognizeErrorKind::Connection, err.to_string())
-                } else {
-                    RecognizeError::new(RecognizeErrorKind::OtherPermanent, err.to_string())
-                }
-            })?;
-
-        tracing::trace!(server_response = ?std::str::from_utf8(&response_bytes));
-
-        Self::build_song_from_response_bytes(&response_bytes)
+        Self::build_candidates_from_response_bytes(&response_bytes)
     }
 
     fn listen_duration(&self) -> Duration {
         Duration::from_secs(5)
     }
+
+    fn encoder_config(&self) -> EncoderConfig {
+        // Keep more of the higher frequencies of the music than the default,
+        // while the upload of the few seconds listened stays small
+        EncoderConfig::new(32_000, 24_000)
+    }
 }
 
 #[cfg(test)]
@@ -149,7 +292,52 @@ mod test {
     use super::*;
 
     fn parse_response_str(response_str: &'static str) -> Result<Song, RecognizeError> {
-        AudD::build_song_from_response_bytes(response_str.as_bytes())
+        parse_response_str_candidates(response_str)
+            .map(|candidates| candidates.into_iter().next().unwrap().song)
+    }
+
+    fn parse_response_str_candidates(
+        response_str: &'static str,
+    ) -> Result<Vec<Candidate>, RecognizeError> {
+        AudD::build_candidates_from_response_bytes(response_str.as_bytes())
+    }
+
+    #[test]
+    fn validate_token() {
+        assert!(
+            AudD::validate_token_from_response_bytes(
+                br#"{"status":"error","error":{"error_code":19,"error_message":"Recognition failed: no callback URL set"}}"#
+            )
+            .is_ok()
+        );
+        assert!(
+            AudD::validate_token_from_response_bytes(
+                br#"{"status":"success","result":"https://example.com/callback"}"#
+            )
+            .is_ok()
+        );
+        assert_eq!(
+            AudD::validate_token_from_response_bytes(
+                br#"{"status":"error","error":{"error_code":900,"error_message":"Recognition failed: authorization failed: wrong api_token."}}"#
+            )
+            .unwrap_err()
+            .kind(),
+            RecognizeErrorKind::InvalidToken
+        );
+        assert_eq!(
+            AudD::validate_token_from_response_bytes(
+                br#"{"status":"error","error":{"error_code":901,"error_message":"Recognition failed: authorization failed: no api_token passed and the limit was reached."}}"#
+            )
+            .unwrap_err()
+            .kind(),
+            RecognizeErrorKind::TokenLimitReached
+        );
+        assert_eq!(
+            AudD::validate_token_from_response_bytes(b"")
+                .unwrap_err()
+                .kind(),
+            RecognizeErrorKind::OtherPermanent
+        );
     }
 
     #[test]
@@ -211,6 +399,12 @@ mod test {
         assert_eq!(song.external_links().get(ExternalLinkKey::SpotifyUrl), None);
         assert_eq!(song.album_art_link(), None);
         assert_eq!(song.playback_link(), None);
+        assert_eq!(song.label().as_deref(), Some("Glory Days Music"));
+        assert_eq!(song.timecode(), Some(Duration::from_secs(24)));
+        assert_eq!(song.isrc(), None);
+        assert_eq!(song.musicbrainz_id(), None);
+        assert_eq!(song.genre(), None);
+        assert_eq!(song.duration(), None);
 
         let res = parse_response_str(
             "{\"status\":\"success\",\"result\":{\"artist\":\"Public\",\"title\":\"Make You Mine\",\"album\":\"Let's Make It\",\"release_date\":\"2014-10-07\",\"label\":\"PUBLIC\",\"timecode\":\"00:43\",\"song_link\":\"https://lis.tn/FUYgUV\"}}",
@@ -258,6 +452,10 @@ mod test {
             Some("https://i.scdn.co/image/ab67616d0000b27393432e914046a003229378da")
         );
         assert_eq!(song.playback_link(), None);
+        assert_eq!(song.label().as_deref(), Some("Universal Music"));
+        assert_eq!(song.isrc().as_deref(), Some("GBUM71401926"));
+        assert_eq!(song.duration(), Some(Duration::from_millis(237247)));
+        assert_eq!(song.timecode(), Some(Duration::from_secs(62)));
 
         let res = parse_response_str(
             "{\"status\":\"success\",\"result\":{\"artist\":\"Alessia Cara\",\"title\":\"Scars To Your Beautiful\",\"album\":\"Know-It-All\",\"release_date\":\"2015-11-13\",\"label\":\"EP Entertainment, LLC / Def Jam\",\"timecode\":\"00:28\",\"song_link\":\"https://lis.tn/ScarsToYourBeautiful\",\"spotify\":{\"album\":{\"name\":\"Know-It-All (Deluxe)\",\"artists\":[{\"name\":\"Alessia Cara\",\"id\":\"2wUjUUtkb5lvLKcGKsKqsR\",\"uri\":\"spotify:artist:2wUjUUtkb5lvLKcGKsKqsR\",\"href\":\"https://api.spotify.com/v1/artists/2wUjUUtkb5lvLKcGKsKqsR\",\"external_urls\":{\"spotify\":\"https://open.spotify.com/artist/2wUjUUtkb5lvLKcGKsKqsR\"}}],\"album_group\":\"\",\"album_type\":\"album\",\"id\":\"3rDbA12I5duZnlwakqDdZa\",\"uri\":\"spotify:album:3rDbA12I5duZnlwakqDdZa\",\"available_markets\":null,\"href\":\"https://api.spotify.com/v1/albums/3rDbA12I5duZnlwakqDdZa\",\"images\":[{\"height\":640,\"width\":640,\"url\":\"https://i.scdn.co/image/ab67616d0000b273e3ae597159d6c2541c4ee61b\"},{\"height\":300,\"width\":300,\"url\":\"https://i.scdn.co/image/ab67616d00001e02e3ae597159d6c2541c4ee61b\"},{\"height\":64,\"width\":64,\"url\":\"https://i.scdn.co/image/ab67616d00004851e3ae597159d6c2541c4ee61b\"}],\"external_urls\":{\"spotify\":\"https://open.spotify.com/album/3rDbA12I5duZnlwakqDdZa\"},\"release_date\":\"2015-11-13\",\"release_date_precision\":\"day\"},\"external_ids\":{\"isrc\":\"USUM71506811\"},\"popularity\":75,\"is_playable\":true,\"linked_from\":null,\"artists\":[{\"name\":\"Alessia Cara\",\"id\":\"2wUjUUtkb5lvLKcGKsKqsR\",\"uri\":\"spotify:artist:2wUjUUtkb5lvLKcGKsKqsR\",\"href\":\"https://api.spotify.com/v1/artists/2wUjUUtkb5lvLKcGKsKqsR\",\"external_urls\":{\"spotify\":\"https://open.spotify.com/artist/2wUjUUtkb5lvLKcGKsKqsR\"}}],\"available_markets\":null,\"disc_number\":1,\"duration_ms\":230226,\"explicit\":false,\"external_urls\":{\"spotify\":\"https://open.spotify.com/track/0prNGof3XqfTvNDxHonvdK\"},\"href\":\"https://api.spotify.com/v1/tracks/0prNGof3XqfTvNDxHonvdK\",\"id\":\"0prNGof3XqfTvNDxHonvdK\",\"name\":\"Scars To Your Beautiful\",\"preview_url\":\"\",\"track_number\":10,\"uri\":\"spotify:track:0prNGof3XqfTvNDxHonvdK\"}}}",
@@ -316,4 +514,101 @@ mod test {
             )
         );
     }
+
+    #[test]
+    fn musicbrainz_and_apple_music() {
+        let song = parse_response_str(
+            r#"{"status":"success","result":{"artist":"Imagine Dragons","title":"Warriors","album":"Warriors","release_date":"2014-09-18","label":"Universal Music","timecode":"00:40","song_link":"https://lis.tn/Warriors","apple_music":{"previews":[{"url":"https://audio-ssl.itunes.apple.com/preview.m4a"}],"artwork":{"width":1500,"height":1500,"url":"https://is4-ssl.mzstatic.com/image/thumb/{w}x{h}bb.jpg"},"artistName":"Imagine Dragons","url":"https://music.apple.com/us/album/warriors/1440831203?i=1440831624","discNumber":1,"genreNames":["Alternative","Music"],"durationInMillis":170799,"releaseDate":"2014-09-18","name":"Warriors","isrc":"USUM71414211","albumName":"Smoke + Mirrors","playParams":{"id":"1440831624","kind":"song"},"trackNumber":13,"composerName":"Dan Reynolds"},"musicbrainz":[{"id":"d6d4f4f6-3d1b-4a3c-8d8f-0f1f3b4a2c1e","score":100,"title":"Warriors","length":170000,"disambiguation":"","video":null,"artist-credit":[{"name":"Imagine Dragons","artist":{"id":"012151a8-0f9a-44c9-997f-ebd68b5389f9","name":"Imagine Dragons","sort-name":"Imagine Dragons"}}],"releases":[],"isrcs":["USUM71414211","USUM71414212"],"tags":[{"count":1,"name":"rock"}]}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(song.isrc().as_deref(), Some("USUM71414211"));
+        assert_eq!(
+            song.musicbrainz_id().as_deref(),
+            Some("d6d4f4f6-3d1b-4a3c-8d8f-0f1f3b4a2c1e")
+        );
+        assert_eq!(
+            song.external_links()
+                .get(ExternalLinkKey::MusicBrainzUrl)
+                .as_deref(),
+            Some("https://musicbrainz.org/recording/d6d4f4f6-3d1b-4a3c-8d8f-0f1f3b4a2c1e")
+        );
+        assert_eq!(
+            song.external_links()
+                .get(ExternalLinkKey::AppleMusicUrl)
+                .as_deref(),
+            Some("https://music.apple.com/us/album/warriors/1440831203?i=1440831624")
+        );
+        assert_eq!(song.label().as_deref(), Some("Universal Music"));
+        assert_eq!(song.genre().as_deref(), Some("Alternative"));
+        assert_eq!(song.duration(), Some(Duration::from_millis(170799)));
+        assert_eq!(song.timecode(), Some(Duration::from_secs(40)));
+        assert_eq!(
+            song.album_art_link().as_deref(),
+            Some("https://is4-ssl.mzstatic.com/image/thumb/600x600bb.jpg")
+        );
+        assert_eq!(
+            song.playback_link().as_deref(),
+            Some("https://audio-ssl.itunes.apple.com/preview.m4a")
+        );
+
+        // Missing data from other providers is taken from MusicBrainz
+        let song = parse_response_str(
+            r#"{"status":"success","result":{"artist":"A","title":"B","album":"C","label":"","song_link":"https://lis.tn/B","musicbrainz":[{"id":"a","length":200000,"isrcs":["ISRC"],"tags":[{"count":2,"name":"pop"}]},{"id":"b"}]}}"#,
+        )
+        .unwrap();
+        assert_eq!(song.musicbrainz_id().as_deref(), Some("a"));
+        assert_eq!(song.isrc().as_deref(), Some("ISRC"));
+        assert_eq!(song.genre().as_deref(), Some("pop"));
+        assert_eq!(song.duration(), Some(Duration::from_secs(200)));
+        assert_eq!(song.label(), None);
+    }
+
+    #[test]
+    fn timecode() {
+        let candidates = parse_response_str_candidates(
+            r#"{"status":"success","result":{"artist":"Public","title":"Make You Mine","album":"Let's Make It","timecode":"00:43","song_link":"https://lis.tn/FUYgUV"}}"#,
+        )
+        .unwrap();
+        assert_eq!(candidates.len(), 1);
+        assert_eq!(candidates[0].confidence, None);
+        assert_eq!(candidates[0].timecode, Some(Duration::from_secs(43)));
+    }
+
+    #[test]
+    fn multiple_results() {
+        let candidates = parse_response_str_candidates(
+            r#"{"status":"success","result":[{"artist":"A","title":"Low","album":"A","timecode":"01:02","score":62,"song_link":"https://lis.tn/Low"},{"artist":"B","title":"Unknown","album":"B","song_link":"https://lis.tn/Unknown"},{"artist":"C","title":"High","album":"C","timecode":"1:00:05","score":95,"song_link":"https://lis.tn/High"}]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            candidates
+                .iter()
+                .map(|candidate| candidate.song.title())
+                .collect::<Vec<_>>(),
+            ["High", "Low", "Unknown"]
+        );
+        assert_eq!(candidates[0].confidence, Some(0.95));
+        assert_eq!(candidates[0].timecode, Some(Duration::from_secs(3605)));
+        assert_eq!(candidates[1].confidence, Some(0.62));
+        assert_eq!(candidates[1].timecode, Some(Duration::from_secs(62)));
+        assert_eq!(candidates[2].confidence, None);
+        assert_eq!(candidates[2].timecode, None);
+    }
+
+    #[test]
+    fn multiple_results_empty() {
+        let res = parse_response_str_candidates(r#"{"status":"success","result":[]}"#);
+        assert_eq!(res.unwrap_err().kind(), RecognizeErrorKind::NoMatches);
+    }
+
+    #[test]
+    fn parse_timecode_formats() {
+        assert_eq!(parse_timecode("00:43"), Some(Duration::from_secs(43)));
+        assert_eq!(parse_timecode("3:07"), Some(Duration::from_secs(187)));
+        assert_eq!(parse_timecode("01:00:05"), Some(Duration::from_secs(3605)));
+        assert_eq!(parse_timecode("43"), None);
+        assert_eq!(parse_timecode("1:2:3:4"), None);
+        assert_eq!(parse_timecode("aa:bb"), None);
+        assert_eq!(parse_timecode(""), None);
+    }
 }
@@ -22,6 +22,11 @@ pub struct AppleMusicData {
     pub previews: Vec<Preview>,
     pub url: String,
     pub artwork: Artwork,
+    pub isrc: Option<String>,
+    #[serde(rename(deserialize = "genreNames"), default)]
+    pub genre_names: Vec<String>,
+    #[serde(rename(deserialize = "durationInMillis"))]
+    pub duration_ms: Option<u64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -39,11 +44,35 @@ pub struct ExternalUrls {
     pub spotify: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct ExternalIds {
+    pub isrc: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct SpotifyData {
     pub album: Album,
     pub preview_url: String,
     pub external_urls: ExternalUrls,
+    pub external_ids: Option<ExternalIds>,
+    pub duration_ms: Option<u64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Tag {
+    pub name: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MusicBrainzData {
+    /// Recording id
+    pub id: String,
+    /// In milliseconds
+    pub length: Option<u64>,
+    #[serde(default)]
+    pub isrcs: Vec<String>,
+    #[serde(default)]
+    pub tags: Vec<Tag>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -61,6 +90,25 @@ pub struct Data {
     pub apple_music_data: Option<AppleMusicData>,
     #[serde(rename(deserialize = "lyrics"))]
     pub lyrics_data: Option<LyricsData>,
+    /// Matching recordings, from the most to the least likely
+    #[serde(rename(deserialize = "musicbrainz"), default)]
+    pub musicbrainz_data: Vec<MusicBrainzData>,
+    /// Record label that released the song
+    pub label: Option<String>,
+    /// Position in the song where the audio matched, in format of `mm:ss`
+    /// or `hh:mm:ss`
+    pub timecode: Option<String>,
+    /// How likely this is the correct match, from 0 to 100
+    pub score: Option<f64>,
+}
+
+/// The result is a single object for the standard API, but it is a list of
+/// matches ranked by score when multiple matches are returned.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawData {
+    Single(Box<Data>),
+    Multiple(Vec<Data>),
 }
 
 #[derive(Debug, Deserialize)]
@@ -71,6 +119,19 @@ struct AudDRawError {
     message: String,
 }
 
+impl AudDRawError {
+    fn into_recognize_error(self) -> RecognizeError {
+        // Based on https://docs.audd.io/#common-errors
+        let kind = match self.code {
+            901 => RecognizeErrorKind::TokenLimitReached,
+            900 => RecognizeErrorKind::InvalidToken,
+            300 => RecognizeErrorKind::Fingerprint,
+            _ => RecognizeErrorKind::OtherPermanent,
+        };
+        RecognizeError::new(kind, format!("#{}: {}", self.code, self.message))
+    }
+}
+
 /// If `status` is `success` `data` is `Some` and `error` is `None`. On the other hand, if status is
 /// `error` it is the opposite.
 ///
@@ -79,16 +140,26 @@ struct AudDRawError {
 pub struct Response {
     status: String,
     #[serde(rename(deserialize = "result"))]
-    data: Option<Data>,
+    data: Option<RawData>,
     error: Option<AudDRawError>,
 }
 
 impl Response {
-    pub fn data(self) -> Result<Data, RecognizeError> {
+    /// Returns the matches, which is never empty, ranked from the most to the
+    /// least likely.
+    pub fn data(self) -> Result<Vec<Data>, RecognizeError> {
         if self.status == "success" {
-            return self
-                .data
-                .ok_or_else(|| RecognizeError::new(RecognizeErrorKind::NoMatches, None));
+            let data = match self.data {
+                Some(RawData::Single(data)) => vec![*data],
+                Some(RawData::Multiple(data)) => data,
+                None => Vec::new(),
+            };
+
+            if data.is_empty() {
+                return Err(RecognizeError::new(RecognizeErrorKind::NoMatches, None));
+            }
+
+            return Ok(data);
         }
 
         if self.status == "error" {
@@ -99,17 +170,7 @@ impl Response {
                 )
             })?;
 
-            // Based on https://docs.audd.io/#common-errors
-            let kind = match error.code {
-                901 => RecognizeErrorKind::TokenLimitReached,
-                900 => RecognizeErrorKind::InvalidToken,
-                300 => RecognizeErrorKind::Fingerprint,
-                _ => RecognizeErrorKind::OtherPermanent,
-            };
-            return Err(RecognizeError::new(
-                kind,
-                format!("#{}: {}", error.code, error.message),
-            ));
+            return Err(error.into_recognize_error());
         }
 
         Err(RecognizeError::new(
@@ -118,3 +179,32 @@ impl Response {
         ))
     }
 }
+
+/// Response of requests other than recognition, where only whether it
+/// succeeded matters.
+#[derive(Debug, Deserialize)]
+pub struct StatusResponse {
+    status: String,
+    error: Option<AudDRawError>,
+}
+
+impl StatusResponse {
+    pub fn result(self) -> Result<(), RecognizeError> {
+        match self.status.as_str() {
+            "success" => Ok(()),
+            "error" => Err(self.error.map_or_else(
+                || {
+                    RecognizeError::new(
+                        RecognizeErrorKind::OtherPermanent,
+                        "Got `error` status but no error".to_string(),
+                    )
+                },
+                AudDRawError::into_recognize_error,
+            )),
+            status => Err(RecognizeError::new(
+                RecognizeErrorKind::OtherPermanent,
+                format!("Got invalid status response of {}", status),
+            )),
+        }
+    }
+}
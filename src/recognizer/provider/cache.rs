@@ -0,0 +1,330 @@
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use gtk::{glib, prelude::*, subclass::prelude::*};
+
+use std::{
+    cell::{Cell, RefCell},
+    time::{Duration, Instant},
+};
+
+use super::{Candidate, Provider, RecognizeError, fingerprint::Fingerprint};
+use crate::{recognizer::recorder::EncoderConfig, song::Song};
+
+/// A candidate kept as plain data, so each hit gets its own song instead of
+/// one shared with the earlier results, which may have been changed since.
+#[derive(Debug)]
+struct CachedCandidate {
+    /// Song encoded in bincode
+    song: Vec<u8>,
+    confidence: Option<f64>,
+    timecode: Option<Duration>,
+}
+
+impl CachedCandidate {
+    fn new(candidate: &Candidate) -> Result<Self> {
+        Ok(Self {
+            song: bincode::serialize(&candidate.song).context("Failed to serialize song")?,
+            confidence: candidate.confidence,
+            timecode: candidate.timecode,
+        })
+    }
+
+    fn to_candidate(&self) -> Result<Candidate> {
+        Ok(Candidate {
+            song: bincode::deserialize::<Song>(&self.song).context("Failed to deserialize song")?,
+            confidence: self.confidence,
+            timecode: self.timecode,
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    fingerprint: Fingerprint,
+    candidates: Vec<CachedCandidate>,
+    recognized_time: Instant,
+}
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, glib::Properties)]
+    #[properties(wrapper_type = super::RecognitionCache)]
+    pub struct RecognitionCache {
+        /// Number of recognitions answered from the cache
+        #[property(get)]
+        pub(super) n_hits: Cell<u32>,
+        /// Number of recognitions passed to the provider
+        #[property(get)]
+        pub(super) n_misses: Cell<u32>,
+
+        pub(super) entries: RefCell<Vec<Entry>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for RecognitionCache {
+        const NAME: &'static str = "MousaiRecognitionCache";
+        type Type = super::RecognitionCache;
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for RecognitionCache {}
+}
+
+glib::wrapper! {
+    /// Recent recognition results by the fingerprint of their audio
+    pub struct RecognitionCache(ObjectSubclass<imp::RecognitionCache>);
+}
+
+impl RecognitionCache {
+    fn new() -> Self {
+        glib::Object::new()
+    }
+
+    /// Returns the cache shared by all providers.
+    pub fn instance() -> Self {
+        thread_local! {
+            static INSTANCE: RecognitionCache = RecognitionCache::new();
+        }
+
+        INSTANCE.with(|instance| instance.clone())
+    }
+
+    /// Returns the result of recognizing audio matching the fingerprint if it
+    /// was recognized within `window`.
+    pub fn lookup(&self, fingerprint: &Fingerprint, window: Duration) -> Option<Vec<Candidate>> {
+        let imp = self.imp();
+
+        imp.entries
+            .borrow_mut()
+            .retain(|entry| entry.recognized_time.elapsed() <= window);
+
+        let candidates = imp
+            .entries
+            .borrow()
+            .iter()
+            .find(|entry| entry.fingerprint.matches(fingerprint))
+            .and_then(|entry| {
+                entry
+                    .candidates
+                    .iter()
+                    .map(|candidate| candidate.to_candidate())
+                    .collect::<Result<Vec<_>>>()
+                    .inspect_err(|err| tracing::warn!("Failed to read cached result: {:?}", err))
+                    .ok()
+            });
+
+        if candidates.is_some() {
+            imp.n_hits.set(imp.n_hits.get() + 1);
+            self.notify_n_hits();
+        } else {
+            imp.n_misses.set(imp.n_misses.get() + 1);
+            self.notify_n_misses();
+        }
+
+        candidates
+    }
+
+    pub fn insert(&self, fingerprint: Fingerprint, candidates: &[Candidate]) {
+        let candidates = match candidates
+            .iter()
+            .map(CachedCandidate::new)
+            .collect::<Result<Vec<_>>>()
+        {
+            Ok(candidates) => candidates,
+            Err(err) => {
+                tracing::warn!("Failed to cache result: {:?}", err);
+                return;
+            }
+        };
+
+        self.imp().entries.borrow_mut().push(Entry {
+            fingerprint,
+            candidates,
+            recognized_time: Instant::now(),
+        });
+    }
+
+    /// Removes all entries and resets the counts.
+    pub fn clear(&self) {
+        let imp = self.imp();
+
+        imp.entries.borrow_mut().clear();
+
+        imp.n_hits.set(0);
+        imp.n_misses.set(0);
+        self.notify_n_hits();
+        self.notify_n_misses();
+    }
+}
+
+/// Wraps a provider to reuse the result of recognizing the same audio again
+/// within `window`, instead of calling the provider.
+#[derive(Debug)]
+pub struct CachedProvider {
+    inner: Box<dyn Provider>,
+    window: Duration,
+}
+
+impl CachedProvider {
+    pub fn new(inner: Box<dyn Provider>, window: Duration) -> Self {
+        Self { inner, window }
+    }
+
+    /// Returns the fingerprint of the audio if it can be used to look up
+    /// results.
+    async fn fingerprint(bytes: &[u8]) -> Option<Fingerprint> {
+        match Fingerprint::from_audio(bytes).await {
+            Ok(fingerprint) if fingerprint.is_distinctive() => Some(fingerprint),
+            Ok(_) => {
+                tracing::debug!("Audio is not distinctive enough to be cached");
+                None
+            }
+            Err(err) => {
+                tracing::warn!("Failed to compute audio fingerprint: {:?}", err);
+                None
+            }
+        }
+    }
+}
+
+#[async_trait(?Send)]
+impl Provider for CachedProvider {
+    async fn recognize(&self, bytes: &[u8]) -> Result<Vec<Candidate>, RecognizeError> {
+        // Test providers are not cached so that changing their mode takes
+        // effect immediately.
+        if self.window.is_zero() || self.inner.is_test() {
+            return self.inner.recognize(bytes).await;
+        }
+
+        let Some(fingerprint) = Self::fingerprint(bytes).await else {
+            return self.inner.recognize(bytes).await;
+        };
+
+        let cache = RecognitionCache::instance();
+
+        if let Some(candidates) = cache.lookup(&fingerprint, self.window) {
+            tracing::debug!("Reusing cached recognition result");
+            return Ok(candidates);
+        }
+
+        let candidates = self.inner.recognize(bytes).await?;
+        cache.insert(fingerprint, &candidates);
+
+        Ok(candidates)
+    }
+
+    fn listen_duration(&self) -> Duration {
+        self.inner.listen_duration()
+    }
+
+    fn encoder_config(&self) -> EncoderConfig {
+        self.inner.encoder_config()
+    }
+
+    fn is_test(&self) -> bool {
+        self.inner.is_test()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{
+        recognizer::provider::fingerprint::tests::test_samples, song::test::new_test_song,
+    };
+
+    fn test_fingerprint(seed: u32) -> Fingerprint {
+        Fingerprint::from_samples(&test_samples(seed, 40_000, 1.0))
+    }
+
+    fn test_candidates(id: &str) -> Vec<Candidate> {
+        vec![Candidate {
+            song: new_test_song(id),
+            confidence: Some(0.9),
+            timecode: Some(Duration::from_secs(1)),
+        }]
+    }
+
+    fn assert_candidates_eq(a: &[Candidate], b: &[Candidate]) {
+        assert_eq!(a.len(), b.len());
+        for (a, b) in a.iter().zip(b) {
+            assert_eq!(a.song.id(), b.song.id());
+            assert_eq!(a.song.title(), b.song.title());
+            assert_eq!(a.confidence, b.confidence);
+            assert_eq!(a.timecode, b.timecode);
+        }
+    }
+
+    #[test]
+    fn lookup() {
+        let cache = RecognitionCache::new();
+
+        assert_eq!(
+            cache.lookup(&test_fingerprint(1), Duration::from_secs(60)),
+            None
+        );
+        assert_eq!(cache.n_hits(), 0);
+        assert_eq!(cache.n_misses(), 1);
+
+        let candidates = test_candidates("a");
+        cache.insert(test_fingerprint(1), &candidates);
+        let hit = cache
+            .lookup(&test_fingerprint(1), Duration::from_secs(60))
+            .unwrap();
+        assert_candidates_eq(&hit, &candidates);
+        assert_eq!(cache.n_hits(), 1);
+        assert_eq!(cache.n_misses(), 1);
+
+        assert_eq!(
+            cache.lookup(&test_fingerprint(2), Duration::from_secs(60)),
+            None
+        );
+        assert_eq!(cache.n_hits(), 1);
+        assert_eq!(cache.n_misses(), 2);
+
+        cache.clear();
+        assert_eq!(cache.n_hits(), 0);
+        assert_eq!(cache.n_misses(), 0);
+        assert_eq!(
+            cache.lookup(&test_fingerprint(1), Duration::from_secs(60)),
+            None
+        );
+    }
+
+    #[test]
+    fn lookup_creates_new_songs() {
+        let cache = RecognitionCache::new();
+
+        let candidates = test_candidates("a");
+        cache.insert(test_fingerprint(1), &candidates);
+
+        // Changes to the earlier results must not leak to later hits
+        candidates[0].song.set_title("Changed");
+
+        let hit_1 = cache
+            .lookup(&test_fingerprint(1), Duration::from_secs(60))
+            .unwrap();
+        let hit_2 = cache
+            .lookup(&test_fingerprint(1), Duration::from_secs(60))
+            .unwrap();
+        assert_eq!(hit_1[0].song.title(), "a");
+        assert_ne!(hit_1[0].song, candidates[0].song);
+        assert_ne!(hit_1[0].song, hit_2[0].song);
+    }
+
+    #[test]
+    fn lookup_expired() {
+        let cache = RecognitionCache::new();
+
+        cache.insert(test_fingerprint(1), &test_candidates("a"));
+        std::thread::sleep(Duration::from_millis(10));
+
+        assert_eq!(
+            cache.lookup(&test_fingerprint(1), Duration::from_millis(1)),
+            None
+        );
+        assert!(cache.imp().entries.borrow().is_empty());
+    }
+}
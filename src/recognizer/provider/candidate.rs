@@ -0,0 +1,134 @@
+use gtk::glib;
+
+use std::time::Duration;
+
+use super::{RecognizeError, RecognizeErrorKind};
+use crate::song::Song;
+
+/// How much lower than the top candidate's confidence another candidate's
+/// can be for it to still be considered a close match
+const CLOSE_CONFIDENCE_DELTA: f64 = 0.1;
+
+/// A possible match returned by a provider
+#[derive(Debug, Clone, PartialEq)]
+pub struct Candidate {
+    pub song: Song,
+    /// How likely this is the correct match, from 0 to 1
+    pub confidence: Option<f64>,
+    /// Position in the song where the recording matched
+    pub timecode: Option<Duration>,
+}
+
+/// Candidates ranked from the most to the least likely match
+#[derive(Debug, Clone, PartialEq, glib::Boxed)]
+#[boxed_type(name = "MousaiBoxedCandidates")]
+pub struct BoxedCandidates(pub Vec<Candidate>);
+
+/// Returns the leading candidates whose confidence is close to the top
+/// candidate's, which always includes the top candidate itself.
+///
+/// Candidates without confidence are never considered close, as there is
+/// nothing to compare them with.
+pub fn close_candidates(candidates: &[Candidate]) -> &[Candidate] {
+    let Some((top, rest)) = candidates.split_first() else {
+        return &[];
+    };
+
+    let Some(top_confidence) = top.confidence else {
+        return &candidates[..1];
+    };
+
+    let n_close = rest
+        .iter()
+        .take_while(|candidate| {
+            candidate
+                .confidence
+                .is_some_and(|confidence| top_confidence - confidence <= CLOSE_CONFIDENCE_DELTA)
+        })
+        .count();
+
+    &candidates[..1 + n_close]
+}
+
+/// Returns the song of the top candidate, for when there is no one to choose
+/// among the candidates.
+///
+/// This returns a `RecognizeErrorKind::NoMatches` error if there are no
+/// candidates.
+pub fn top_song(candidates: Vec<Candidate>) -> Result<Song, RecognizeError> {
+    candidates
+        .into_iter()
+        .next()
+        .map(|candidate| candidate.song)
+        .ok_or_else(|| RecognizeError::new(RecognizeErrorKind::NoMatches, None))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::song::test::new_test_song;
+
+    fn candidate(id: &str, confidence: Option<f64>) -> Candidate {
+        Candidate {
+            song: new_test_song(id),
+            confidence,
+            timecode: None,
+        }
+    }
+
+    fn titles(candidates: &[Candidate]) -> Vec<String> {
+        candidates
+            .iter()
+            .map(|candidate| candidate.song.title())
+            .collect()
+    }
+
+    #[test]
+    fn close_candidates_empty() {
+        assert!(close_candidates(&[]).is_empty());
+    }
+
+    #[test]
+    fn top_song_empty() {
+        assert_eq!(
+            top_song(Vec::new()).unwrap_err().kind(),
+            RecognizeErrorKind::NoMatches
+        );
+
+        let candidates = vec![candidate("a", Some(0.9)), candidate("b", Some(0.8))];
+        assert_eq!(top_song(candidates).unwrap().title(), "a");
+    }
+
+    #[test]
+    fn close_candidates_single() {
+        let candidates = [candidate("a", Some(0.9))];
+        assert_eq!(titles(close_candidates(&candidates)), ["a"]);
+
+        let candidates = [candidate("a", None)];
+        assert_eq!(titles(close_candidates(&candidates)), ["a"]);
+    }
+
+    #[test]
+    fn close_candidates_multiple() {
+        let candidates = [
+            candidate("a", Some(0.9)),
+            candidate("b", Some(0.85)),
+            candidate("c", Some(0.8)),
+            candidate("d", Some(0.5)),
+        ];
+        assert_eq!(titles(close_candidates(&candidates)), ["a", "b", "c"]);
+
+        let candidates = [candidate("a", Some(0.9)), candidate("b", Some(0.5))];
+        assert_eq!(titles(close_candidates(&candidates)), ["a"]);
+    }
+
+    #[test]
+    fn close_candidates_without_confidence() {
+        let candidates = [candidate("a", None), candidate("b", None)];
+        assert_eq!(titles(close_candidates(&candidates)), ["a"]);
+
+        let candidates = [candidate("a", Some(0.9)), candidate("b", None)];
+        assert_eq!(titles(close_candidates(&candidates)), ["a"]);
+    }
+}
@@ -0,0 +1,209 @@
+use anyhow::{Context, Result, anyhow, ensure};
+use gst::prelude::*;
+use gtk::{gio, glib};
+
+/// Sample rate the audio is decoded at before fingerprinting
+const SAMPLE_RATE: u32 = 8_000;
+
+/// Number of samples the energy is measured over (64 ms)
+const FRAME_LEN: usize = 512;
+
+/// Number of samples between the start of consecutive frames (32 ms)
+const FRAME_HOP: usize = 256;
+
+/// How long to wait for the decoder to produce a sample
+const DECODE_TIMEOUT: gst::ClockTime = gst::ClockTime::from_seconds(5);
+
+/// Minimum number of bits that must overlap for two fingerprints to match,
+/// around 2 seconds of audio
+const MIN_OVERLAP_BITS: usize = 64;
+
+/// Maximum ratio of differing bits in the overlap for two fingerprints to
+/// match. Unrelated audio differ in around half of the bits.
+const MAX_BIT_ERROR_RATE: f64 = 0.2;
+
+/// Compact description of how the loudness of the audio changes over time,
+/// which stays the same regardless of the volume and the encoding.
+///
+/// Each bit is whether the energy of a frame is higher than the previous one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Fingerprint {
+    bits: Vec<bool>,
+}
+
+impl Fingerprint {
+    /// Decodes the recorded audio and computes its fingerprint, off the main
+    /// thread as decoding blocks.
+    pub async fn from_audio(bytes: &[u8]) -> Result<Self> {
+        let bytes = glib::Bytes::from(bytes);
+        gio::spawn_blocking(move || {
+            let samples = decode(&bytes).context("Failed to decode audio")?;
+            Ok(Self::from_samples(&samples))
+        })
+        .await
+        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+    }
+
+    /// Computes the fingerprint of mono samples at `SAMPLE_RATE`.
+    pub fn from_samples(samples: &[i16]) -> Self {
+        let energies = (0..)
+            .map(|index| index * FRAME_HOP)
+            .take_while(|start| start + FRAME_LEN <= samples.len())
+            .map(|start| {
+                samples[start..start + FRAME_LEN]
+                    .iter()
+                    .map(|&sample| (sample as f64).powi(2))
+                    .sum::<f64>()
+            })
+            .collect::<Vec<_>>();
+
+        Self {
+            bits: energies
+                .windows(2)
+                .map(|window| window[1] > window[0])
+                .collect(),
+        }
+    }
+
+    /// Whether there is enough variation in the audio to tell it apart from
+    /// other audio. For example, silence is never distinctive.
+    pub fn is_distinctive(&self) -> bool {
+        if self.bits.len() < MIN_OVERLAP_BITS {
+            return false;
+        }
+
+        let n_set = self.bits.iter().filter(|&&bit| bit).count();
+        let set_ratio = n_set as f64 / self.bits.len() as f64;
+        (0.2..=0.8).contains(&set_ratio)
+    }
+
+    /// Whether the fingerprints are likely of the same audio, even if one
+    /// starts later than the other.
+    pub fn matches(&self, other: &Self) -> bool {
+        let (a, b) = (&self.bits, &other.bits);
+
+        if a.len() < MIN_OVERLAP_BITS || b.len() < MIN_OVERLAP_BITS {
+            return false;
+        }
+
+        // Shift `b` from starting `a.len() - MIN_OVERLAP_BITS` bits before
+        // `a` to starting `b.len() - MIN_OVERLAP_BITS` bits after it.
+        let min_offset = -((a.len() - MIN_OVERLAP_BITS) as isize);
+        let max_offset = (b.len() - MIN_OVERLAP_BITS) as isize;
+
+        (min_offset..=max_offset).any(|offset| {
+            let (a, b) = if offset < 0 {
+                (&a[offset.unsigned_abs()..], &b[..])
+            } else {
+                (&a[..], &b[offset as usize..])
+            };
+
+            let overlap = a.len().min(b.len());
+            let n_errors = a.iter().zip(b).filter(|(a, b)| a != b).count();
+            n_errors as f64 / overlap as f64 <= MAX_BIT_ERROR_RATE
+        })
+    }
+}
+
+/// Returns the mono samples at `SAMPLE_RATE` of the encoded audio.
+fn decode(bytes: &glib::Bytes) -> Result<Vec<i16>> {
+    let pipeline = gst::parse::launch(&format!(
+        "giostreamsrc name=src ! decodebin ! audioconvert ! audioresample ! audio/x-raw,format=S16LE,channels=1,rate={} ! appsink name=sink sync=false",
+        SAMPLE_RATE
+    ))?
+    .downcast::<gst::Pipeline>()
+    .map_err(|_| anyhow!("Decoding pipeline is not a pipeline"))?;
+
+    let src = pipeline.by_name("src").context("No source")?;
+    src.set_property("stream", gio::MemoryInputStream::from_bytes(bytes));
+    let sink = pipeline.by_name("sink").context("No sink")?;
+
+    pipeline.set_state(gst::State::Playing)?;
+
+    let res = pull_samples(&sink);
+    pipeline.set_state(gst::State::Null)?;
+    res
+}
+
+fn pull_samples(sink: &gst::Element) -> Result<Vec<i16>> {
+    let mut samples = Vec::new();
+
+    while let Some(sample) =
+        sink.emit_by_name::<Option<gst::Sample>>("try-pull-sample", &[&DECODE_TIMEOUT.nseconds()])
+    {
+        let buffer = sample.buffer().context("Sample has no buffer")?;
+        let map = buffer.map_readable()?;
+        samples.extend(
+            map.chunks_exact(2)
+                .map(|chunk| i16::from_le_bytes([chunk[0], chunk[1]])),
+        );
+    }
+
+    ensure!(
+        sink.property::<bool>("eos"),
+        "Decoding stopped before the end"
+    );
+
+    Ok(samples)
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+
+    /// Returns noise whose loudness changes randomly every 300 samples.
+    pub fn test_samples(seed: u32, len: usize, gain: f64) -> Vec<i16> {
+        let mut state = seed;
+        let mut amplitude = 0.0;
+
+        (0..len)
+            .map(|index| {
+                if index % 300 == 0 {
+                    state = state.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                    amplitude = (state >> 16) as f64 / u16::MAX as f64 * 10_000.0;
+                }
+
+                let sign = if index % 2 == 0 { 1.0 } else { -1.0 };
+                (sign * amplitude * gain) as i16
+            })
+            .collect()
+    }
+
+    #[test]
+    fn matches_same_audio() {
+        let samples = test_samples(1, 40_000, 1.0);
+
+        let a = Fingerprint::from_samples(&samples);
+        assert!(a.is_distinctive());
+        assert!(a.matches(&a));
+
+        // Quieter and starting later
+        let quieter = test_samples(1, 40_000, 0.5);
+        let b = Fingerprint::from_samples(&quieter[FRAME_HOP * 20..]);
+        assert!(a.matches(&b));
+        assert!(b.matches(&a));
+    }
+
+    #[test]
+    fn does_not_match_other_audio() {
+        let a = Fingerprint::from_samples(&test_samples(1, 40_000, 1.0));
+        let b = Fingerprint::from_samples(&test_samples(2, 40_000, 1.0));
+        assert!(!a.matches(&b));
+        assert!(!b.matches(&a));
+    }
+
+    #[test]
+    fn too_short() {
+        let a = Fingerprint::from_samples(&test_samples(1, 40_000, 1.0));
+        let b = Fingerprint::from_samples(&test_samples(1, FRAME_HOP * 10, 1.0));
+        assert!(!b.is_distinctive());
+        assert!(!a.matches(&b));
+        assert!(!b.matches(&a));
+    }
+
+    #[test]
+    fn silence_is_not_distinctive() {
+        let fingerprint = Fingerprint::from_samples(&[0; 40_000]);
+        assert!(!fingerprint.is_distinctive());
+    }
+}
@@ -1,7 +1,11 @@
 mod aud_d;
+mod cache;
+mod candidate;
 mod error;
 mod error_tester;
+mod fingerprint;
 mod settings;
+mod usage;
 
 use async_trait::async_trait;
 use gtk::glib;
@@ -9,19 +13,32 @@ use gtk::glib;
 use std::{fmt, time::Duration};
 
 pub use self::{
+    aud_d::AudD,
+    cache::{CachedProvider, RecognitionCache},
+    candidate::{BoxedCandidates, Candidate, close_candidates, top_song},
     error::{RecognizeError, RecognizeErrorKind},
     settings::{ProviderSettings, ProviderType, TestProviderMode},
+    usage::ProviderUsage,
 };
-use crate::song::Song;
+use super::recorder::EncoderConfig;
 
 #[async_trait(?Send)]
 pub trait Provider: fmt::Debug {
     /// Recognize a song from bytes
-    async fn recognize(&self, bytes: &[u8]) -> Result<Song, RecognizeError>;
+    ///
+    /// The returned candidates are ranked from the most to the least likely
+    /// match and must not be empty; `RecognizeErrorKind::NoMatches` must be
+    /// returned instead.
+    async fn recognize(&self, bytes: &[u8]) -> Result<Vec<Candidate>, RecognizeError>;
 
     /// How long to record the audio
     fn listen_duration(&self) -> Duration;
 
+    /// Sample rate and bitrate to encode the recorded audio with
+    fn encoder_config(&self) -> EncoderConfig {
+        EncoderConfig::default()
+    }
+
     /// Whether this supports `TestProviderMode`
     fn is_test(&self) -> bool {
         false
@@ -34,7 +51,7 @@ pub trait TestProvider {
         &self,
         bytes: &[u8],
         mode: TestProviderMode,
-    ) -> Result<Song, RecognizeError>;
+    ) -> Result<Vec<Candidate>, RecognizeError>;
 }
 
 #[async_trait(?Send)]
@@ -42,7 +59,7 @@ impl<T> Provider for T
 where
     T: TestProvider + fmt::Debug,
 {
-    async fn recognize(&self, bytes: &[u8]) -> Result<Song, RecognizeError> {
+    async fn recognize(&self, bytes: &[u8]) -> Result<Vec<Candidate>, RecognizeError> {
         let duration = ProviderSettings::lock().test_recognize_duration;
         glib::timeout_future(duration).await;
 
@@ -38,19 +38,26 @@ pub enum ProviderType {
 impl ProviderType {
     pub fn to_provider(self) -> Box<dyn Provider> {
         use super::{
+            CachedProvider,
             aud_d::{AudD, AudDMock},
             error_tester::ErrorTester,
         };
 
-        match self {
+        let provider: Box<dyn Provider> = match self {
             Self::AudD => {
                 // FIXME handle this outside
-                let api_token = Application::get().settings().aud_d_api_token();
-                Box::new(AudD::new(Some(&api_token)))
+                let api_token = Application::get().credentials().aud_d_api_token();
+                Box::new(AudD::new(api_token.as_deref()))
             }
             Self::AudDMock => Box::new(AudDMock),
             Self::ErrorTester => Box::new(ErrorTester),
-        }
+        };
+
+        let cache_window = Application::get().settings().recognition_cache_window();
+        Box::new(CachedProvider::new(
+            provider,
+            Duration::from_secs(cache_window.into()),
+        ))
     }
 }
 
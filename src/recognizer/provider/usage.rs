@@ -0,0 +1,119 @@
+use gtk::glib;
+
+use crate::settings::Settings;
+
+/// Number of requests AudD allows per day without a token
+const AUD_D_TRIAL_DAILY_LIMIT: u32 = 10;
+
+/// Calls left before the daily limit at which the user is warned
+const WARN_REMAINING_CALLS: u32 = 3;
+
+/// Local count of the calls made to the provider today
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProviderUsage {
+    pub calls: u32,
+    /// Known number of calls allowed per day, if any
+    pub daily_limit: Option<u32>,
+}
+
+impl ProviderUsage {
+    /// Returns today's usage of AudD.
+    ///
+    /// The daily limit is only known for the trial without a token, or if
+    /// the user has set the one of their plan.
+    pub fn today(settings: &Settings, has_token: bool) -> Self {
+        let daily_limit = if has_token {
+            Some(settings.aud_d_daily_limit()).filter(|&limit| limit > 0)
+        } else {
+            Some(AUD_D_TRIAL_DAILY_LIMIT)
+        };
+
+        Self {
+            calls: calls_on(
+                &settings.provider_calls_day(),
+                settings.provider_calls(),
+                &today(),
+            ),
+            daily_limit,
+        }
+    }
+
+    /// Counts a call made to the provider today.
+    pub fn record_call(settings: &Settings) {
+        let today = today();
+        let calls = calls_on(
+            &settings.provider_calls_day(),
+            settings.provider_calls(),
+            &today,
+        );
+
+        settings.set_provider_calls_day(&today);
+        settings.set_provider_calls(calls.saturating_add(1));
+    }
+
+    pub fn remaining_calls(&self) -> Option<u32> {
+        self.daily_limit
+            .map(|daily_limit| daily_limit.saturating_sub(self.calls))
+    }
+
+    /// Whether the daily limit is about to be or has been reached.
+    pub fn is_near_limit(&self) -> bool {
+        self.remaining_calls()
+            .is_some_and(|remaining| remaining <= WARN_REMAINING_CALLS)
+    }
+}
+
+/// Returns the calls made on `day`, given the stored count of `stored_day`.
+fn calls_on(stored_day: &str, stored_calls: u32, day: &str) -> u32 {
+    if stored_day == day { stored_calls } else { 0 }
+}
+
+fn today() -> String {
+    glib::DateTime::now_local()
+        .and_then(|now| now.format("%F"))
+        .map(String::from)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn calls_on_resets_on_new_day() {
+        assert_eq!(calls_on("2024-01-01", 5, "2024-01-01"), 5);
+        assert_eq!(calls_on("2024-01-01", 5, "2024-01-02"), 0);
+        assert_eq!(calls_on("", 5, "2024-01-02"), 0);
+    }
+
+    #[test]
+    fn remaining_calls() {
+        let usage = ProviderUsage {
+            calls: 4,
+            daily_limit: Some(10),
+        };
+        assert_eq!(usage.remaining_calls(), Some(6));
+        assert!(!usage.is_near_limit());
+
+        let usage = ProviderUsage {
+            calls: 7,
+            daily_limit: Some(10),
+        };
+        assert_eq!(usage.remaining_calls(), Some(3));
+        assert!(usage.is_near_limit());
+
+        let usage = ProviderUsage {
+            calls: 12,
+            daily_limit: Some(10),
+        };
+        assert_eq!(usage.remaining_calls(), Some(0));
+        assert!(usage.is_near_limit());
+
+        let usage = ProviderUsage {
+            calls: 1000,
+            daily_limit: None,
+        };
+        assert_eq!(usage.remaining_calls(), None);
+        assert!(!usage.is_near_limit());
+    }
+}
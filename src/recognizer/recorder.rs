@@ -8,12 +8,62 @@ use gtk::{
 };
 
 use crate::{
-    device::{self, DeviceClass},
+    device::{self, AppStream, DeviceClass},
     settings::AudioSourceType,
 };
 
-#[derive(Default)]
+/// Sample rates in Hz that the audio can be captured at, which are the ones
+/// `webrtcdsp` accepts when preprocessing
+const SAMPLE_RATES: [i32; 4] = [8_000, 16_000, 32_000, 48_000];
+
+/// Parameters passed to the encoder of the recording
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncoderConfig {
+    sample_rate: i32,
+    bitrate: i32,
+}
+
+impl EncoderConfig {
+    /// Creates a config with the sample rate clamped to the closest one that
+    /// the audio can be captured at.
+    pub fn new(sample_rate: i32, bitrate: i32) -> Self {
+        let clamped_sample_rate = SAMPLE_RATES
+            .into_iter()
+            .min_by_key(|rate| rate.abs_diff(sample_rate))
+            .unwrap();
+
+        if clamped_sample_rate != sample_rate {
+            tracing::warn!(
+                "Unsupported sample rate {} Hz; using {} Hz instead",
+                sample_rate,
+                clamped_sample_rate
+            );
+        }
+
+        Self {
+            sample_rate: clamped_sample_rate,
+            bitrate,
+        }
+    }
+
+    /// Sample rate in Hz
+    pub fn sample_rate(&self) -> i32 {
+        self.sample_rate
+    }
+
+    /// Target bitrate in bits per second
+    pub fn bitrate(&self) -> i32 {
+        self.bitrate
+    }
+}
+
+impl Default for EncoderConfig {
+    fn default() -> Self {
+        Self::new(16_000, 16_000)
+    }
+}
 
+#[derive(Default)]
 pub struct Recorder {
     pipeline: RefCell<Option<(gst::Pipeline, BusWatchGuard, gio::MemoryOutputStream)>>,
 }
@@ -30,6 +80,9 @@ impl Recorder {
     pub fn start(
         &self,
         audio_source_type: AudioSourceType,
+        app_stream: Option<&AppStream>,
+        encoder_config: EncoderConfig,
+        is_preprocessing_enabled: bool,
         peak_callback: impl Fn(f64) + 'static,
     ) -> Result<()> {
         ensure!(
@@ -38,7 +91,13 @@ impl Recorder {
         );
 
         let output_stream = gio::MemoryOutputStream::new_resizable();
-        let pipeline = create_pipeline(&output_stream, audio_source_type)?;
+        let pipeline = create_pipeline(
+            &output_stream,
+            audio_source_type,
+            app_stream,
+            encoder_config,
+            is_preprocessing_enabled,
+        )?;
 
         let bus_watch_guard = pipeline
             .bus()
@@ -58,6 +117,10 @@ impl Recorder {
         Ok(())
     }
 
+    pub fn is_recording(&self) -> bool {
+        self.pipeline.borrow().is_some()
+    }
+
     pub fn stop(&self) -> Result<glib::Bytes> {
         let (pipeline, _bus_watch_guard, stream) = self
             .pipeline
@@ -147,11 +210,14 @@ fn handle_bus_message(
     }
 }
 
-fn make_pulsesrc(audio_source_type: AudioSourceType) -> Result<gst::Element> {
-    let pulsesrc = gst::ElementFactory::make("pulsesrc").build()?;
-
+fn make_source(
+    audio_source_type: AudioSourceType,
+    app_stream: Option<&AppStream>,
+) -> Result<gst::Element> {
     match audio_source_type {
         AudioSourceType::DesktopAudio => {
+            let pulsesrc = gst::ElementFactory::make("pulsesrc").build()?;
+
             let device = device::find_default(DeviceClass::Sink)?;
             let pulsesink = device.create_element(None)?;
 
@@ -164,8 +230,12 @@ fn make_pulsesrc(audio_source_type: AudioSourceType) -> Result<gst::Element> {
             pulsesrc.set_property("device", &monitor_name);
 
             tracing::debug!("Found desktop audio with name `{}`", monitor_name);
+
+            Ok(pulsesrc)
         }
         AudioSourceType::Microphone => {
+            let pulsesrc = gst::ElementFactory::make("pulsesrc").build()?;
+
             let device = device::find_default(DeviceClass::Source)?;
             device.reconfigure_element(&pulsesrc)?;
 
@@ -175,51 +245,124 @@ fn make_pulsesrc(audio_source_type: AudioSourceType) -> Result<gst::Element> {
             ensure!(!device_name.is_empty(), "Empty device name");
 
             tracing::debug!("Found microphone with name `{}`", device_name);
+
+            Ok(pulsesrc)
+        }
+        AudioSourceType::ApplicationAudio => {
+            let app_stream = app_stream.context("No application stream selected")?;
+
+            // Pulse can only record from sources and their monitors, so the
+            // stream node is targeted directly through PipeWire instead.
+            let pipewiresrc = gst::ElementFactory::make("pipewiresrc")
+                .property("target-object", app_stream.serial.to_string())
+                .build()?;
+
+            tracing::debug!(
+                "Found application stream `{}` with serial `{}`",
+                app_stream.display_name(),
+                app_stream.serial
+            );
+
+            Ok(pipewiresrc)
         }
     }
+}
+
+/// Creates elements that normalize the gain, cut the low rumble, and suppress
+/// the background noise of the raw audio.
+///
+/// This uses `webrtcdsp` if it is available, otherwise it falls back to a
+/// high-pass filter and a noise gate.
+fn make_preprocessing_elements() -> Result<Vec<gst::Element>> {
+    if gst::ElementFactory::find("webrtcdsp").is_some() {
+        let webrtcdsp = gst::ElementFactory::make("webrtcdsp")
+            .property("echo-cancel", false)
+            .property("gain-control", true)
+            .property("high-pass-filter", true)
+            .property("noise-suppression", true)
+            .build()?;
+        // `webrtcdsp` only outputs a limited set of formats
+        let audioconvert = gst::ElementFactory::make("audioconvert").build()?;
+
+        tracing::debug!("Using webrtcdsp for preprocessing");
+
+        return Ok(vec![webrtcdsp, audioconvert]);
+    }
+
+    let high_pass = gst::ElementFactory::make("audiocheblimit")
+        .property_from_str("mode", "high-pass")
+        .property("cutoff", 80_f32)
+        .build()?;
+    let noise_gate = gst::ElementFactory::make("audiodynamic")
+        .property_from_str("mode", "expander")
+        .property_from_str("characteristics", "soft-knee")
+        .property("threshold", 0.02_f32)
+        .property("ratio", 0.5_f32)
+        .build()?;
+    let compressor = gst::ElementFactory::make("audiodynamic")
+        .property_from_str("mode", "compressor")
+        .property_from_str("characteristics", "soft-knee")
+        .property("threshold", 0.5_f32)
+        .property("ratio", 0.5_f32)
+        .build()?;
+
+    tracing::debug!(
+        "webrtcdsp is not available; using audiocheblimit and audiodynamic for preprocessing"
+    );
 
-    Ok(pulsesrc)
+    Ok(vec![high_pass, noise_gate, compressor])
 }
 
 fn create_pipeline(
     stream: &gio::MemoryOutputStream,
     audio_source_type: AudioSourceType,
+    app_stream: Option<&AppStream>,
+    encoder_config: EncoderConfig,
+    is_preprocessing_enabled: bool,
 ) -> Result<gst::Pipeline> {
     let pipeline = gst::Pipeline::new();
 
-    let pulsesrc = make_pulsesrc(audio_source_type)?;
+    let source = make_source(audio_source_type, app_stream)?;
     let audioconvert = gst::ElementFactory::make("audioconvert").build()?;
+    let preprocessing_elements = if is_preprocessing_enabled {
+        make_preprocessing_elements().context("Failed to make preprocessing elements")?
+    } else {
+        Vec::new()
+    };
     let level = gst::ElementFactory::make("level")
         .property("interval", gst::ClockTime::from_mseconds(80))
         .property("peak-ttl", gst::ClockTime::from_mseconds(80))
         .build()?;
+    // Opus only encodes some of the sample rates the audio can be captured at
+    let audioresample = gst::ElementFactory::make("audioresample").build()?;
     let opusenc = gst::ElementFactory::make("opusenc")
-        .property("bitrate", 16_000)
+        .property("bitrate", encoder_config.bitrate())
         .build()?;
     let oggmux = gst::ElementFactory::make("oggmux").build()?;
     let giostreamsink = gst::ElementFactory::make("giostreamsink")
         .property("stream", stream)
         .build()?;
 
-    let elements = [
-        &pulsesrc,
-        &audioconvert,
-        &level,
-        &opusenc,
-        &oggmux,
-        &giostreamsink,
-    ];
-    pipeline.add_many(elements)?;
-
-    pulsesrc.link_filtered(
+    let elements = [&source, &audioconvert]
+        .into_iter()
+        .chain(&preprocessing_elements)
+        .chain([&level, &audioresample, &opusenc, &oggmux, &giostreamsink])
+        .collect::<Vec<_>>();
+    pipeline.add_many(&elements)?;
+
+    source.link_filtered(
         &audioconvert,
         &gst::Caps::builder("audio/x-raw")
             .field("channels", 1)
-            .field("rate", 16_000)
+            .field("rate", encoder_config.sample_rate())
             .build(),
     )?;
-    audioconvert.link(&level)?;
-    level.link(&opusenc)?;
+    gst::Element::link_many(
+        [&audioconvert]
+            .into_iter()
+            .chain(&preprocessing_elements)
+            .chain([&level, &audioresample, &opusenc]),
+    )?;
     opusenc.link_filtered(&oggmux, &gst::Caps::builder("audio/x-opus").build())?;
     oggmux.link_filtered(&giostreamsink, &gst::Caps::builder("audio/ogg").build())?;
 
@@ -229,3 +372,17 @@ fn create_pipeline(
 
     Ok(pipeline)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encoder_config_clamps_sample_rate() {
+        assert_eq!(EncoderConfig::new(16_000, 16_000).sample_rate(), 16_000);
+        assert_eq!(EncoderConfig::new(44_100, 16_000).sample_rate(), 48_000);
+        assert_eq!(EncoderConfig::new(22_050, 16_000).sample_rate(), 16_000);
+        assert_eq!(EncoderConfig::new(0, 16_000).sample_rate(), 8_000);
+        assert_eq!(EncoderConfig::new(96_000, 16_000).sample_rate(), 48_000);
+    }
+}
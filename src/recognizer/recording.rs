@@ -1,10 +1,16 @@
+use anyhow::Context;
 use gtk::{glib, prelude::*, subclass::prelude::*};
 use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
 
 use std::cell::{Cell, OnceCell, RefCell};
 
 use super::RecognizeError;
-use crate::{date_time::DateTime, serde_helpers, song::Song};
+use crate::{
+    database::versioned::{self, Versioned},
+    date_time::DateTime,
+    serde_helpers,
+    song::{Song, SongV1, SongV2, SongV3},
+};
 
 #[derive(Debug, Clone, PartialEq, Eq, glib::Boxed, Serialize, Deserialize)]
 #[boxed_type(name = "MousaiBoxedRecognizeResult", nullable)]
@@ -25,6 +31,12 @@ mod imp {
         #[property(get, set = Self::set_recognize_result, explicit_notify, nullable)]
         pub(super) recognize_result: RefCell<Option<BoxedRecognizeResult>>,
 
+        /// Whether this was recorded in incognito mode, so it must be kept
+        /// only in memory
+        #[property(get, set, construct_only)]
+        #[serde(skip)]
+        pub(super) is_incognito: Cell<bool>,
+
         #[serde(skip)] // So we can retry next session
         pub(super) recognize_retries: Cell<u8>,
     }
@@ -63,6 +75,15 @@ impl Recording {
             .build()
     }
 
+    /// Creates a recording that is kept only in memory.
+    pub fn new_incognito(bytes: &glib::Bytes, recorded_time: &DateTime) -> Self {
+        glib::Object::builder()
+            .property("bytes", bytes)
+            .property("recorded-time", recorded_time)
+            .property("is-incognito", true)
+            .build()
+    }
+
     pub fn recognize_retries(&self) -> u8 {
         self.imp().recognize_retries.get()
     }
@@ -105,11 +126,117 @@ impl<'de> Deserialize<'de> for Recording {
     }
 }
 
+impl Versioned for Recording {
+    // This contains a song, so this is incremented along with the version of
+    // `Song`.
+    const VERSION: u16 = RecordingV3::VERSION + Song::VERSION - SongV3::VERSION;
+
+    fn upgrade(version: u16, bytes: &[u8]) -> anyhow::Result<Self> {
+        match version {
+            1 => versioned::upgrade_from(bytes, |recording_v1: RecordingV1| {
+                recording_v1
+                    .into_recording_v2()
+                    .into_recording_v3()
+                    .into_recording()
+            }),
+            2 => versioned::upgrade_from(bytes, |recording_v2: RecordingV2| {
+                recording_v2.into_recording_v3().into_recording()
+            }),
+            _ => anyhow::bail!("Unknown version {}", version),
+        }
+    }
+}
+
+/// Layout of [`Recording`] in the database before [`Song`] kept its original
+/// metadata.
+///
+/// This is only used for migrating the stored recordings.
+#[derive(Serialize, Deserialize)]
+pub struct RecordingV1 {
+    bytes: Option<Vec<u8>>,
+    recorded_time: Option<DateTime>,
+    recognize_result: Option<Result<SongV1, RecognizeError>>,
+}
+
+impl RecordingV1 {
+    pub fn into_recording_v2(self) -> RecordingV2 {
+        RecordingV2 {
+            bytes: self.bytes,
+            recorded_time: self.recorded_time,
+            recognize_result: self
+                .recognize_result
+                .map(|recognize_result| recognize_result.map(|song_v1| song_v1.into_song_v2())),
+        }
+    }
+}
+
+/// Layout of [`Recording`] in the database before [`Song`] stored the extended
+/// metadata.
+///
+/// This is only used for migrating the stored recordings.
+#[derive(Serialize, Deserialize)]
+pub struct RecordingV2 {
+    bytes: Option<Vec<u8>>,
+    recorded_time: Option<DateTime>,
+    recognize_result: Option<Result<SongV2, RecognizeError>>,
+}
+
+impl RecordingV2 {
+    pub fn into_recording_v3(self) -> RecordingV3 {
+        RecordingV3 {
+            bytes: self.bytes,
+            recorded_time: self.recorded_time,
+            recognize_result: self
+                .recognize_result
+                .map(|recognize_result| recognize_result.map(|song_v2| song_v2.into_song_v3())),
+        }
+    }
+}
+
+/// Layout of [`Recording`] in the database after [`Song`] stored the extended
+/// metadata.
+///
+/// This is only used for migrating the stored recordings, so it must not
+/// change even when [`Recording`] does.
+#[derive(Serialize, Deserialize)]
+pub struct RecordingV3 {
+    bytes: Option<Vec<u8>>,
+    recorded_time: Option<DateTime>,
+    recognize_result: Option<Result<SongV3, RecognizeError>>,
+}
+
+impl RecordingV3 {
+    pub const VERSION: u16 = 3;
+
+    pub fn into_recording(self) -> anyhow::Result<Recording> {
+        let bytes = self.bytes.context("Recording has no bytes")?;
+        let recorded_time = self
+            .recorded_time
+            .context("Recording has no recorded time")?;
+
+        let recording = Recording::new(&glib::Bytes::from_owned(bytes), &recorded_time);
+        if let Some(recognize_result) = self.recognize_result {
+            let recognize_result = match recognize_result {
+                Ok(song_v3) => Ok(song_v3.into_song()?),
+                Err(err) => Err(err),
+            };
+            recording.set_recognize_result(Some(BoxedRecognizeResult(recognize_result)));
+        }
+
+        Ok(recording)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    use crate::recognizer::RecognizeErrorKind;
+    use heed::{BytesDecode, BytesEncode};
+
+    use crate::{
+        database::versioned::VersionedCodec, recognizer::RecognizeErrorKind,
+        song::test::assert_fixture_song,
+    };
 
     fn assert_recording_eq(v1: &Recording, v2: &Recording) {
         assert_eq!(v1.bytes(), v2.bytes());
@@ -166,4 +293,36 @@ mod tests {
         assert_recording_eq(&val, &de_val);
         assert_ne!(val.recognize_retries(), de_val.recognize_retries());
     }
+
+    #[test]
+    fn versioned_fixtures() {
+        let fixtures: [(u16, &[u8]); 3] = [
+            (1, include_bytes!("../database/fixtures/recording_v1.bin")),
+            (2, include_bytes!("../database/fixtures/recording_v2.bin")),
+            (3, include_bytes!("../database/fixtures/recording_v3.bin")),
+        ];
+        assert_eq!(fixtures.last().unwrap().0, Recording::VERSION);
+
+        for (version, bytes) in fixtures {
+            let recording = VersionedCodec::<Recording>::bytes_decode(bytes).unwrap();
+            assert_eq!(recording.bytes().as_ref(), [1, 2, 3]);
+            assert_eq!(
+                recording.recorded_time(),
+                DateTime::from_iso8601("2024-01-02T03:00:00Z").unwrap()
+            );
+            let Some(BoxedRecognizeResult(Ok(song))) = recording.recognize_result() else {
+                panic!("recording must have a recognized song");
+            };
+            assert_fixture_song(&song, version);
+
+            let new_bytes = VersionedCodec::<Recording>::bytes_encode(&recording).unwrap();
+            let de_recording = VersionedCodec::<Recording>::bytes_decode(&new_bytes).unwrap();
+            assert_eq!(recording.bytes(), de_recording.bytes());
+            assert_eq!(recording.recorded_time(), de_recording.recorded_time());
+
+            if version == Recording::VERSION {
+                assert_eq!(new_bytes, bytes);
+            }
+        }
+    }
 }
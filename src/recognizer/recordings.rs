@@ -5,7 +5,6 @@ use gtk::{
     prelude::*,
     subclass::prelude::*,
 };
-use heed::types::SerdeBincode;
 use indexmap::IndexMap;
 
 use std::{
@@ -16,14 +15,14 @@ use std::{
 
 use super::Recording;
 use crate::{
-    database::{EnvExt, RECORDINGS_DB_NAME},
+    database::{EnvExt, RECORDINGS_DB_NAME, quarantine, versioned::VersionedCodec},
     uid::{Uid, UidCodec},
     utils,
 };
 
 const RECORDING_NOTIFY_HANDLER_ID_KEY: &str = "mousai-recording-notify-handler-id";
 
-type RecordingDatabase = heed::Database<UidCodec, SerdeBincode<Recording>>;
+type RecordingDatabase = heed::Database<UidCodec, VersionedCodec<Recording>>;
 
 mod imp {
     use super::*;
@@ -77,11 +76,10 @@ impl Recordings {
             let db: RecordingDatabase = env
                 .create_database(wtxn, Some(RECORDINGS_DB_NAME))
                 .context("Failed to create recordings db")?;
-            let recordings = db
-                .iter(wtxn)
-                .context("Failed to iter recordings from db")?
-                .collect::<Result<IndexMap<_, _>, _>>()
-                .context("Failed to collect recordings from db")?;
+            let recordings = quarantine::decode_all(&env, wtxn, db, RECORDINGS_DB_NAME)
+                .context("Failed to decode recordings from db")?
+                .into_iter()
+                .collect::<IndexMap<_, _>>();
             Ok((db, recordings))
         })?;
 
@@ -104,15 +102,19 @@ impl Recordings {
         Ok(this)
     }
 
+    /// Appends the recording, storing it in the database unless it is
+    /// incognito.
     pub fn insert(&self, recording: Recording) -> Result<()> {
         let recording_id = Uid::generate();
 
-        let (env, db) = self.db();
-        env.with_write_txn(|wtxn| {
-            db.put(wtxn, &recording_id, &recording)
-                .context("Failed to put recording to db")?;
-            Ok(())
-        })?;
+        if !recording.is_incognito() {
+            let (env, db) = self.db();
+            env.with_write_txn(|wtxn| {
+                db.put(wtxn, &recording_id, &recording)
+                    .context("Failed to put recording to db")?;
+                Ok(())
+            })?;
+        }
 
         self.bind_recording_to_items_changed_and_db(&recording_id, &recording);
 
@@ -148,14 +150,15 @@ impl Recordings {
         let mut to_take_indices = BTreeSet::new();
         for (index, (id, recording)) in imp.list.borrow().iter().enumerate() {
             if filter_func(recording) {
-                to_take_ids.push(id.clone());
+                // Incognito recordings are not in the db
+                if !recording.is_incognito() {
+                    to_take_ids.push(id.clone());
+                }
                 to_take_indices.insert(index);
                 debug_assert_eq!(index, imp.list.borrow().get_index_of(id).unwrap());
             }
         }
 
-        debug_assert_eq!(to_take_ids.len(), to_take_indices.len());
-
         let (env, db) = self.db();
         env.with_write_txn(|wtxn| {
             for key in &to_take_ids {
@@ -215,17 +218,19 @@ impl Recordings {
                         tracing::debug!("Recording property `{}` notified", pspec.name());
 
                         let (env, db) = obj.db();
-                        if let Err(err) = env.with_write_txn(|wtxn| {
-                            debug_assert!(
-                                db.get(wtxn, &recording_id).unwrap().is_some(),
-                                "recording must exist in the db"
-                            );
-
-                            db.put(wtxn, &recording_id, recording)
-                                .context("Failed to put recording to db")?;
-
-                            Ok(())
-                        }) {
+                        if !recording.is_incognito()
+                            && let Err(err) = env.with_write_txn(|wtxn| {
+                                debug_assert!(
+                                    db.get(wtxn, &recording_id).unwrap().is_some(),
+                                    "recording must exist in the db"
+                                );
+
+                                db.put(wtxn, &recording_id, recording)
+                                    .context("Failed to put recording to db")?;
+
+                                Ok(())
+                            })
+                        {
                             tracing::error!("Failed to update recording in database: {:?}", err);
                         }
 
@@ -255,17 +260,14 @@ mod tests {
     use std::rc::Rc;
 
     use crate::{
-        database, date_time::DateTime, recognizer::recording::BoxedRecognizeResult, song::Song,
+        database, date_time::DateTime, recognizer::recording::BoxedRecognizeResult,
+        song::test::new_test_song,
     };
 
     fn new_test_recording(bytes: &'static [u8]) -> Recording {
         Recording::new(&glib::Bytes::from_static(bytes), &DateTime::now_utc())
     }
 
-    fn new_test_song(id: &str) -> Song {
-        Song::builder(&Uid::from(id), id, id, id).build()
-    }
-
     fn assert_n_items_and_db_count_eq(recordings: &Recordings, n: usize) {
         assert_eq!(recordings.n_items(), n as u32);
 
@@ -401,6 +403,39 @@ mod tests {
         assert_synced_to_db(&recordings);
     }
 
+    #[test]
+    fn insert_incognito() {
+        let (env, _tempdir) = database::new_test_env();
+        let recordings = Recordings::load_from_env(env).unwrap();
+
+        recordings.insert(new_test_recording(b"a")).unwrap();
+        recordings
+            .insert(Recording::new_incognito(
+                &glib::Bytes::from_static(b"b"),
+                &DateTime::now_utc(),
+            ))
+            .unwrap();
+        assert_eq!(recordings.n_items(), 2);
+
+        let (env, db) = recordings.db();
+        let db_len = |env: &heed::Env| db.len(&env.read_txn().unwrap()).unwrap();
+        assert_eq!(db_len(env), 1);
+
+        let incognito = recordings.item(1).and_downcast::<Recording>().unwrap();
+        assert!(incognito.is_incognito());
+        incognito.set_recognize_result(Some(BoxedRecognizeResult(Ok(new_test_song("b")))));
+        assert_eq!(db_len(env), 1);
+
+        let taken = recordings.take_filtered(|r| r.is_incognito()).unwrap();
+        assert_eq!(taken.len(), 1);
+        assert_eq!(recordings.n_items(), 1);
+        assert_eq!(db_len(env), 1);
+
+        let taken = recordings.take_filtered(|_| true).unwrap();
+        assert_eq!(taken.len(), 1);
+        assert_n_items_and_db_count_eq(&recordings, 0);
+    }
+
     #[test]
     fn insert_items_changed() {
         let (env, _tempdir) = database::new_test_env();
@@ -0,0 +1,294 @@
+use anyhow::{Context, Result};
+
+use crate::{
+    date_time::DateTime,
+    recognizer::{Recording, Recordings},
+    settings::Settings,
+    song_list::SongList,
+    song_trash::SongTrash,
+    uid::Uid,
+};
+
+const USECS_PER_DAY: i64 = 24 * 60 * 60 * 1_000_000;
+
+/// How long songs and saved recordings are kept in the history
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetentionPolicy {
+    /// Songs last heard and recordings recorded more than this many days ago
+    /// are removed, or 0 to keep them regardless of their age
+    pub max_age_days: u32,
+    /// Only this many of the most recently heard songs are kept, or 0 to keep
+    /// any number of songs
+    pub max_songs: u32,
+}
+
+impl RetentionPolicy {
+    pub fn from_settings(settings: &Settings) -> Self {
+        Self {
+            max_age_days: settings.history_max_age_days(),
+            max_songs: settings.history_max_songs(),
+        }
+    }
+
+    /// Whether this never removes anything.
+    pub fn keeps_all(&self) -> bool {
+        self.max_age_days == 0 && self.max_songs == 0
+    }
+
+    /// Returns the ids of the songs that must be removed, from the least to
+    /// the most recently heard, given the ids of the songs and when they were
+    /// last heard in unix microseconds.
+    ///
+    /// Songs without a last heard time are never removed for their age, but
+    /// are the first to be removed when there are too many songs.
+    pub fn songs_to_prune(&self, songs: &[(Uid, Option<i64>)], now: &DateTime) -> Vec<Uid> {
+        let mut songs = songs.iter().collect::<Vec<_>>();
+        songs.sort_by_key(|(_, last_heard)| *last_heard);
+
+        let n_excess = if self.max_songs == 0 {
+            0
+        } else {
+            songs.len().saturating_sub(self.max_songs as usize)
+        };
+
+        songs
+            .into_iter()
+            .enumerate()
+            .filter(|(index, (_, last_heard))| {
+                *index < n_excess
+                    || last_heard.is_some_and(|last_heard| self.is_expired(last_heard, now))
+            })
+            .map(|(_, (song_id, _))| song_id.clone())
+            .collect()
+    }
+
+    /// Whether the saved recording must be removed.
+    pub fn is_recording_expired(&self, recording: &Recording, now: &DateTime) -> bool {
+        self.is_expired(recording.recorded_time().to_unix_usec(), now)
+    }
+
+    fn is_expired(&self, time_usec: i64, now: &DateTime) -> bool {
+        self.max_age_days != 0
+            && now.to_unix_usec() - time_usec > self.max_age_days as i64 * USECS_PER_DAY
+    }
+}
+
+/// What is removed when pruning
+#[derive(Debug, Default)]
+pub struct PruneSummary {
+    /// Ids of the removed songs, from the least to the most recently heard
+    pub song_ids: Vec<Uid>,
+    pub n_recordings: usize,
+}
+
+impl PruneSummary {
+    pub fn is_empty(&self) -> bool {
+        self.song_ids.is_empty() && self.n_recordings == 0
+    }
+}
+
+/// Returns what [`prune`] would remove, without removing anything.
+pub fn preview(
+    policy: RetentionPolicy,
+    song_list: &SongList,
+    recordings: &Recordings,
+    now: &DateTime,
+) -> PruneSummary {
+    if policy.keeps_all() {
+        return PruneSummary::default();
+    }
+
+    PruneSummary {
+        song_ids: policy.songs_to_prune(&song_list.last_heard_times(), now),
+        n_recordings: recordings
+            .peek_filtered(|recording| policy.is_recording_expired(recording, now))
+            .len(),
+    }
+}
+
+/// Moves the songs to the trash, and removes the saved recordings, that are
+/// not to be kept according to the policy.
+///
+/// The clips of the songs are kept along with them in the trash, until they
+/// are purged from it.
+pub fn prune(
+    policy: RetentionPolicy,
+    song_list: &SongList,
+    song_trash: &SongTrash,
+    recordings: &Recordings,
+    now: &DateTime,
+) -> Result<PruneSummary> {
+    if policy.keeps_all() {
+        return Ok(PruneSummary::default());
+    }
+
+    let songs_to_prune = policy.songs_to_prune(&song_list.last_heard_times(), now);
+    let songs = songs_to_prune
+        .iter()
+        .filter_map(|song_id| song_list.get(song_id))
+        .collect::<Vec<_>>();
+
+    // Trash first, so the songs are not lost if it fails
+    song_trash
+        .insert_many(&songs, now)
+        .context("Failed to move songs to trash")?;
+    song_list
+        .remove_many(&songs.iter().map(|song| song.id_ref()).collect::<Vec<_>>())
+        .context("Failed to remove songs")?;
+
+    let n_recordings = recordings
+        .take_filtered(|recording| policy.is_recording_expired(recording, now))
+        .context("Failed to remove saved recordings")?
+        .len();
+
+    tracing::debug!(
+        "Pruned {} songs and {} saved recordings with {:?}",
+        songs.len(),
+        n_recordings,
+        policy
+    );
+
+    Ok(PruneSummary {
+        song_ids: songs.iter().map(|song| song.id()).collect(),
+        n_recordings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use gtk::prelude::*;
+
+    use crate::{database, song::test::new_test_song};
+
+    fn entry(id: &str, last_heard: Option<&str>) -> (Uid, Option<i64>) {
+        let last_heard =
+            last_heard.map(|last_heard| DateTime::from_iso8601(last_heard).unwrap().to_unix_usec());
+        (Uid::from(id), last_heard)
+    }
+
+    fn ids(ids: &[&str]) -> Vec<Uid> {
+        ids.iter().map(|&id| Uid::from(id)).collect()
+    }
+
+    fn test_songs() -> Vec<(Uid, Option<i64>)> {
+        vec![
+            entry("a", Some("2024-01-10T00:00:00Z")),
+            entry("b", Some("2024-01-01T00:00:00Z")),
+            entry("c", None),
+            entry("d", Some("2024-01-20T00:00:00Z")),
+        ]
+    }
+
+    fn now() -> DateTime {
+        DateTime::from_iso8601("2024-01-21T00:00:00Z").unwrap()
+    }
+
+    #[test]
+    fn songs_to_prune_keeps_all() {
+        let policy = RetentionPolicy {
+            max_age_days: 0,
+            max_songs: 0,
+        };
+        assert!(policy.keeps_all());
+        assert!(policy.songs_to_prune(&test_songs(), &now()).is_empty());
+    }
+
+    #[test]
+    fn songs_to_prune_max_age() {
+        let policy = RetentionPolicy {
+            max_age_days: 15,
+            max_songs: 0,
+        };
+        assert_eq!(policy.songs_to_prune(&test_songs(), &now()), ids(&["b"]));
+
+        let policy = RetentionPolicy {
+            max_age_days: 5,
+            max_songs: 0,
+        };
+        assert_eq!(
+            policy.songs_to_prune(&test_songs(), &now()),
+            ids(&["b", "a"])
+        );
+    }
+
+    #[test]
+    fn songs_to_prune_max_songs() {
+        let policy = RetentionPolicy {
+            max_age_days: 0,
+            max_songs: 2,
+        };
+        assert_eq!(
+            policy.songs_to_prune(&test_songs(), &now()),
+            ids(&["c", "b"])
+        );
+
+        let policy = RetentionPolicy {
+            max_age_days: 0,
+            max_songs: 10,
+        };
+        assert!(policy.songs_to_prune(&test_songs(), &now()).is_empty());
+    }
+
+    #[test]
+    fn songs_to_prune_max_age_and_songs() {
+        let policy = RetentionPolicy {
+            max_age_days: 15,
+            max_songs: 3,
+        };
+        assert_eq!(
+            policy.songs_to_prune(&test_songs(), &now()),
+            ids(&["c", "b"])
+        );
+    }
+
+    #[test]
+    fn prune_moves_songs_to_trash() {
+        let (env, _tempdir) = database::new_test_env();
+        let song_list = SongList::load_from_env(env.clone()).unwrap();
+        let song_trash = SongTrash::load_from_env(env.clone()).unwrap();
+        let recordings = Recordings::load_from_env(env).unwrap();
+
+        for (id, last_heard) in [
+            ("a", "2024-01-10T00:00:00Z"),
+            ("b", "2024-01-01T00:00:00Z"),
+            ("c", "2024-01-20T00:00:00Z"),
+        ] {
+            let song = new_test_song(id);
+            song.set_last_heard(DateTime::from_iso8601(last_heard).unwrap());
+            song_list.insert(song).unwrap();
+        }
+
+        let policy = RetentionPolicy {
+            max_age_days: 15,
+            max_songs: 0,
+        };
+        let summary = prune(policy, &song_list, &song_trash, &recordings, &now()).unwrap();
+        assert_eq!(summary.song_ids, ids(&["b"]));
+        assert!(!song_list.contains(&Uid::from("b")));
+        assert_eq!(song_list.n_items(), 2);
+
+        // Pruned songs can still be restored until they are purged
+        let trashed_songs = song_trash.snapshot().unwrap();
+        assert_eq!(trashed_songs.len(), 1);
+        assert_eq!(trashed_songs[0].song.id_ref(), &Uid::from("b"));
+        assert_eq!(trashed_songs[0].deleted_time, now());
+    }
+
+    #[test]
+    fn is_recording_expired() {
+        let policy = RetentionPolicy {
+            max_age_days: 15,
+            max_songs: 0,
+        };
+        let recording = |recorded_time: &str| {
+            Recording::new(
+                &gtk::glib::Bytes::from_static(b""),
+                &DateTime::from_iso8601(recorded_time).unwrap(),
+            )
+        };
+        assert!(policy.is_recording_expired(&recording("2024-01-01T00:00:00Z"), &now()));
+        assert!(!policy.is_recording_expired(&recording("2024-01-10T00:00:00Z"), &now()));
+    }
+}
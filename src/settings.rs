@@ -6,6 +6,11 @@ use std::collections::HashMap;
 use crate::config::APP_ID;
 
 #[gen_settings(file = "./data/io.github.seadve.Mousai.gschema.xml.in")]
+#[gen_settings_define(
+    key_name = "link-templates",
+    arg_type = "Vec<HashMap<String, String>>",
+    ret_type = "Vec<HashMap<String, String>>"
+)]
 #[gen_settings_define(
     key_name = "memory-list",
     arg_type = "Vec<HashMap<String, String>>",
@@ -18,3 +23,15 @@ impl Default for Settings {
         Self::new(APP_ID)
     }
 }
+
+#[cfg(test)]
+impl Settings {
+    /// Returns settings that are only kept in memory, so tests don't change
+    /// the user's settings.
+    pub fn new_in_memory() -> Self {
+        Self(gio::Settings::with_backend(
+            APP_ID,
+            &gio::memory_settings_backend_new(),
+        ))
+    }
+}
@@ -1,23 +1,41 @@
-use anyhow::Result;
+use anyhow::{Context, Result, bail};
 use fuzzy_matcher::{FuzzyMatcher, skim::SkimMatcherV2};
 use gtk::{glib, prelude::*, subclass::prelude::*};
+use indexmap::IndexMap;
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Deserializer, Serialize, Serializer, de};
 
 use std::{
     cell::{Cell, OnceCell, RefCell},
+    marker::PhantomData,
     rc::Rc,
+    time::Duration,
 };
 
 use crate::{
     Application,
     album_art::AlbumArt,
+    database::versioned::{self, Versioned},
     date_time::DateTime,
     external_links::{ExternalLinkKey, ExternalLinks},
     serde_helpers,
     uid::Uid,
 };
 
+/// Prefix of the ids of songs that were added by the user
+const MANUALLY_ADDED_ID_PREFIX: &str = "Manual";
+
+/// Metadata of a song that can be edited by the user
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SongMetadata {
+    pub title: String,
+    pub artist: String,
+    pub album: String,
+    pub release_date: Option<String>,
+    /// External link values by their keys
+    pub external_links: IndexMap<String, String>,
+}
+
 mod imp {
     use super::*;
 
@@ -29,19 +47,19 @@ mod imp {
         #[serde(with = "serde_helpers::once_cell")]
         pub(super) id: OnceCell<Uid>,
         /// Title of the song
-        #[property(get, set, construct_only)]
+        #[property(get, set = Self::set_title, explicit_notify)]
         pub(super) title: RefCell<String>,
         /// Artist of the song
-        #[property(get, set, construct_only)]
+        #[property(get, set = Self::set_artist, explicit_notify)]
         pub(super) artist: RefCell<String>,
         /// Album where the song was from
-        #[property(get, set, construct_only)]
+        #[property(get, set = Self::set_album, explicit_notify)]
         pub(super) album: RefCell<String>,
         /// Arbitrary string for release date
-        #[property(get, set, construct_only)]
+        #[property(get, set = Self::set_release_date, explicit_notify)]
         pub(super) release_date: RefCell<Option<String>>,
         /// Links relevant to the song
-        #[property(get, set, construct_only)]
+        #[property(get, set = Self::set_external_links, explicit_notify)]
         pub(super) external_links: RefCell<ExternalLinks>,
         /// Link where the album art can be downloaded
         #[property(get, set, construct_only)]
@@ -52,12 +70,39 @@ mod imp {
         /// Lyrics of the song
         #[property(get, set, construct_only)]
         pub(super) lyrics: RefCell<Option<String>>,
+        /// International Standard Recording Code
+        #[property(get, set, construct_only)]
+        pub(super) isrc: RefCell<Option<String>>,
+        /// MusicBrainz recording id
+        #[property(get, set, construct_only)]
+        pub(super) musicbrainz_id: RefCell<Option<String>>,
+        /// Record label that released the song
+        #[property(get, set, construct_only)]
+        pub(super) label: RefCell<Option<String>>,
+        /// Genre of the song
+        #[property(get, set, construct_only)]
+        pub(super) genre: RefCell<Option<String>>,
         /// Date and time when last heard
         #[property(get, set = Self::set_last_heard, explicit_notify)]
         pub(super) last_heard: RefCell<Option<DateTime>>,
         /// Whether the song was heard for the first time
         #[property(get, set = Self::set_is_newly_heard, explicit_notify)]
         pub(super) is_newly_heard: Cell<bool>,
+        /// Whether the metadata was edited by the user
+        #[property(get = Self::is_edited)]
+        #[serde(skip)]
+        pub(super) is_edited: PhantomData<bool>,
+        /// Whether the song was added by the user instead of being recognized
+        #[property(get = Self::is_manually_added)]
+        #[serde(skip)]
+        pub(super) is_manually_added: PhantomData<bool>,
+
+        /// Metadata from the provider before it was first edited
+        pub(super) original_metadata: RefCell<Option<SongMetadata>>,
+        /// Length of the song
+        pub(super) duration: Cell<Option<Duration>>,
+        /// Position in the song where the recording matched when last heard
+        pub(super) timecode: Cell<Option<Duration>>,
     }
 
     #[glib::object_subclass]
@@ -70,6 +115,86 @@ mod imp {
     impl ObjectImpl for Song {}
 
     impl Song {
+        fn set_title(&self, title: String) {
+            let obj = self.obj();
+
+            if title == obj.title() {
+                return;
+            }
+
+            self.title.replace(title);
+            obj.notify_title();
+        }
+
+        fn set_artist(&self, artist: String) {
+            let obj = self.obj();
+
+            if artist == obj.artist() {
+                return;
+            }
+
+            self.artist.replace(artist);
+            obj.notify_artist();
+        }
+
+        fn set_album(&self, album: String) {
+            let obj = self.obj();
+
+            if album == obj.album() {
+                return;
+            }
+
+            self.album.replace(album);
+            obj.notify_album();
+        }
+
+        fn set_release_date(&self, release_date: Option<String>) {
+            let obj = self.obj();
+
+            if release_date == obj.release_date() {
+                return;
+            }
+
+            self.release_date.replace(release_date);
+            obj.notify_release_date();
+        }
+
+        fn set_external_links(&self, external_links: ExternalLinks) {
+            let obj = self.obj();
+
+            if external_links == obj.external_links() {
+                return;
+            }
+
+            self.external_links.replace(external_links);
+            obj.notify_external_links();
+        }
+
+        fn is_edited(&self) -> bool {
+            self.original_metadata.borrow().is_some()
+        }
+
+        fn is_manually_added(&self) -> bool {
+            self.obj().id_ref().has_prefix(MANUALLY_ADDED_ID_PREFIX)
+        }
+
+        pub(super) fn set_metadata(&self, metadata: SongMetadata) {
+            let obj = self.obj();
+
+            // Only notify after all values are set, so that listeners see a
+            // consistent state, and once per property.
+            let _guard = obj.freeze_notify();
+
+            self.set_title(metadata.title);
+            self.set_artist(metadata.artist);
+            self.set_album(metadata.album);
+            self.set_release_date(metadata.release_date);
+            if metadata.external_links != obj.external_links().to_map() {
+                self.set_external_links(ExternalLinks::from_map(metadata.external_links));
+            }
+            obj.notify_is_edited();
+        }
+
         fn set_last_heard(&self, last_heard: Option<DateTime>) {
             let obj = self.obj();
 
@@ -109,6 +234,17 @@ impl Song {
         SongBuilder::new(id, title, artist, album)
     }
 
+    /// Like [`Song::builder`], but for songs added by the user instead of
+    /// being recognized, with a generated id.
+    pub fn manually_added_builder(title: &str, artist: &str, album: &str) -> SongBuilder {
+        SongBuilder::new(
+            &Uid::generate_prefixed(MANUALLY_ADDED_ID_PREFIX),
+            title,
+            artist,
+            album,
+        )
+    }
+
     /// Returns the score of song against the pattern.
     pub fn fuzzy_match(&self, pattern: &str) -> Option<i64> {
         static FUZZY_MATCHER: Lazy<SkimMatcherV2> = Lazy::new(SkimMatcherV2::default);
@@ -127,6 +263,136 @@ impl Song {
         self.imp().id.get().unwrap()
     }
 
+    /// Length of the song, if known
+    pub fn duration(&self) -> Option<Duration> {
+        self.imp().duration.get()
+    }
+
+    /// Position in the song where the recording matched when last heard, if
+    /// known
+    pub fn timecode(&self) -> Option<Duration> {
+        self.imp().timecode.get()
+    }
+
+    /// Returns the current, possibly edited, metadata of the song.
+    pub fn metadata(&self) -> SongMetadata {
+        SongMetadata {
+            title: self.title(),
+            artist: self.artist(),
+            album: self.album(),
+            release_date: self.release_date(),
+            external_links: self.external_links().to_map(),
+        }
+    }
+
+    /// Returns the metadata from the provider if the song was edited.
+    pub fn original_metadata(&self) -> Option<SongMetadata> {
+        self.imp().original_metadata.borrow().clone()
+    }
+
+    /// Replaces the metadata of the song with the given one, keeping the
+    /// metadata from the provider so that it can be reverted later.
+    pub fn edit(&self, metadata: SongMetadata) {
+        let imp = self.imp();
+
+        let original_metadata = imp
+            .original_metadata
+            .take()
+            .unwrap_or_else(|| self.metadata());
+
+        // Editing the values back to the original is the same as reverting.
+        if metadata != original_metadata {
+            imp.original_metadata.replace(Some(original_metadata));
+        }
+
+        imp.set_metadata(metadata);
+    }
+
+    /// Restores the metadata from the provider, if the song was edited.
+    pub fn revert_edits(&self) {
+        let imp = self.imp();
+
+        let Some(original_metadata) = imp.original_metadata.take() else {
+            return;
+        };
+
+        imp.set_metadata(original_metadata);
+    }
+
+    /// Absorbs `other`, which is the same track stored as a separate song.
+    ///
+    /// The values of this song take precedence, and only the missing ones are
+    /// taken from `other`, except for the last heard time, where the latest
+    /// one is kept.
+    pub fn merge(&self, other: &Song) {
+        let imp = self.imp();
+
+        let _guard = self.freeze_notify();
+
+        if let Some(other_last_heard) = other.last_heard()
+            && self
+                .last_heard()
+                .is_none_or(|last_heard| other_last_heard > last_heard)
+        {
+            self.set_last_heard(other_last_heard);
+            imp.timecode.set(other.timecode());
+        }
+
+        if other.is_newly_heard() {
+            self.set_is_newly_heard(true);
+        }
+
+        let mut metadata = self.metadata();
+        if metadata.release_date.is_none() {
+            metadata.release_date = other.release_date();
+        }
+        for (key, value) in other.external_links().to_map() {
+            metadata.external_links.entry(key).or_insert(value);
+        }
+        if metadata != self.metadata() {
+            imp.set_metadata(metadata);
+        }
+
+        if self.album_art_link().is_none() && other.album_art_link().is_some() {
+            imp.album_art_link.replace(other.album_art_link());
+            self.notify_album_art_link();
+        }
+
+        if self.playback_link().is_none() && other.playback_link().is_some() {
+            imp.playback_link.replace(other.playback_link());
+            self.notify_playback_link();
+        }
+
+        if self.lyrics().is_none() && other.lyrics().is_some() {
+            imp.lyrics.replace(other.lyrics());
+            self.notify_lyrics();
+        }
+
+        if self.isrc().is_none() && other.isrc().is_some() {
+            imp.isrc.replace(other.isrc());
+            self.notify_isrc();
+        }
+
+        if self.musicbrainz_id().is_none() && other.musicbrainz_id().is_some() {
+            imp.musicbrainz_id.replace(other.musicbrainz_id());
+            self.notify_musicbrainz_id();
+        }
+
+        if self.label().is_none() && other.label().is_some() {
+            imp.label.replace(other.label());
+            self.notify_label();
+        }
+
+        if self.genre().is_none() && other.genre().is_some() {
+            imp.genre.replace(other.genre());
+            self.notify_genre();
+        }
+
+        if self.duration().is_none() {
+            imp.duration.set(other.duration());
+        }
+    }
+
     /// Returns a result of album art for the corresponding album art link if it exists
     pub fn album_art(&self) -> Option<Rc<AlbumArt>> {
         let album_art_link = self.album_art_link()?;
@@ -148,7 +414,7 @@ impl Serialize for Song {
 impl<'de> Deserialize<'de> for Song {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         let deserialized_imp = imp::Song::deserialize(deserializer)?;
-        Ok(glib::Object::builder()
+        let song = glib::Object::builder()
             .property(
                 "id",
                 deserialized_imp
@@ -170,12 +436,263 @@ impl<'de> Deserialize<'de> for Song {
             )
             .property("playback-link", deserialized_imp.playback_link.into_inner())
             .property("lyrics", deserialized_imp.lyrics.into_inner())
+            .property("isrc", deserialized_imp.isrc.into_inner())
+            .property(
+                "musicbrainz-id",
+                deserialized_imp.musicbrainz_id.into_inner(),
+            )
+            .property("label", deserialized_imp.label.into_inner())
+            .property("genre", deserialized_imp.genre.into_inner())
             .property("last-heard", deserialized_imp.last_heard.into_inner())
             .property(
                 "is-newly-heard",
                 deserialized_imp.is_newly_heard.into_inner(),
             )
-            .build())
+            .build::<Self>();
+        let imp = song.imp();
+        imp.original_metadata
+            .replace(deserialized_imp.original_metadata.into_inner());
+        imp.duration.set(deserialized_imp.duration.get());
+        imp.timecode.set(deserialized_imp.timecode.get());
+        Ok(song)
+    }
+}
+
+impl Versioned for Song {
+    // Recordings and trashed songs contain songs, so their versions are
+    // derived from this.
+    const VERSION: u16 = 3;
+
+    fn upgrade(version: u16, bytes: &[u8]) -> Result<Self> {
+        match version {
+            1 => versioned::upgrade_from(bytes, |song_v1: SongV1| {
+                song_v1.into_song_v2().into_song_v3().into_song()
+            }),
+            2 => {
+                versioned::upgrade_from(bytes, |song_v2: SongV2| song_v2.into_song_v3().into_song())
+            }
+            _ => bail!("Unknown version {}", version),
+        }
+    }
+}
+
+/// Fields of a stored [`Song`] that can be read without creating the song, such
+/// as off the main thread
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SongSummary {
+    pub id: Uid,
+    pub title: String,
+    pub artist: String,
+    /// External link values by their keys
+    pub external_links: IndexMap<String, String>,
+    pub isrc: Option<String>,
+    pub musicbrainz_id: Option<String>,
+    /// When the song was last heard, in unix microseconds
+    pub last_heard: Option<i64>,
+}
+
+impl SongSummary {
+    pub fn new(song: &Song) -> Self {
+        Self {
+            id: song.id(),
+            title: song.title(),
+            artist: song.artist(),
+            external_links: song.external_links().to_map(),
+            isrc: song.isrc(),
+            musicbrainz_id: song.musicbrainz_id(),
+            last_heard: song
+                .last_heard()
+                .map(|last_heard| last_heard.to_unix_usec()),
+        }
+    }
+
+    /// Decodes the summary of a song stored with its versioned layout.
+    ///
+    /// Only the needed fields are decoded if it is stored with the current
+    /// layout, so the song is not created.
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let Some(prefix) = versioned::decode_prefix::<Song, SongPrefix<'_>>(bytes)? else {
+            let song = versioned::decode::<Song>(bytes)?;
+            return Ok(Self::new(&song));
+        };
+
+        Ok(Self {
+            id: prefix.id.context("Song has no id")?,
+            title: prefix.title,
+            artist: prefix.artist,
+            external_links: prefix.external_links,
+            isrc: prefix.isrc,
+            musicbrainz_id: prefix.musicbrainz_id,
+            last_heard: prefix
+                .last_heard
+                .map(|last_heard| last_heard.to_unix_usec()),
+        })
+    }
+}
+
+/// The leading fields of the current layout of [`Song`], up to when it was
+/// last heard, where the fields not in [`SongSummary`] are only borrowed
+///
+/// This must be changed along with the layout of `Song`.
+#[derive(Deserialize)]
+struct SongPrefix<'a> {
+    id: Option<Uid>,
+    title: String,
+    artist: String,
+    _album: &'a str,
+    #[serde(borrow)]
+    _release_date: Option<&'a str>,
+    external_links: IndexMap<String, String>,
+    #[serde(borrow)]
+    _album_art_link: Option<&'a str>,
+    #[serde(borrow)]
+    _playback_link: Option<&'a str>,
+    #[serde(borrow)]
+    _lyrics: Option<&'a str>,
+    isrc: Option<String>,
+    musicbrainz_id: Option<String>,
+    #[serde(borrow)]
+    _label: Option<&'a str>,
+    #[serde(borrow)]
+    _genre: Option<&'a str>,
+    last_heard: Option<DateTime>,
+}
+
+/// Layout of [`Song`] in the database before the original metadata was kept.
+///
+/// This is only used for migrating the stored songs.
+#[derive(Serialize, Deserialize)]
+pub struct SongV1 {
+    id: Option<Uid>,
+    title: String,
+    artist: String,
+    album: String,
+    release_date: Option<String>,
+    external_links: ExternalLinks,
+    album_art_link: Option<String>,
+    playback_link: Option<String>,
+    lyrics: Option<String>,
+    last_heard: Option<DateTime>,
+    is_newly_heard: bool,
+}
+
+impl SongV1 {
+    pub fn into_song_v2(self) -> SongV2 {
+        SongV2 {
+            id: self.id,
+            title: self.title,
+            artist: self.artist,
+            album: self.album,
+            release_date: self.release_date,
+            external_links: self.external_links,
+            album_art_link: self.album_art_link,
+            playback_link: self.playback_link,
+            lyrics: self.lyrics,
+            last_heard: self.last_heard,
+            is_newly_heard: self.is_newly_heard,
+            original_metadata: None,
+        }
+    }
+}
+
+/// Layout of [`Song`] in the database before the extended metadata, such as
+/// the ISRC, was stored.
+///
+/// This is only used for migrating the stored songs.
+#[derive(Serialize, Deserialize)]
+pub struct SongV2 {
+    id: Option<Uid>,
+    title: String,
+    artist: String,
+    album: String,
+    release_date: Option<String>,
+    external_links: ExternalLinks,
+    album_art_link: Option<String>,
+    playback_link: Option<String>,
+    lyrics: Option<String>,
+    last_heard: Option<DateTime>,
+    is_newly_heard: bool,
+    original_metadata: Option<SongMetadata>,
+}
+
+impl SongV2 {
+    pub fn into_song_v3(self) -> SongV3 {
+        SongV3 {
+            id: self.id,
+            title: self.title,
+            artist: self.artist,
+            album: self.album,
+            release_date: self.release_date,
+            external_links: self.external_links,
+            album_art_link: self.album_art_link,
+            playback_link: self.playback_link,
+            lyrics: self.lyrics,
+            isrc: None,
+            musicbrainz_id: None,
+            label: None,
+            genre: None,
+            last_heard: self.last_heard,
+            is_newly_heard: self.is_newly_heard,
+            original_metadata: self.original_metadata,
+            duration: None,
+            timecode: None,
+        }
+    }
+}
+
+/// Layout of [`Song`] in the database after the extended metadata was stored.
+///
+/// This is only used for migrating the stored songs, so it must not change
+/// even when [`Song`] does.
+#[derive(Serialize, Deserialize)]
+pub struct SongV3 {
+    id: Option<Uid>,
+    title: String,
+    artist: String,
+    album: String,
+    release_date: Option<String>,
+    external_links: ExternalLinks,
+    album_art_link: Option<String>,
+    playback_link: Option<String>,
+    lyrics: Option<String>,
+    isrc: Option<String>,
+    musicbrainz_id: Option<String>,
+    label: Option<String>,
+    genre: Option<String>,
+    last_heard: Option<DateTime>,
+    is_newly_heard: bool,
+    original_metadata: Option<SongMetadata>,
+    duration: Option<Duration>,
+    timecode: Option<Duration>,
+}
+
+impl SongV3 {
+    pub const VERSION: u16 = 3;
+
+    pub fn into_song(self) -> Result<Song> {
+        let id = self.id.context("Song has no id")?;
+        let song = glib::Object::builder::<Song>()
+            .property("id", id)
+            .property("title", self.title)
+            .property("artist", self.artist)
+            .property("album", self.album)
+            .property("release-date", self.release_date)
+            .property("external-links", self.external_links)
+            .property("album-art-link", self.album_art_link)
+            .property("playback-link", self.playback_link)
+            .property("lyrics", self.lyrics)
+            .property("isrc", self.isrc)
+            .property("musicbrainz-id", self.musicbrainz_id)
+            .property("label", self.label)
+            .property("genre", self.genre)
+            .property("last-heard", self.last_heard)
+            .property("is-newly-heard", self.is_newly_heard)
+            .build();
+        let imp = song.imp();
+        imp.original_metadata.replace(self.original_metadata);
+        imp.duration.set(self.duration);
+        imp.timecode.set(self.timecode);
+        Ok(song)
     }
 }
 
@@ -183,6 +700,8 @@ impl<'de> Deserialize<'de> for Song {
 pub struct SongBuilder {
     properties: Vec<(&'static str, glib::Value)>,
     external_links: ExternalLinks,
+    duration: Option<Duration>,
+    timecode: Option<Duration>,
 }
 
 impl SongBuilder {
@@ -195,6 +714,8 @@ impl SongBuilder {
                 ("album", album.into()),
             ],
             external_links: ExternalLinks::default(),
+            duration: None,
+            timecode: None,
         }
     }
 
@@ -223,6 +744,36 @@ impl SongBuilder {
         self
     }
 
+    pub fn isrc(&mut self, value: &str) -> &mut Self {
+        self.properties.push(("isrc", value.into()));
+        self
+    }
+
+    pub fn musicbrainz_id(&mut self, value: &str) -> &mut Self {
+        self.properties.push(("musicbrainz-id", value.into()));
+        self
+    }
+
+    pub fn label(&mut self, value: &str) -> &mut Self {
+        self.properties.push(("label", value.into()));
+        self
+    }
+
+    pub fn genre(&mut self, value: &str) -> &mut Self {
+        self.properties.push(("genre", value.into()));
+        self
+    }
+
+    pub fn duration(&mut self, value: Duration) -> &mut Self {
+        self.duration = Some(value);
+        self
+    }
+
+    pub fn timecode(&mut self, value: Duration) -> &mut Self {
+        self.timecode = Some(value);
+        self
+    }
+
     /// Pushes an external link. This is not idempotent.
     pub fn external_link(&mut self, key: ExternalLinkKey, value: impl Into<String>) -> &mut Self {
         self.external_links.insert(key, value.into());
@@ -232,17 +783,25 @@ impl SongBuilder {
     pub fn build(&mut self) -> Song {
         self.properties
             .push(("external-links", self.external_links.to_value()));
-        glib::Object::with_mut_values(Song::static_type(), &mut self.properties)
-            .downcast()
-            .unwrap()
+        let song = glib::Object::with_mut_values(Song::static_type(), &mut self.properties)
+            .downcast::<Song>()
+            .unwrap();
+
+        let imp = song.imp();
+        imp.duration.set(self.duration);
+        imp.timecode.set(self.timecode);
+
+        song
     }
 }
 
 #[cfg(test)]
-mod test {
+pub mod test {
     use super::*;
 
-    use crate::external_link::ExternalLink;
+    use heed::{BytesDecode, BytesEncode};
+
+    use crate::{database::versioned::VersionedCodec, external_link::ExternalLink};
 
     #[test]
     fn id_ref() {
@@ -256,6 +815,25 @@ mod test {
         assert_eq!(&song.id(), song.id_ref());
     }
 
+    #[test]
+    fn manually_added() {
+        let song = Song::manually_added_builder("Some song", "Someone", "SomeAlbum").build();
+        assert!(song.is_manually_added());
+        assert_ne!(
+            song.id_ref(),
+            Song::manually_added_builder("Some song", "Someone", "SomeAlbum")
+                .build()
+                .id_ref()
+        );
+
+        let song = Song::builder(&Uid::from("UniqueSongId"), "Some song", "Someone", "").build();
+        assert!(!song.is_manually_added());
+
+        let song =
+            Song::builder(&Uid::from_prefixed("AudD", "a"), "Some song", "Someone", "").build();
+        assert!(!song.is_manually_added());
+    }
+
     #[test]
     fn properties() {
         let song = Song::builder(
@@ -268,6 +846,12 @@ mod test {
         .album_art_link("https://album.png")
         .playback_link("https://test.mp3")
         .lyrics("Some song lyrics")
+        .isrc("GBUM71401926")
+        .musicbrainz_id("0b3a3da0-2a3e-4d7d-a0c6-4b2d1c0a7e4f")
+        .label("Some Label")
+        .genre("Pop")
+        .duration(Duration::from_secs(237))
+        .timecode(Duration::from_secs(62))
         .newly_heard(true)
         .build();
 
@@ -278,6 +862,15 @@ mod test {
         assert_eq!(song.album_art_link().as_deref(), Some("https://album.png"));
         assert_eq!(song.playback_link().as_deref(), Some("https://test.mp3"));
         assert_eq!(song.lyrics().as_deref(), Some("Some song lyrics"));
+        assert_eq!(song.isrc().as_deref(), Some("GBUM71401926"));
+        assert_eq!(
+            song.musicbrainz_id().as_deref(),
+            Some("0b3a3da0-2a3e-4d7d-a0c6-4b2d1c0a7e4f")
+        );
+        assert_eq!(song.label().as_deref(), Some("Some Label"));
+        assert_eq!(song.genre().as_deref(), Some("Pop"));
+        assert_eq!(song.duration(), Some(Duration::from_secs(237)));
+        assert_eq!(song.timecode(), Some(Duration::from_secs(62)));
         assert!(song.is_newly_heard());
     }
 
@@ -303,8 +896,16 @@ mod test {
         assert_eq!(v1.album_art_link(), v2.album_art_link());
         assert_eq!(v1.playback_link(), v2.playback_link());
         assert_eq!(v1.lyrics(), v2.lyrics());
+        assert_eq!(v1.isrc(), v2.isrc());
+        assert_eq!(v1.musicbrainz_id(), v2.musicbrainz_id());
+        assert_eq!(v1.label(), v2.label());
+        assert_eq!(v1.genre(), v2.genre());
+        assert_eq!(v1.duration(), v2.duration());
+        assert_eq!(v1.timecode(), v2.timecode());
         assert_eq!(v1.last_heard(), v2.last_heard());
         assert_eq!(v1.is_newly_heard(), v2.is_newly_heard());
+        assert_eq!(v1.original_metadata(), v2.original_metadata());
+        assert_eq!(v1.is_edited(), v2.is_edited());
     }
 
     #[test]
@@ -326,6 +927,12 @@ mod test {
             .album_art_link("some value")
             .playback_link("some value")
             .lyrics("some value")
+            .isrc("some value")
+            .musicbrainz_id("some value")
+            .label("some value")
+            .genre("some value")
+            .duration(Duration::from_millis(237_247))
+            .timecode(Duration::from_secs(62))
             .build();
         let bytes = bincode::serialize(&val).unwrap();
         let de_val = bincode::deserialize::<Song>(&bytes).unwrap();
@@ -336,6 +943,72 @@ mod test {
         let bytes = bincode::serialize(&val).unwrap();
         let de_val = bincode::deserialize::<Song>(&bytes).unwrap();
         assert_song_eq(&val, &de_val);
+
+        let val = SongBuilder::new(&Uid::from("e"), "E Title", "E Artist", "E Album").build();
+        let mut metadata = val.metadata();
+        metadata.title = "Edited E Title".to_string();
+        val.edit(metadata);
+        let bytes = bincode::serialize(&val).unwrap();
+        let de_val = bincode::deserialize::<Song>(&bytes).unwrap();
+        assert_song_eq(&val, &de_val);
+    }
+
+    #[test]
+    fn edit_and_revert() {
+        let song = Song::builder(&Uid::from("a"), "Title", "Artist", "Album")
+            .release_date("2020")
+            .external_link(ExternalLinkKey::AudDUrl, "https://lis.tn/a")
+            .build();
+        let original_metadata = song.metadata();
+        assert!(!song.is_edited());
+        assert_eq!(song.original_metadata(), None);
+
+        let n_title_notified = Rc::new(Cell::new(0));
+        song.connect_title_notify({
+            let n_title_notified = Rc::clone(&n_title_notified);
+            move |_| n_title_notified.set(n_title_notified.get() + 1)
+        });
+
+        let mut metadata = song.metadata();
+        metadata.title = "Edited Title".to_string();
+        metadata.release_date = None;
+        metadata
+            .external_links
+            .insert("spotify-url".to_string(), "https://spotify.com".to_string());
+        song.edit(metadata.clone());
+        assert!(song.is_edited());
+        assert_eq!(song.metadata(), metadata);
+        assert_eq!(song.title(), "Edited Title");
+        assert_eq!(song.release_date(), None);
+        assert_eq!(
+            song.external_links()
+                .get(ExternalLinkKey::SpotifyUrl)
+                .as_deref(),
+            Some("https://spotify.com")
+        );
+        assert_eq!(song.original_metadata().as_ref(), Some(&original_metadata));
+        assert_eq!(n_title_notified.get(), 1);
+
+        // Original metadata is kept across multiple edits
+        metadata.artist = "Edited Artist".to_string();
+        song.edit(metadata.clone());
+        assert_eq!(song.metadata(), metadata);
+        assert_eq!(song.original_metadata().as_ref(), Some(&original_metadata));
+
+        song.revert_edits();
+        assert!(!song.is_edited());
+        assert_eq!(song.metadata(), original_metadata);
+        assert_eq!(song.original_metadata(), None);
+        assert_eq!(n_title_notified.get(), 2);
+
+        // Editing back to the original values is the same as reverting
+        let mut metadata = song.metadata();
+        metadata.album = "Edited Album".to_string();
+        song.edit(metadata);
+        assert!(song.is_edited());
+        song.edit(original_metadata.clone());
+        assert!(!song.is_edited());
+        assert_eq!(song.metadata(), original_metadata);
     }
 
     #[test]
@@ -372,4 +1045,261 @@ mod test {
         );
         assert!(song.is_newly_heard());
     }
+
+    #[test]
+    fn merge() {
+        let song = Song::builder(&Uid::from("a"), "Title", "Artist", "Album")
+            .external_link(ExternalLinkKey::AudDUrl, "https://lis.tn/a")
+            .build();
+        song.set_last_heard(DateTime::from_iso8601("2022-07-28T08:23:28+08").unwrap());
+
+        let other = Song::builder(&Uid::from("b"), "Other Title", "Artist", "")
+            .release_date("2020")
+            .lyrics("Lyrics")
+            .isrc("GBUM71401926")
+            .timecode(Duration::from_secs(62))
+            .external_link(ExternalLinkKey::AudDUrl, "https://lis.tn/b")
+            .external_link(ExternalLinkKey::SpotifyUrl, "https://spotify.com")
+            .build();
+        other.set_last_heard(DateTime::from_iso8601("2023-07-28T08:23:28+08").unwrap());
+
+        song.merge(&other);
+        assert_eq!(song.id_ref(), &Uid::from("a"));
+        assert_eq!(song.title(), "Title");
+        assert_eq!(song.album(), "Album");
+        assert_eq!(song.release_date().as_deref(), Some("2020"));
+        assert_eq!(song.lyrics().as_deref(), Some("Lyrics"));
+        assert_eq!(song.isrc().as_deref(), Some("GBUM71401926"));
+        assert_eq!(song.timecode(), Some(Duration::from_secs(62)));
+        assert_eq!(
+            song.external_links()
+                .get(ExternalLinkKey::AudDUrl)
+                .as_deref(),
+            Some("https://lis.tn/a")
+        );
+        assert_eq!(
+            song.external_links()
+                .get(ExternalLinkKey::SpotifyUrl)
+                .as_deref(),
+            Some("https://spotify.com")
+        );
+        assert_eq!(song.last_heard(), other.last_heard());
+
+        // The latest last heard is kept
+        let older = Song::builder(&Uid::from("c"), "Title", "Artist", "").build();
+        older.set_last_heard(DateTime::from_iso8601("2021-07-28T08:23:28+08").unwrap());
+        song.merge(&older);
+        assert_eq!(song.last_heard(), other.last_heard());
+    }
+
+    #[test]
+    fn song_v1_into_song() {
+        let song_v1 = SongV1 {
+            id: Some(Uid::from("a")),
+            title: "A Title".to_string(),
+            artist: "A Artist".to_string(),
+            album: "A Album".to_string(),
+            release_date: Some("2020".to_string()),
+            external_links: ExternalLinks::default(),
+            album_art_link: Some("https://album.png".to_string()),
+            playback_link: None,
+            lyrics: Some("Some lyrics".to_string()),
+            last_heard: Some(DateTime::now_utc()),
+            is_newly_heard: true,
+        };
+        song_v1
+            .external_links
+            .insert(ExternalLinkKey::AudDUrl, "https://lis.tn/a".to_string());
+
+        let bytes = bincode::serialize(&song_v1).unwrap();
+        assert!(bincode::deserialize::<Song>(&bytes).is_err());
+
+        let song = bincode::deserialize::<SongV1>(&bytes)
+            .unwrap()
+            .into_song_v2()
+            .into_song_v3()
+            .into_song()
+            .unwrap();
+        assert_eq!(song.id_ref(), &Uid::from("a"));
+        assert_eq!(song.title(), "A Title");
+        assert_eq!(song.artist(), "A Artist");
+        assert_eq!(song.album(), "A Album");
+        assert_eq!(song.release_date().as_deref(), Some("2020"));
+        assert_eq!(
+            song.external_links()
+                .get(ExternalLinkKey::AudDUrl)
+                .as_deref(),
+            Some("https://lis.tn/a")
+        );
+        assert_eq!(song.album_art_link().as_deref(), Some("https://album.png"));
+        assert_eq!(song.playback_link(), None);
+        assert_eq!(song.lyrics().as_deref(), Some("Some lyrics"));
+        assert_eq!(song.last_heard(), song_v1.last_heard);
+        assert!(song.is_newly_heard());
+        assert!(!song.is_edited());
+    }
+
+    #[test]
+    fn song_v2_into_song() {
+        let original_metadata = SongMetadata {
+            title: "Original Title".to_string(),
+            artist: "A Artist".to_string(),
+            album: "A Album".to_string(),
+            release_date: None,
+            external_links: IndexMap::new(),
+        };
+        let song_v2 = SongV2 {
+            id: Some(Uid::from("a")),
+            title: "A Title".to_string(),
+            artist: "A Artist".to_string(),
+            album: "A Album".to_string(),
+            release_date: None,
+            external_links: ExternalLinks::default(),
+            album_art_link: None,
+            playback_link: None,
+            lyrics: None,
+            last_heard: None,
+            is_newly_heard: true,
+            original_metadata: Some(original_metadata.clone()),
+        };
+
+        let bytes = bincode::serialize(&song_v2).unwrap();
+        assert!(bincode::deserialize::<Song>(&bytes).is_err());
+
+        let song = bincode::deserialize::<SongV2>(&bytes)
+            .unwrap()
+            .into_song_v3()
+            .into_song()
+            .unwrap();
+        assert_eq!(song.id_ref(), &Uid::from("a"));
+        assert_eq!(song.title(), "A Title");
+        assert!(song.is_newly_heard());
+        assert_eq!(song.original_metadata(), Some(original_metadata));
+        assert!(song.is_edited());
+        assert_eq!(song.isrc(), None);
+        assert_eq!(song.duration(), None);
+    }
+
+    #[test]
+    fn song_v3_into_song() {
+        let song = Song::builder(&Uid::from("a"), "A Title", "A Artist", "A Album")
+            .isrc("GBUM71401926")
+            .genre("Pop")
+            .duration(Duration::from_secs(237))
+            .timecode(Duration::from_secs(62))
+            .build();
+        song.set_last_heard(DateTime::now_utc());
+
+        // The current layout must still be readable as `SongV3`
+        let bytes = bincode::serialize(&song).unwrap();
+        let de_song = bincode::deserialize::<SongV3>(&bytes)
+            .unwrap()
+            .into_song()
+            .unwrap();
+        assert_song_eq(&song, &de_song);
+        assert_eq!(de_song.isrc().as_deref(), Some("GBUM71401926"));
+        assert_eq!(de_song.genre().as_deref(), Some("Pop"));
+        assert_eq!(de_song.duration(), Some(Duration::from_secs(237)));
+        assert_eq!(de_song.timecode(), Some(Duration::from_secs(62)));
+    }
+
+    #[test]
+    fn summary_decode() {
+        let song = Song::builder(&Uid::from("a"), "A Title", "A Artist", "A Album")
+            .release_date("2020")
+            .lyrics("Some lyrics")
+            .isrc("GBUM71401926")
+            .musicbrainz_id("0b3a3da0-2a3e-4d7d-a0c6-4b2d1c0a7e4f")
+            .genre("Pop")
+            .external_link(ExternalLinkKey::SpotifyUrl, "https://spotify.com/1")
+            .build();
+        song.set_last_heard(DateTime::now_utc());
+
+        // Only the prefix is decoded with the current layout
+        let bytes = VersionedCodec::<Song>::bytes_encode(&song).unwrap();
+        let summary = SongSummary::decode(&bytes).unwrap();
+        assert_eq!(summary, SongSummary::new(&song));
+        assert_eq!(summary.isrc.as_deref(), Some("GBUM71401926"));
+
+        // Older layouts are decoded fully
+        let bytes: &[u8] = include_bytes!("database/fixtures/song_v1.bin");
+        let song = VersionedCodec::<Song>::bytes_decode(bytes).unwrap();
+        assert_eq!(SongSummary::decode(bytes).unwrap(), SongSummary::new(&song));
+    }
+
+    /// Returns a song whose id, title, artist, and album are all `id`.
+    pub fn new_test_song(id: &str) -> Song {
+        Song::builder(&Uid::from(id), id, id, id).build()
+    }
+
+    /// Asserts that the song has the values stored in the fixtures of the
+    /// given version.
+    pub fn assert_fixture_song(song: &Song, version: u16) {
+        assert_eq!(song.id_ref(), &Uid::from("AudD-fixture"));
+        assert_eq!(song.title(), "Title");
+        assert_eq!(song.artist(), "Artist");
+        assert_eq!(song.album(), "Album");
+        assert_eq!(song.release_date().as_deref(), Some("2020"));
+        assert_eq!(
+            song.external_links()
+                .get(ExternalLinkKey::AudDUrl)
+                .as_deref(),
+            Some("https://lis.tn/a")
+        );
+        assert_eq!(song.album_art_link().as_deref(), Some("https://album.png"));
+        assert_eq!(song.playback_link().as_deref(), Some("https://test.mp3"));
+        assert_eq!(song.lyrics().as_deref(), Some("Lyrics"));
+        assert_eq!(
+            song.last_heard(),
+            Some(DateTime::from_iso8601("2024-01-02T03:04:05Z").unwrap())
+        );
+        assert!(song.is_newly_heard());
+
+        if version >= 2 {
+            assert_eq!(
+                song.original_metadata().map(|metadata| metadata.title),
+                Some("Original Title".to_string())
+            );
+        } else {
+            assert_eq!(song.original_metadata(), None);
+        }
+
+        if version >= 3 {
+            assert_eq!(song.isrc().as_deref(), Some("GBUM71401926"));
+            assert_eq!(
+                song.musicbrainz_id().as_deref(),
+                Some("0b3a3da0-2a3e-4d7d-a0c6-4b2d1c0a7e4f")
+            );
+            assert_eq!(song.label().as_deref(), Some("Label"));
+            assert_eq!(song.genre().as_deref(), Some("Pop"));
+            assert_eq!(song.duration(), Some(Duration::from_secs(237)));
+            assert_eq!(song.timecode(), Some(Duration::from_secs(62)));
+        } else {
+            assert_eq!(song.isrc(), None);
+            assert_eq!(song.duration(), None);
+        }
+    }
+
+    #[test]
+    fn versioned_fixtures() {
+        let fixtures: [(u16, &[u8]); 3] = [
+            (1, include_bytes!("database/fixtures/song_v1.bin")),
+            (2, include_bytes!("database/fixtures/song_v2.bin")),
+            (3, include_bytes!("database/fixtures/song_v3.bin")),
+        ];
+        assert_eq!(fixtures.last().unwrap().0, Song::VERSION);
+
+        for (version, bytes) in fixtures {
+            let song = VersionedCodec::<Song>::bytes_decode(bytes).unwrap();
+            assert_fixture_song(&song, version);
+
+            let new_bytes = VersionedCodec::<Song>::bytes_encode(&song).unwrap();
+            let de_song = VersionedCodec::<Song>::bytes_decode(&new_bytes).unwrap();
+            assert_song_eq(&song, &de_song);
+
+            if version == Song::VERSION {
+                assert_eq!(new_bytes, bytes);
+            }
+        }
+    }
 }
@@ -0,0 +1,265 @@
+use anyhow::{Context, Result};
+use gtk::glib;
+
+use std::borrow::Cow;
+
+use crate::{
+    database::{EnvExt, SONG_CLIPS_DB_NAME},
+    uid::{Uid, UidCodec},
+};
+
+type SongClipDatabase = heed::Database<UidCodec, SongClipCodec>;
+
+/// Encodes a clip as its saved time in microseconds, in big-endian, followed by
+/// the clip's bytes.
+///
+/// This makes it cheap to read the saved times only when pruning.
+pub struct SongClipCodec;
+
+impl<'a> heed::BytesEncode<'a> for SongClipCodec {
+    type EItem = (i64, &'a [u8]);
+
+    fn bytes_encode(
+        (saved_time, bytes): &'a Self::EItem,
+    ) -> Result<Cow<'a, [u8]>, heed::BoxedError> {
+        let mut ret = Vec::with_capacity(8 + bytes.len());
+        ret.extend_from_slice(&saved_time.to_be_bytes());
+        ret.extend_from_slice(bytes);
+        Ok(Cow::Owned(ret))
+    }
+}
+
+impl<'a> heed::BytesDecode<'a> for SongClipCodec {
+    type DItem = (i64, &'a [u8]);
+
+    fn bytes_decode(bytes: &'a [u8]) -> Result<Self::DItem, heed::BoxedError> {
+        let (saved_time, clip) = bytes
+            .split_first_chunk::<8>()
+            .ok_or("clip is shorter than the saved time")?;
+        Ok((i64::from_be_bytes(*saved_time), clip))
+    }
+}
+
+/// Audio recorded when recognizing songs, stored by song id.
+#[derive(Clone)]
+pub struct SongClips {
+    env: heed::Env,
+    db: SongClipDatabase,
+}
+
+impl SongClips {
+    /// Load from the `song_clips` table in the database
+    pub fn load_from_env(env: heed::Env) -> Result<Self> {
+        let db = env.with_write_txn(|wtxn| {
+            env.create_database(wtxn, Some(SONG_CLIPS_DB_NAME))
+                .context("Failed to create song clips db")
+        })?;
+
+        Ok(Self { env, db })
+    }
+
+    pub fn get(&self, song_id: &Uid) -> Result<Option<glib::Bytes>> {
+        let rtxn = self.env.read_txn().context("Failed to create read txn")?;
+        let clip = self
+            .db
+            .get(&rtxn, song_id)
+            .context("Failed to get clip from db")?
+            .map(|(_, bytes)| glib::Bytes::from(bytes));
+        Ok(clip)
+    }
+
+    pub fn contains(&self, song_id: &Uid) -> Result<bool> {
+        let rtxn = self.env.read_txn().context("Failed to create read txn")?;
+        let contains = self
+            .db
+            .remap_data_type::<heed::types::DecodeIgnore>()
+            .get(&rtxn, song_id)
+            .context("Failed to get clip from db")?
+            .is_some();
+        Ok(contains)
+    }
+
+    /// Returns the ids of the songs that have a clip.
+    pub fn ids(&self) -> Result<Vec<Uid>> {
+        let rtxn = self.env.read_txn().context("Failed to create read txn")?;
+        let ids = self
+            .db
+            .remap_data_type::<heed::types::DecodeIgnore>()
+            .iter(&rtxn)
+            .context("Failed to iter clips from db")?
+            .map(|item| item.map(|(song_id, _)| song_id))
+            .collect::<Result<Vec<_>, _>>()
+            .context("Failed to collect clips from db")?;
+        Ok(ids)
+    }
+
+    /// Stores the clip for the song, replacing its existing clip, then removes
+    /// the oldest clips so that at most `limit` clips are kept.
+    pub fn insert(&self, song_id: &Uid, bytes: &[u8], limit: u32) -> Result<()> {
+        self.env.with_write_txn(|wtxn| {
+            self.db
+                .put(wtxn, song_id, &(glib::real_time(), bytes))
+                .context("Failed to put clip to db")?;
+
+            let n_clips = self.db.len(wtxn).context("Failed to get db len")?;
+            let n_excess = n_clips.saturating_sub(limit as u64) as usize;
+
+            if n_excess == 0 {
+                return Ok(());
+            }
+
+            let mut saved_times = self
+                .db
+                .iter(wtxn)
+                .context("Failed to iter clips from db")?
+                .map(|item| item.map(|(song_id, (saved_time, _))| (saved_time, song_id)))
+                .collect::<Result<Vec<_>, _>>()
+                .context("Failed to collect clips from db")?;
+            saved_times.sort_unstable_by_key(|(saved_time, _)| *saved_time);
+
+            for (_, song_id) in saved_times.into_iter().take(n_excess) {
+                self.db
+                    .delete(wtxn, &song_id)
+                    .context("Failed to delete clip from db")?;
+            }
+
+            tracing::debug!("Pruned {} old clips", n_excess);
+
+            Ok(())
+        })
+    }
+
+    /// Returns whether the song had a clip.
+    pub fn remove(&self, song_id: &Uid) -> Result<bool> {
+        self.env.with_write_txn(|wtxn| {
+            self.db
+                .delete(wtxn, song_id)
+                .context("Failed to delete clip from db")
+        })
+    }
+
+    /// Moves the clip of `from_song_id` to `to_song_id`, unless the latter
+    /// already has one, in which case the clip of `from_song_id` is dropped.
+    pub fn transfer(&self, from_song_id: &Uid, to_song_id: &Uid) -> Result<()> {
+        self.env.with_write_txn(|wtxn| {
+            let Some((saved_time, bytes)) = self
+                .db
+                .get(wtxn, from_song_id)
+                .context("Failed to get clip from db")?
+                .map(|(saved_time, bytes)| (saved_time, bytes.to_vec()))
+            else {
+                return Ok(());
+            };
+
+            if self
+                .db
+                .get(wtxn, to_song_id)
+                .context("Failed to get clip from db")?
+                .is_none()
+            {
+                self.db
+                    .put(wtxn, to_song_id, &(saved_time, bytes.as_slice()))
+                    .context("Failed to put clip to db")?;
+            }
+
+            self.db
+                .delete(wtxn, from_song_id)
+                .context("Failed to delete clip from db")?;
+
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::database;
+
+    fn n_clips(clips: &SongClips) -> u64 {
+        let rtxn = clips.env.read_txn().unwrap();
+        clips.db.len(&rtxn).unwrap()
+    }
+
+    #[test]
+    fn insert_and_get() {
+        let (env, _tempdir) = database::new_test_env();
+        let clips = SongClips::load_from_env(env).unwrap();
+
+        assert!(clips.get(&Uid::from("a")).unwrap().is_none());
+        assert!(!clips.contains(&Uid::from("a")).unwrap());
+
+        clips.insert(&Uid::from("a"), b"A", 10).unwrap();
+        clips.insert(&Uid::from("b"), b"", 10).unwrap();
+        assert_eq!(n_clips(&clips), 2);
+
+        assert_eq!(clips.get(&Uid::from("a")).unwrap().unwrap().as_ref(), b"A");
+        assert!(clips.contains(&Uid::from("a")).unwrap());
+        assert_eq!(clips.get(&Uid::from("b")).unwrap().unwrap().as_ref(), b"");
+        assert!(clips.contains(&Uid::from("b")).unwrap());
+
+        clips.insert(&Uid::from("a"), b"A2", 10).unwrap();
+        assert_eq!(n_clips(&clips), 2);
+        assert_eq!(clips.get(&Uid::from("a")).unwrap().unwrap().as_ref(), b"A2");
+    }
+
+    #[test]
+    fn insert_prune() {
+        let (env, _tempdir) = database::new_test_env();
+        let clips = SongClips::load_from_env(env).unwrap();
+
+        clips.insert(&Uid::from("a"), b"A", 2).unwrap();
+        clips.insert(&Uid::from("b"), b"B", 2).unwrap();
+        assert_eq!(n_clips(&clips), 2);
+
+        clips.insert(&Uid::from("c"), b"C", 2).unwrap();
+        assert_eq!(n_clips(&clips), 2);
+        assert!(!clips.contains(&Uid::from("a")).unwrap());
+        assert!(clips.contains(&Uid::from("b")).unwrap());
+        assert!(clips.contains(&Uid::from("c")).unwrap());
+
+        // Replacing refreshes the saved time
+        clips.insert(&Uid::from("b"), b"B2", 2).unwrap();
+        clips.insert(&Uid::from("d"), b"D", 2).unwrap();
+        assert!(clips.contains(&Uid::from("b")).unwrap());
+        assert!(!clips.contains(&Uid::from("c")).unwrap());
+        assert!(clips.contains(&Uid::from("d")).unwrap());
+
+        clips.insert(&Uid::from("e"), b"E", 0).unwrap();
+        assert_eq!(n_clips(&clips), 0);
+    }
+
+    #[test]
+    fn remove() {
+        let (env, _tempdir) = database::new_test_env();
+        let clips = SongClips::load_from_env(env).unwrap();
+
+        clips.insert(&Uid::from("a"), b"A", 10).unwrap();
+        assert!(clips.remove(&Uid::from("a")).unwrap());
+        assert!(!clips.remove(&Uid::from("a")).unwrap());
+        assert!(clips.get(&Uid::from("a")).unwrap().is_none());
+        assert_eq!(n_clips(&clips), 0);
+    }
+
+    #[test]
+    fn transfer() {
+        let (env, _tempdir) = database::new_test_env();
+        let clips = SongClips::load_from_env(env).unwrap();
+
+        clips.insert(&Uid::from("a"), b"A", 10).unwrap();
+        clips.transfer(&Uid::from("a"), &Uid::from("b")).unwrap();
+        assert!(!clips.contains(&Uid::from("a")).unwrap());
+        assert_eq!(clips.get(&Uid::from("b")).unwrap().unwrap().as_ref(), b"A");
+
+        // The existing clip is kept
+        clips.insert(&Uid::from("c"), b"C", 10).unwrap();
+        clips.transfer(&Uid::from("c"), &Uid::from("b")).unwrap();
+        assert!(!clips.contains(&Uid::from("c")).unwrap());
+        assert_eq!(clips.get(&Uid::from("b")).unwrap().unwrap().as_ref(), b"A");
+
+        // Nothing to transfer
+        clips.transfer(&Uid::from("d"), &Uid::from("b")).unwrap();
+        assert_eq!(n_clips(&clips), 1);
+    }
+}
@@ -0,0 +1,334 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+};
+
+use crate::{external_links::ExternalLinkKey, song::SongSummary, uid::Uid};
+
+/// Keys of external links that point to one specific track, so songs sharing
+/// a value for one of them are the same track.
+const TRACK_LINK_KEYS: [ExternalLinkKey; 2] =
+    [ExternalLinkKey::AppleMusicUrl, ExternalLinkKey::SpotifyUrl];
+
+/// Words after which the rest of an artist or title only lists featured artists
+const FEATURING_WORDS: [&str; 4] = ["feat.", "feat", "ft.", "featuring"];
+
+/// Returns groups of ids of songs that are likely the same track, even if they
+/// were recognized through different providers or with different ids.
+///
+/// This only needs the summaries of the songs, so it can be done off the main
+/// thread.
+///
+/// Songs are considered the same track if they share a track link, an ISRC, or
+/// a MusicBrainz id, or if their normalized artist and title are equal.
+///
+/// Each group has at least two songs, ordered from the most recently heard, and
+/// the groups are ordered by their most recently heard song.
+pub fn find_duplicates(songs: &[SongSummary]) -> Vec<Vec<Uid>> {
+    let mut parents = (0..songs.len()).collect::<Vec<_>>();
+    let mut first_index_by_key = HashMap::new();
+
+    for (index, song) in songs.iter().enumerate() {
+        for key in keys(song) {
+            if let Some(&first_index) = first_index_by_key.get(&key) {
+                union(&mut parents, first_index, index);
+            } else {
+                first_index_by_key.insert(key, index);
+            }
+        }
+    }
+
+    // Ordered, so groups heard at the same time keep the order of `songs`
+    let mut groups = BTreeMap::<usize, Vec<&SongSummary>>::new();
+    for (index, song) in songs.iter().enumerate() {
+        let root = find(&mut parents, index);
+        groups.entry(root).or_default().push(song);
+    }
+
+    let mut groups = groups
+        .into_values()
+        .filter(|group| group.len() > 1)
+        .collect::<Vec<_>>();
+    for group in &mut groups {
+        group.sort_by_key(|song| Reverse(song.last_heard));
+    }
+    groups.sort_by_key(|group| Reverse(group[0].last_heard));
+
+    groups
+        .into_iter()
+        .map(|group| group.into_iter().map(|song| song.id.clone()).collect())
+        .collect()
+}
+
+/// Keys that are equal for songs of the same track
+fn keys(song: &SongSummary) -> Vec<String> {
+    let mut keys = TRACK_LINK_KEYS
+        .iter()
+        .filter_map(|&key| {
+            let value = song.external_links.get(key.as_ref())?;
+            Some(format!("{}:{}", key.as_ref(), value))
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(isrc) = song.isrc.as_ref().filter(|isrc| !isrc.is_empty()) {
+        keys.push(format!("isrc:{}", isrc.to_uppercase()));
+    }
+
+    if let Some(musicbrainz_id) = song.musicbrainz_id.as_ref().filter(|id| !id.is_empty()) {
+        keys.push(format!("musicbrainz-id:{}", musicbrainz_id));
+    }
+
+    let artist = normalize_artist(&song.artist);
+    let title = normalize_title(&song.title);
+    if !artist.is_empty() && !title.is_empty() {
+        keys.push(format!("artist-title:{}\n{}", artist, title));
+    }
+
+    keys
+}
+
+fn find(parents: &mut [usize], index: usize) -> usize {
+    let mut root = index;
+    while parents[root] != root {
+        root = parents[root];
+    }
+
+    // Point everything on the path directly to the root to keep lookups short
+    let mut index = index;
+    while parents[index] != root {
+        let next = parents[index];
+        parents[index] = root;
+        index = next;
+    }
+
+    root
+}
+
+fn union(parents: &mut [usize], a: usize, b: usize) {
+    let root_a = find(parents, a);
+    let root_b = find(parents, b);
+    if root_a != root_b {
+        parents[root_b] = root_a;
+    }
+}
+
+fn normalize_artist(artist: &str) -> String {
+    normalize(strip_featuring(&artist.to_lowercase()))
+}
+
+/// Removes the parts of the title that commonly differ between providers, such
+/// as bracketed remarks, "- Remastered" suffixes, and featured artists.
+fn normalize_title(title: &str) -> String {
+    let title = title.to_lowercase();
+
+    let mut without_brackets = String::with_capacity(title.len());
+    let mut depth = 0_u32;
+    for c in title.chars() {
+        match c {
+            '(' | '[' => depth += 1,
+            ')' | ']' => depth = depth.saturating_sub(1),
+            _ if depth == 0 => without_brackets.push(c),
+            _ => {}
+        }
+    }
+
+    let without_suffix = without_brackets
+        .split_once(" - ")
+        .map_or(without_brackets.as_str(), |(title, _)| title);
+
+    normalize(strip_featuring(without_suffix))
+}
+
+fn strip_featuring(text: &str) -> &str {
+    let mut end = text.len();
+
+    for word in FEATURING_WORDS {
+        let mut start = 0;
+        while let Some(position) = text[start..].find(word) {
+            let position = start + position;
+            let after = position + word.len();
+
+            let is_word_start = text[..position]
+                .chars()
+                .next_back()
+                .is_some_and(|c| c.is_whitespace());
+            let is_word_end = text[after..]
+                .chars()
+                .next()
+                .is_none_or(|c| c.is_whitespace());
+
+            if is_word_start && is_word_end {
+                end = end.min(position);
+                break;
+            }
+
+            start = after;
+        }
+    }
+
+    &text[..end]
+}
+
+/// Lowercases, replaces "&" with "and", and drops punctuation and repeated
+/// whitespace.
+fn normalize(text: &str) -> String {
+    text.to_lowercase()
+        .replace('&', " and ")
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::{date_time::DateTime, song::Song};
+
+    fn new_test_song(id: &str, title: &str, artist: &str) -> Song {
+        Song::builder(&Uid::from(id), title, artist, "").build()
+    }
+
+    fn find_duplicates_of(songs: &[Song]) -> Vec<Vec<Uid>> {
+        let summaries = songs.iter().map(SongSummary::new).collect::<Vec<_>>();
+        find_duplicates(&summaries)
+    }
+
+    #[test]
+    fn normalize_title_variants() {
+        assert_eq!(normalize_title("Bohemian Rhapsody"), "bohemian rhapsody");
+        assert_eq!(
+            normalize_title("Bohemian Rhapsody - Remastered 2011"),
+            "bohemian rhapsody"
+        );
+        assert_eq!(
+            normalize_title("Bohemian Rhapsody (Live) [Remastered]"),
+            "bohemian rhapsody"
+        );
+        assert_eq!(normalize_title("Stay feat. Someone"), "stay");
+        assert_eq!(normalize_title("Left & Right!"), "left and right");
+        assert_eq!(normalize_title("Featherweight"), "featherweight");
+    }
+
+    #[test]
+    fn normalize_artist_variants() {
+        assert_eq!(normalize_artist("Queen"), "queen");
+        assert_eq!(
+            normalize_artist("The Kid LAROI ft. Justin"),
+            "the kid laroi"
+        );
+        assert_eq!(normalize_artist("Simon & Garfunkel"), "simon and garfunkel");
+        assert_eq!(normalize_artist("Daft Punk featuring X"), "daft punk");
+    }
+
+    #[test]
+    fn find_duplicates_by_artist_title() {
+        let songs = [
+            new_test_song("AudD-a", "Bohemian Rhapsody", "Queen"),
+            new_test_song("AudD-b", "Other", "Someone"),
+            new_test_song("Other-c", "Bohemian Rhapsody - Remastered 2011", "QUEEN"),
+        ];
+
+        assert_eq!(
+            find_duplicates_of(&songs),
+            [[Uid::from("AudD-a"), Uid::from("Other-c")]]
+        );
+    }
+
+    #[test]
+    fn find_duplicates_by_track_link() {
+        let a = Song::builder(&Uid::from("a"), "Song", "Artist", "")
+            .external_link(ExternalLinkKey::SpotifyUrl, "https://spotify.com/1")
+            .build();
+        let b = Song::builder(
+            &Uid::from("b"),
+            "Song (Japanese Version)",
+            "アーティスト",
+            "",
+        )
+        .external_link(ExternalLinkKey::SpotifyUrl, "https://spotify.com/1")
+        .build();
+        let c = Song::builder(&Uid::from("c"), "Song", "Another Artist", "")
+            .external_link(ExternalLinkKey::SpotifyUrl, "https://spotify.com/2")
+            .build();
+
+        assert_eq!(
+            find_duplicates_of(&[a, b, c]),
+            [[Uid::from("a"), Uid::from("b")]]
+        );
+    }
+
+    #[test]
+    fn find_duplicates_by_identifier() {
+        let a = Song::builder(&Uid::from("a"), "Song", "Artist", "")
+            .isrc("usum71703861")
+            .build();
+        let b = Song::builder(&Uid::from("b"), "Canción", "Artista", "")
+            .isrc("USUM71703861")
+            .build();
+        let c = Song::builder(&Uid::from("c"), "Song", "Another Artist", "")
+            .musicbrainz_id("b1a9c0e9-d987-4042-ae91-78d6a3267d69")
+            .build();
+        let d = Song::builder(&Uid::from("d"), "Other Song", "Someone", "")
+            .musicbrainz_id("b1a9c0e9-d987-4042-ae91-78d6a3267d69")
+            .build();
+
+        assert_eq!(
+            find_duplicates_of(&[a, b, c, d]),
+            [
+                [Uid::from("a"), Uid::from("b")],
+                [Uid::from("c"), Uid::from("d")]
+            ]
+        );
+    }
+
+    #[test]
+    fn find_duplicates_transitive() {
+        // `a` and `b` share a link, while `b` and `c` share an artist and title
+        let a = Song::builder(&Uid::from("a"), "Translated", "Artist", "")
+            .external_link(ExternalLinkKey::AppleMusicUrl, "https://music.apple.com/1")
+            .build();
+        let b = Song::builder(&Uid::from("b"), "Song", "Artist", "")
+            .external_link(ExternalLinkKey::AppleMusicUrl, "https://music.apple.com/1")
+            .build();
+        let c = new_test_song("c", "Song", "Artist");
+
+        let groups = find_duplicates_of(&[a, b, c]);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].len(), 3);
+    }
+
+    #[test]
+    fn find_duplicates_order() {
+        let songs = [
+            new_test_song("a", "Song", "Artist"),
+            new_test_song("b", "Song", "Artist"),
+            new_test_song("c", "Other", "Artist"),
+            new_test_song("d", "Other", "Artist"),
+        ];
+        songs[0].set_last_heard(DateTime::from_iso8601("2021-01-01T00:00:00Z").unwrap());
+        songs[1].set_last_heard(DateTime::from_iso8601("2022-01-01T00:00:00Z").unwrap());
+        songs[2].set_last_heard(DateTime::from_iso8601("2023-01-01T00:00:00Z").unwrap());
+
+        assert_eq!(
+            find_duplicates_of(&songs),
+            [
+                [Uid::from("c"), Uid::from("d")],
+                [Uid::from("b"), Uid::from("a")],
+            ]
+        );
+    }
+
+    #[test]
+    fn find_duplicates_none() {
+        let songs = [
+            new_test_song("a", "Song", "Artist"),
+            new_test_song("b", "Song", "Another Artist"),
+            new_test_song("c", "", ""),
+            new_test_song("d", "", ""),
+        ];
+
+        assert!(find_duplicates_of(&songs).is_empty());
+    }
+}
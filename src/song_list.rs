@@ -1,38 +1,88 @@
-use anyhow::{Context, Result};
+use anyhow::{Context, Result, ensure};
+use gettextrs::gettext;
 use gtk::{
     gio,
-    glib::{self, clone},
+    glib::{self, clone, closure_local},
     prelude::*,
     subclass::prelude::*,
 };
-use heed::types::SerdeBincode;
-use indexmap::IndexMap;
+use heed::{BytesDecode, types::Bytes};
+use indexmap::{IndexMap, map::Entry};
 
 use std::{
-    cell::{OnceCell, RefCell},
+    cell::{Cell, OnceCell, RefCell},
     collections::{BTreeSet, HashMap, HashSet},
-    time::Instant,
+    time::{Duration, Instant},
 };
 
 use crate::{
-    database::{EnvExt, SONG_LIST_DB_NAME},
-    song::Song,
+    database::{self, EnvExt, SONG_LIST_DB_NAME, quarantine, versioned::VersionedCodec},
+    recent_songs::RecentSongs,
+    song::{Song, SongSummary},
     uid::{Uid, UidCodec},
     utils,
 };
 
 const SONG_NOTIFY_HANDLER_ID_KEY: &str = "mousai-song-notify-handler-id";
 
-type SongDatabase = heed::Database<UidCodec, SerdeBincode<Song>>;
+/// How long changes to songs are queued before being saved to the db, so that
+/// changes to many songs at once are saved in a single txn
+const FLUSH_DELAY: Duration = Duration::from_millis(500);
+
+/// How many songs are appended at once while loading, before letting the main
+/// loop handle other events
+const LOAD_CHUNK_SIZE: usize = 500;
+
+type SongDatabase = heed::Database<UidCodec, VersionedCodec<Song>>;
+
+/// A song in the list, which is only decoded from the db when accessed
+#[derive(Default)]
+struct SongRecord {
+    /// When the song was last heard, which the songs are sorted by in
+    /// [`RecentSongs`]
+    last_heard: Option<i64>,
+    /// The song, if it was accessed and is still alive
+    song: Option<glib::WeakRef<Song>>,
+}
+
+impl SongRecord {
+    fn new(song: &Song) -> Self {
+        Self {
+            last_heard: last_heard_key(song),
+            song: Some(song.downgrade()),
+        }
+    }
+
+    fn song(&self) -> Option<Song> {
+        self.song.as_ref().and_then(|song| song.upgrade())
+    }
+}
 
 mod imp {
     use super::*;
+    use glib::subclass::Signal;
+    use once_cell::sync::Lazy;
 
-    #[derive(Default)]
+    #[derive(Default, glib::Properties)]
+    #[properties(wrapper_type = super::SongList)]
     pub struct SongList {
-        pub(super) list: RefCell<IndexMap<Uid, Song>>,
+        /// Whether the songs are still being loaded from the db
+        #[property(get)]
+        pub(super) is_loading: Cell<bool>,
+
+        pub(super) list: RefCell<IndexMap<Uid, SongRecord>>,
+        pub(super) recent_songs: OnceCell<RecentSongs>,
 
         pub(super) db: OnceCell<(heed::Env, SongDatabase)>,
+
+        /// Songs whose changes are not saved to the db yet, which are kept
+        /// alive until they are
+        pub(super) dirty_songs: RefCell<HashMap<Uid, Song>>,
+        pub(super) flush_source_id: RefCell<Option<glib::SourceId>>,
+
+        /// Ids of the songs that were inserted or removed while loading, whose
+        /// entries read before loading are outdated
+        pub(super) changed_while_loading_ids: RefCell<HashSet<Uid>>,
     }
 
     #[glib::object_subclass]
@@ -42,7 +92,32 @@ mod imp {
         type Interfaces = (gio::ListModel,);
     }
 
-    impl ObjectImpl for SongList {}
+    #[glib::derived_properties]
+    impl ObjectImpl for SongList {
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    Signal::builder("flush-failed").build(),
+                    Signal::builder("read-failed").build(),
+                ]
+            });
+
+            SIGNALS.as_ref()
+        }
+
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let obj = self.obj();
+            self.recent_songs.set(RecentSongs::new(&obj)).unwrap();
+        }
+
+        fn dispose(&self) {
+            if let Some(source_id) = self.flush_source_id.take() {
+                source_id.remove();
+            }
+        }
+    }
 
     impl ListModelImpl for SongList {
         fn item_type(&self) -> glib::Type {
@@ -54,60 +129,217 @@ mod imp {
         }
 
         fn item(&self, position: u32) -> Option<glib::Object> {
-            self.list
+            let song_id = self
+                .list
                 .borrow()
                 .get_index(position as usize)
-                .map(|(_, v)| v.upcast_ref::<glib::Object>())
-                .cloned()
+                .map(|(song_id, _)| song_id.clone())?;
+            self.obj()
+                .get_or_placeholder(&song_id)
+                .map(|song| song.upcast())
         }
     }
 }
 
 glib::wrapper! {
+    /// Songs in the `songs` table in the database, in the order they were
+    /// added
+    ///
+    /// Only a lightweight record is kept for each song, and the songs are only
+    /// decoded from the db when accessed, then kept while they are alive
+    /// elsewhere or have unsaved changes.
     pub struct SongList(ObjectSubclass<imp::SongList>)
         @implements gio::ListModel;
 }
 
 impl SongList {
+    /// Emitted when the queued changes to songs fail to be saved to the db.
+    ///
+    /// The changes are kept queued, so they are retried on the next flush.
+    pub fn connect_flush_failed<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self) + 'static,
+    {
+        self.connect_closure(
+            "flush-failed",
+            false,
+            closure_local!(|obj: &Self| {
+                f(obj);
+            }),
+        )
+    }
+
+    fn emit_flush_failed(&self) {
+        self.emit_by_name::<()>("flush-failed", &[]);
+    }
+
+    /// Emitted when a song fails to be read from the db while being accessed
+    /// as an item, which is shown as a placeholder instead.
+    pub fn connect_read_failed<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self) + 'static,
+    {
+        self.connect_closure(
+            "read-failed",
+            false,
+            closure_local!(|obj: &Self| {
+                f(obj);
+            }),
+        )
+    }
+
+    fn emit_read_failed(&self) {
+        self.emit_by_name::<()>("read-failed", &[]);
+    }
+
     /// Load from the `songs` table in the database
     pub fn load_from_env(env: heed::Env) -> Result<Self> {
         let db_load_start_time = Instant::now();
 
-        let (db, songs) = env.with_write_txn(|wtxn| {
-            let db: SongDatabase = env
-                .create_database(wtxn, Some(SONG_LIST_DB_NAME))
-                .context("Failed to create songs db")?;
-            let songs = db
-                .iter(wtxn)
-                .context("Failed to iter songs from db")?
-                .collect::<Result<IndexMap<_, _>, _>>()
-                .context("Failed to collect songs from db")?;
-            Ok((db, songs))
-        })?;
+        let entries = SongListEntries::read(&env)?;
+        let this = Self::new_loading(env)?;
+        this.append_records(&entries.songs);
+        this.finish_loading(entries.invalid_entries)?;
 
         tracing::debug!(
             "Loaded {} songs in {:?}",
-            songs.len(),
+            this.n_items(),
             db_load_start_time.elapsed()
         );
-        for (id, song) in &songs {
-            debug_assert_eq!(id, song.id_ref(), "id must be equal to song.id()");
-        }
 
-        let this = glib::Object::new::<Self>();
+        Ok(this)
+    }
 
-        for (_, song) in &songs {
-            this.bind_song_to_db(song);
-        }
+    /// Creates an empty list for the `songs` table in the database, which is
+    /// filled with [`SongList::load`].
+    ///
+    /// Songs can already be inserted and removed while loading.
+    pub fn new_loading(env: heed::Env) -> Result<Self> {
+        let db = env.with_write_txn(|wtxn| {
+            let db: SongDatabase = env
+                .create_database(wtxn, Some(SONG_LIST_DB_NAME))
+                .context("Failed to create songs db")?;
+            Ok(db)
+        })?;
+
+        let this = glib::Object::new::<Self>();
 
         let imp = this.imp();
-        imp.list.replace(songs);
+        imp.is_loading.set(true);
         imp.db.set((env, db)).unwrap();
 
+        Ok(this)
+    }
+
+    /// Appends the songs read with [`SongListEntries::read`] to the list, a
+    /// chunk at a time, so the main loop is not blocked while loading a lot of
+    /// songs.
+    ///
+    /// Entries that can't be decoded are moved to the quarantine db.
+    pub async fn load(&self, entries: SongListEntries) -> Result<()> {
+        debug_assert!(self.is_loading(), "list must be created with `new_loading`");
+
+        for chunk in entries.songs.chunks(LOAD_CHUNK_SIZE) {
+            self.append_records(chunk);
+
+            // Let the main loop handle pending events and redraw before
+            // appending the next chunk
+            glib::timeout_future_with_priority(glib::Priority::DEFAULT_IDLE, Duration::ZERO).await;
+        }
+
+        self.finish_loading(entries.invalid_entries)
+    }
+
+    /// Only the records of the songs are appended, so the songs are created
+    /// only when accessed.
+    fn append_records(&self, songs: &[(Uid, Option<i64>)]) {
+        let imp = self.imp();
+
+        let mut appended = Vec::new();
+        let index_of_first_append = self.n_items();
+
+        {
+            let mut list = imp.list.borrow_mut();
+
+            for (song_id, last_heard) in songs {
+                if self.is_changed_while_loading(song_id) {
+                    continue;
+                }
+
+                if let Entry::Vacant(entry) = list.entry(song_id.clone()) {
+                    appended.push((song_id.clone(), *last_heard));
+                    entry.insert(SongRecord {
+                        last_heard: *last_heard,
+                        song: None,
+                    });
+                }
+            }
+        }
+
+        let n_appended = self.n_items() - index_of_first_append;
+        if n_appended != 0 {
+            self.items_changed(index_of_first_append, 0, n_appended);
+        }
+
+        self.recent_songs().insert_many(appended);
+    }
+
+    fn finish_loading(&self, invalid_entries: Vec<(Vec<u8>, String)>) -> Result<()> {
+        let imp = self.imp();
+
+        // Entries that were replaced while loading are not invalid anymore
+        let invalid_entries = invalid_entries
+            .into_iter()
+            .filter(|(key, _)| {
+                !UidCodec::bytes_decode(key).is_ok_and(|id| self.is_changed_while_loading(&id))
+            })
+            .collect::<Vec<_>>();
+
+        if !invalid_entries.is_empty() {
+            let (env, _) = self.db();
+            env.with_write_txn(|wtxn| {
+                for (key, reason) in &invalid_entries {
+                    quarantine::move_to_quarantine(
+                        env,
+                        wtxn,
+                        SONG_LIST_DB_NAME,
+                        key,
+                        reason.clone(),
+                    )?;
+                }
+                Ok(())
+            })
+            .context("Failed to quarantine songs")?;
+        }
+
+        imp.changed_while_loading_ids.borrow_mut().clear();
+        imp.is_loading.set(false);
+        self.notify_is_loading();
+
         // TODO Remove in future releases
-        migrate_from_memory_list(&this).context("Failed to migrate from memory list")?;
+        migrate_from_memory_list(self).context("Failed to migrate from memory list")?;
 
-        Ok(this)
+        Ok(())
+    }
+
+    fn is_changed_while_loading(&self, song_id: &Uid) -> bool {
+        self.imp()
+            .changed_while_loading_ids
+            .borrow()
+            .contains(song_id)
+    }
+
+    /// Marks the songs as changed if the list is still loading, so their
+    /// outdated entries are not loaded.
+    fn mark_changed_while_loading<'a>(&self, song_ids: impl IntoIterator<Item = &'a Uid>) {
+        if !self.is_loading() {
+            return;
+        }
+
+        self.imp()
+            .changed_while_loading_ids
+            .borrow_mut()
+            .extend(song_ids.into_iter().cloned());
     }
 
     /// If an equivalent song already exists in the list, it returns false and updates
@@ -122,16 +354,25 @@ impl SongList {
                 .context("Failed to put song to db")?;
             Ok(())
         })?;
-
-        self.bind_song_to_db(&song);
-        let (position, prev_value) = self.imp().list.borrow_mut().insert_full(song.id(), song);
-
-        if let Some(prev_value) = prev_value {
-            unbind_song_from_db(&prev_value);
+        self.imp().dirty_songs.borrow_mut().remove(song.id_ref());
+        self.mark_changed_while_loading([song.id_ref()]);
+
+        self.rebind_song_to_db(&song);
+        let (position, prev_record) = self
+            .imp()
+            .list
+            .borrow_mut()
+            .insert_full(song.id(), SongRecord::new(&song));
+        let last_heard = last_heard_key(&song);
+
+        if let Some(prev_record) = prev_record {
             self.items_changed(position as u32, 1, 1);
+            self.recent_songs()
+                .update(song.id_ref(), prev_record.last_heard, last_heard);
             Ok(false)
         } else {
             self.items_changed(position as u32, 0, 1);
+            self.recent_songs().insert(song.id_ref(), last_heard);
             Ok(true)
         }
     }
@@ -152,23 +393,34 @@ impl SongList {
             }
             Ok(())
         })?;
+        {
+            let mut dirty_songs = self.imp().dirty_songs.borrow_mut();
+            for song in &songs {
+                dirty_songs.remove(song.id_ref());
+            }
+        }
+        self.mark_changed_while_loading(songs.iter().map(|song| song.id_ref()));
 
         let mut updated_indices = HashSet::new();
         let mut n_appended = 0;
-
-        {
-            let mut list = self.imp().list.borrow_mut();
-
-            for song in songs {
-                self.bind_song_to_db(&song);
-                let (index, prev_value) = list.insert_full(song.id(), song);
-
-                if let Some(prev_value) = prev_value {
-                    unbind_song_from_db(&prev_value);
-                    updated_indices.insert(index);
-                } else {
-                    n_appended += 1;
-                }
+        let mut appended = Vec::new();
+        let mut updated = Vec::new();
+
+        for song in songs {
+            self.rebind_song_to_db(&song);
+            let last_heard = last_heard_key(&song);
+            let (index, prev_record) = self
+                .imp()
+                .list
+                .borrow_mut()
+                .insert_full(song.id(), SongRecord::new(&song));
+
+            if let Some(prev_record) = prev_record {
+                updated_indices.insert(index);
+                updated.push((song.id(), prev_record.last_heard, last_heard));
+            } else {
+                n_appended += 1;
+                appended.push((song.id(), last_heard));
             }
         }
 
@@ -191,10 +443,40 @@ impl SongList {
             }
         }
 
+        let recent_songs = self.recent_songs();
+        recent_songs.insert_many(appended);
+        for (song_id, old_last_heard, new_last_heard) in updated {
+            recent_songs.update(&song_id, old_last_heard, new_last_heard);
+        }
+
         Ok(n_appended)
     }
 
     pub fn remove_many(&self, song_ids: &[&Uid]) -> Result<Vec<Song>> {
+        // The songs must be read before they are deleted from the db
+        let mut songs = song_ids
+            .iter()
+            .filter(|&&song_id| self.contains(song_id))
+            .map(|&song_id| {
+                let song = self.get(song_id).context("Failed to read song to remove")?;
+                Ok((song_id.clone(), song))
+            })
+            .collect::<Result<HashMap<_, _>>>()?;
+
+        let removed = self
+            .remove_many_unread(song_ids)?
+            .into_iter()
+            .map(|song_id| songs.remove(&song_id).expect("song must have been read"))
+            .collect();
+
+        Ok(removed)
+    }
+
+    /// Removes the songs like [`Self::remove_many`], but without reading the
+    /// ones that are not alive from the db.
+    ///
+    /// This returns the ids of the removed songs.
+    pub fn remove_many_unread(&self, song_ids: &[&Uid]) -> Result<Vec<Uid>> {
         let imp = self.imp();
 
         let (env, db) = self.db();
@@ -205,6 +487,13 @@ impl SongList {
             }
             Ok(())
         })?;
+        {
+            let mut dirty_songs = imp.dirty_songs.borrow_mut();
+            for &song_id in song_ids {
+                dirty_songs.remove(song_id);
+            }
+        }
+        self.mark_changed_while_loading(song_ids.iter().copied());
 
         let to_remove_indices = {
             let list = imp.list.borrow();
@@ -214,35 +503,168 @@ impl SongList {
                 .collect::<BTreeSet<_>>()
         };
 
-        let removed = {
-            let mut ret = Vec::with_capacity(to_remove_indices.len());
+        let mut removed_records = Vec::with_capacity(to_remove_indices.len());
 
-            // Reverse the iterations so we don't shift the indices
-            for &(first, count) in utils::consecutive_groups(&to_remove_indices).iter().rev() {
-                {
-                    let mut list = imp.list.borrow_mut();
+        // Reverse the iterations so we don't shift the indices
+        for &(first, count) in utils::consecutive_groups(&to_remove_indices).iter().rev() {
+            {
+                let mut list = imp.list.borrow_mut();
 
-                    for index in (first..first + count).rev() {
-                        let (_, song) =
-                            list.shift_remove_index(index).expect("index must be valid");
+                for index in (first..first + count).rev() {
+                    let (song_id, record) =
+                        list.shift_remove_index(index).expect("index must be valid");
+                    if let Some(song) = record.song() {
                         unbind_song_from_db(&song);
-                        ret.push(song);
                     }
+                    removed_records.push((song_id, record.last_heard));
                 }
-
-                self.items_changed(first as u32, count as u32, 0);
             }
 
-            debug_assert_eq!(ret.len(), to_remove_indices.len());
+            self.items_changed(first as u32, count as u32, 0);
+        }
 
-            ret
-        };
+        self.recent_songs().remove_many(&removed_records);
 
-        Ok(removed)
+        Ok(removed_records
+            .into_iter()
+            .map(|(song_id, _)| song_id)
+            .collect())
     }
 
+    /// Merges the songs with `duplicate_ids` into the song with `song_id`, then
+    /// removes them from the list.
+    ///
+    /// This returns the removed duplicates.
+    pub fn merge(&self, song_id: &Uid, duplicate_ids: &[&Uid]) -> Result<Vec<Song>> {
+        let song = self
+            .get(song_id)
+            .context("Song to merge into is not in the list")?;
+
+        let duplicates = duplicate_ids
+            .iter()
+            .map(|&duplicate_id| {
+                ensure!(
+                    duplicate_id != song_id,
+                    "Song must not be merged into itself"
+                );
+                self.get(duplicate_id)
+                    .context("Duplicate is not in the list")
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for duplicate in &duplicates {
+            song.merge(duplicate);
+        }
+
+        // Save the merged values before the duplicates are removed, so they
+        // are not lost if it fails.
+        self.flush()?;
+
+        self.remove_many(duplicate_ids)
+    }
+
+    /// Returns the ids of the songs in the list, in order, along with when they
+    /// were last heard in unix microseconds.
+    pub fn last_heard_times(&self) -> Vec<(Uid, Option<i64>)> {
+        self.imp()
+            .list
+            .borrow()
+            .iter()
+            .map(|(song_id, record)| (song_id.clone(), record.last_heard))
+            .collect()
+    }
+
+    /// Returns the summaries of the songs in the list, in order.
+    ///
+    /// The queued changes are saved first, then the summaries are decoded
+    /// from the db off the main thread, so the songs are not created.
+    pub async fn summaries(&self) -> Result<Vec<SongSummary>> {
+        self.flush()?;
+
+        let (env, db) = self.db();
+        let env = env.clone();
+        let db = db.remap_types::<Bytes, Bytes>();
+        let summaries = database::spawn_blocking(move || {
+            let rtxn = env.read_txn().context("Failed to create read txn")?;
+            let mut summaries = Vec::new();
+            for item in db.iter(&rtxn).context("Failed to iter songs from db")? {
+                let (_, bytes) = item.context("Failed to read song from db")?;
+                match SongSummary::decode(bytes) {
+                    Ok(summary) => summaries.push(summary),
+                    Err(err) => tracing::warn!("Failed to decode song summary: {:?}", err),
+                }
+            }
+            anyhow::Ok(summaries)
+        })
+        .await?;
+
+        // Songs may have been inserted or removed while decoding
+        let list = self.imp().list.borrow();
+        let mut summaries = summaries
+            .into_iter()
+            .filter_map(|summary| Some((list.get_index_of(&summary.id)?, summary)))
+            .collect::<Vec<_>>();
+        summaries.sort_by_key(|(index, _)| *index);
+
+        Ok(summaries.into_iter().map(|(_, summary)| summary).collect())
+    }
+
+    /// Returns the song with the given id, reading it from the db if it is
+    /// not alive.
     pub fn get(&self, song_id: &Uid) -> Option<Song> {
-        self.imp().list.borrow().get(song_id).cloned()
+        match self.try_get(song_id)? {
+            Ok(song) => Some(song),
+            Err(err) => {
+                tracing::error!("Failed to read song from db: {:?}", err);
+                None
+            }
+        }
+    }
+
+    /// Returns the song with the given id like [`Self::get`], but with a
+    /// placeholder if it fails to be read from the db, so that items of models
+    /// are never missing. `read-failed` is emitted in that case.
+    ///
+    /// The placeholder is not saved to the db, and reading the song is retried
+    /// the next time it is accessed.
+    pub fn get_or_placeholder(&self, song_id: &Uid) -> Option<Song> {
+        match self.try_get(song_id)? {
+            Ok(song) => Some(song),
+            Err(err) => {
+                tracing::error!("Failed to read song from db: {:?}", err);
+                self.emit_read_failed();
+                Some(
+                    Song::builder(
+                        song_id,
+                        &gettext("Unknown Title"),
+                        &gettext("Unknown Artist"),
+                        "",
+                    )
+                    .build(),
+                )
+            }
+        }
+    }
+
+    /// Returns `None` if the song is not in the list.
+    fn try_get(&self, song_id: &Uid) -> Option<Result<Song>> {
+        let imp = self.imp();
+
+        if let Some(song) = imp.list.borrow().get(song_id)?.song() {
+            return Some(Ok(song));
+        }
+
+        let song = match self.read_song(song_id) {
+            Ok(song) => song,
+            Err(err) => return Some(Err(err)),
+        };
+        self.bind_song_to_db(&song);
+
+        if let Some(record) = imp.list.borrow_mut().get_mut(song_id) {
+            record.song = Some(song.downgrade());
+        }
+
+        Some(Ok(song))
     }
 
     pub fn contains(&self, song_id: &Uid) -> bool {
@@ -253,10 +675,144 @@ impl SongList {
         self.n_items() == 0
     }
 
+    /// Returns the songs in the list, from the most recently heard.
+    pub fn recent_songs(&self) -> RecentSongs {
+        self.imp().recent_songs.get().unwrap().clone()
+    }
+
+    /// Saves the queued changes to songs to the db in a single txn.
+    ///
+    /// The changes are kept queued if this fails.
+    pub fn flush(&self) -> Result<()> {
+        let imp = self.imp();
+
+        if let Some(source_id) = imp.flush_source_id.take() {
+            source_id.remove();
+        }
+
+        let songs = {
+            let list = imp.list.borrow();
+            imp.dirty_songs
+                .borrow()
+                .values()
+                .filter(|song| list.contains_key(song.id_ref()))
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        if songs.is_empty() {
+            return Ok(());
+        }
+
+        let (env, db) = self.db();
+        env.with_write_txn(|wtxn| {
+            for song in &songs {
+                debug_assert!(
+                    db.get(wtxn, song.id_ref()).unwrap().is_some(),
+                    "song must exist in the db"
+                );
+
+                db.put(wtxn, song.id_ref(), song)
+                    .context("Failed to put song to db")?;
+            }
+            Ok(())
+        })?;
+        imp.dirty_songs.borrow_mut().clear();
+
+        tracing::debug!("Flushed {} songs to db", songs.len());
+
+        Ok(())
+    }
+
+    /// Queues the song to be saved to the db once idle.
+    fn queue_flush(&self, song: &Song) {
+        let imp = self.imp();
+
+        imp.dirty_songs.borrow_mut().insert(song.id(), song.clone());
+
+        // There is no main loop to flush on once idle, such as in tests.
+        if !glib::MainContext::default().is_owner() {
+            self.flush_or_emit_failed();
+            return;
+        }
+
+        if imp.flush_source_id.borrow().is_some() {
+            return;
+        }
+
+        let source_id = glib::timeout_add_local_full(
+            FLUSH_DELAY,
+            glib::Priority::DEFAULT_IDLE,
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                #[upgrade_or]
+                glib::ControlFlow::Break,
+                move || {
+                    obj.imp().flush_source_id.replace(None);
+                    obj.flush_or_emit_failed();
+                    glib::ControlFlow::Break
+                }
+            ),
+        );
+        imp.flush_source_id.replace(Some(source_id));
+    }
+
+    fn flush_or_emit_failed(&self) {
+        if let Err(err) = self.flush() {
+            tracing::error!("Failed to flush songs to db: {:?}", err);
+            self.emit_flush_failed();
+        }
+    }
+
     fn db(&self) -> &(heed::Env, SongDatabase) {
         self.imp().db.get().unwrap()
     }
 
+    fn read_song(&self, song_id: &Uid) -> Result<Song> {
+        let (env, db) = self.db();
+        let rtxn = env.read_txn().context("Failed to create read txn")?;
+        db.get(&rtxn, song_id)
+            .context("Failed to get song from db")?
+            .context("Song is not in the db")
+    }
+
+    /// Updates when the song was last heard in its record, and moves it in the
+    /// recent songs accordingly.
+    fn update_last_heard(&self, song: &Song) {
+        let last_heard = last_heard_key(song);
+
+        let prev_last_heard = {
+            let mut list = self.imp().list.borrow_mut();
+            let Some(record) = list.get_mut(song.id_ref()) else {
+                return;
+            };
+            std::mem::replace(&mut record.last_heard, last_heard)
+        };
+
+        self.recent_songs()
+            .update(song.id_ref(), prev_last_heard, last_heard);
+    }
+
+    /// Binds `song` in place of the alive song with the same id, if any.
+    fn rebind_song_to_db(&self, song: &Song) {
+        let prev_song = self
+            .imp()
+            .list
+            .borrow()
+            .get(song.id_ref())
+            .and_then(|record| record.song());
+
+        match prev_song {
+            Some(prev_song) if &prev_song == song => {}
+            Some(prev_song) => {
+                unbind_song_from_db(&prev_song);
+                self.bind_song_to_db(song);
+            }
+            None => self.bind_song_to_db(song),
+        }
+    }
+
     fn bind_song_to_db(&self, song: &Song) {
         unsafe {
             let handler_id = song.connect_notify_local(
@@ -267,20 +823,11 @@ impl SongList {
                     move |song, pspec| {
                         tracing::debug!("Song property `{}` notified", pspec.name());
 
-                        let (env, db) = obj.db();
-                        if let Err(err) = env.with_write_txn(|wtxn| {
-                            debug_assert!(
-                                db.get(wtxn, song.id_ref()).unwrap().is_some(),
-                                "song must exist in the db"
-                            );
-
-                            db.put(wtxn, song.id_ref(), song)
-                                .context("Failed to put song to db")?;
+                        if pspec.name() == "last-heard" {
+                            obj.update_last_heard(song);
+                        }
 
-                            Ok(())
-                        }) {
-                            tracing::error!("Failed to update song in database: {:?}", err);
-                        };
+                        obj.queue_flush(song);
                     }
                 ),
             );
@@ -289,6 +836,11 @@ impl SongList {
     }
 }
 
+fn last_heard_key(song: &Song) -> Option<i64> {
+    song.last_heard()
+        .map(|last_heard| last_heard.to_unix_usec())
+}
+
 fn unbind_song_from_db(song: &Song) {
     unsafe {
         let handler_id = song
@@ -298,6 +850,67 @@ fn unbind_song_from_db(song: &Song) {
     };
 }
 
+/// Songs in the `songs` table in the database, read off the main thread to be
+/// loaded into a [`SongList`]
+///
+/// Only the ids of the songs and when they were last heard are kept, so only
+/// these are sent to the main thread.
+pub struct SongListEntries {
+    /// Ids of the songs and when they were last heard
+    songs: Vec<(Uid, Option<i64>)>,
+    /// Keys of the entries that can't be decoded, along with why
+    invalid_entries: Vec<(Vec<u8>, String)>,
+}
+
+impl SongListEntries {
+    /// Reads and decodes all entries of the `songs` table in the database.
+    ///
+    /// The decoded songs are dropped right away, so this can be called off the
+    /// main thread.
+    pub fn read(env: &heed::Env) -> Result<Self> {
+        env.with_write_txn(|wtxn| {
+            let db: SongDatabase = env
+                .create_database(wtxn, Some(SONG_LIST_DB_NAME))
+                .context("Failed to create songs db")?;
+
+            let mut songs = Vec::new();
+            let mut invalid_entries = Vec::new();
+
+            for item in db
+                .remap_types::<Bytes, Bytes>()
+                .iter(wtxn)
+                .context("Failed to iter songs db")?
+            {
+                let (key, value) = item.context("Failed to read song from db")?;
+
+                match quarantine::decode::<VersionedCodec<Song>>(key, value) {
+                    Ok(Some((id, song))) => {
+                        debug_assert_eq!(&id, song.id_ref(), "id must be equal to song.id()");
+                        songs.push((id, last_heard_key(&song)));
+                    }
+                    // Stored by a newer release, so keep it in the db as is
+                    Ok(None) => {}
+                    Err(reason) => invalid_entries.push((key.to_vec(), reason)),
+                }
+            }
+
+            Ok(Self {
+                songs,
+                invalid_entries,
+            })
+        })
+    }
+
+    /// Returns the number of songs that were decoded.
+    pub fn len(&self) -> usize {
+        self.songs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.songs.is_empty()
+    }
+}
+
 /// Migrate from the old memory list of Mousai v0.6.6 and earlier.
 fn migrate_from_memory_list(song_list: &SongList) -> Result<()> {
     use crate::{date_time::DateTime, external_links::ExternalLinkKey, settings::Settings};
@@ -445,10 +1058,36 @@ mod test {
         rc::Rc,
     };
 
-    use crate::database;
+    use crate::{
+        database::{self, versioned::Versioned},
+        date_time::DateTime,
+        song::test::new_test_song,
+    };
 
-    fn new_test_song(id: &str) -> Song {
-        Song::builder(&Uid::from(id), id, id, id).build()
+    /// Returns all songs in the list, in order, reading the ones that are not
+    /// alive from the db.
+    fn snapshot(song_list: &SongList) -> Vec<Song> {
+        let song_ids = song_list
+            .imp()
+            .list
+            .borrow()
+            .keys()
+            .cloned()
+            .collect::<Vec<_>>();
+        song_ids
+            .iter()
+            .filter_map(|song_id| song_list.get(song_id))
+            .collect()
+    }
+
+    fn n_alive_songs(song_list: &SongList) -> usize {
+        song_list
+            .imp()
+            .list
+            .borrow()
+            .values()
+            .filter(|record| record.song().is_some())
+            .count()
     }
 
     fn assert_n_items_and_db_count_eq(song_list: &SongList, n: usize) {
@@ -466,14 +1105,18 @@ mod test {
         let (env, db) = song_list.db();
 
         // Test if the items are synced to the database
+        let songs = snapshot(song_list);
         let rtxn = env.read_txn().unwrap();
         for item in db.iter(&rtxn).unwrap() {
             let (_, song) = item.unwrap();
             assert!(!song.is_newly_heard());
         }
-        for (id, song) in song_list.imp().list.borrow().iter() {
+        for song in &songs {
             assert_eq!(
-                db.get(&rtxn, id).unwrap().unwrap().is_newly_heard(),
+                db.get(&rtxn, song.id_ref())
+                    .unwrap()
+                    .unwrap()
+                    .is_newly_heard(),
                 song.is_newly_heard()
             );
         }
@@ -488,10 +1131,14 @@ mod test {
 
         // Test if the items are synced to the database even
         // after the song is modified\
+        let songs = snapshot(song_list);
         let rtxn = env.read_txn().unwrap();
-        for (id, song) in song_list.imp().list.borrow().iter() {
+        for song in &songs {
             assert_eq!(
-                db.get(&rtxn, id).unwrap().unwrap().is_newly_heard(),
+                db.get(&rtxn, song.id_ref())
+                    .unwrap()
+                    .unwrap()
+                    .is_newly_heard(),
                 song.is_newly_heard()
             );
         }
@@ -504,20 +1151,24 @@ mod test {
             .set_is_newly_heard(true);
         assert_n_items_and_db_count_eq(song_list, 2);
 
+        let songs = snapshot(song_list);
         let rtxn = env.read_txn().unwrap();
         for item in db.iter(&rtxn).unwrap() {
             let (_, song) = item.unwrap();
             assert!(song.is_newly_heard());
         }
-        for (id, song) in song_list.imp().list.borrow().iter() {
+        for song in &songs {
             assert_eq!(
-                db.get(&rtxn, id).unwrap().unwrap().is_newly_heard(),
+                db.get(&rtxn, song.id_ref())
+                    .unwrap()
+                    .unwrap()
+                    .is_newly_heard(),
                 song.is_newly_heard()
             );
         }
         drop(rtxn);
 
-        for (_, song) in song_list.imp().list.borrow().iter() {
+        for song in songs {
             song.set_is_newly_heard(false);
         }
 
@@ -556,6 +1207,210 @@ mod test {
         assert_synced_to_db(&song_list);
     }
 
+    #[test]
+    fn load_keeps_newer_version() {
+        let (env, _tempdir) = database::new_test_env();
+        let mut wtxn = env.write_txn().unwrap();
+        let db: SongDatabase = env
+            .create_database(&mut wtxn, Some(SONG_LIST_DB_NAME))
+            .unwrap();
+        db.put(&mut wtxn, &Uid::from("a"), &new_test_song("a"))
+            .unwrap();
+        let mut newer_value = (Song::VERSION + 1).to_be_bytes().to_vec();
+        newer_value.extend_from_slice(b"unknown layout");
+        db.remap_data_type::<Bytes>()
+            .put(&mut wtxn, &Uid::from("newer"), &newer_value)
+            .unwrap();
+        db.remap_data_type::<Bytes>()
+            .put(&mut wtxn, &Uid::from("invalid"), b"")
+            .unwrap();
+        wtxn.commit().unwrap();
+
+        let song_list = SongList::load_from_env(env.clone()).unwrap();
+        assert_eq!(song_list.n_items(), 1);
+        assert!(song_list.contains(&Uid::from("a")));
+        assert!(!song_list.contains(&Uid::from("newer")));
+
+        // Only the invalid entry is quarantined, while the newer one is kept
+        assert_eq!(quarantine::len(&env).unwrap(), 1);
+        let rtxn = env.read_txn().unwrap();
+        let raw_db = db.remap_data_type::<Bytes>();
+        assert_eq!(
+            raw_db.get(&rtxn, &Uid::from("newer")).unwrap(),
+            Some(&newer_value[..])
+        );
+        assert_eq!(raw_db.get(&rtxn, &Uid::from("invalid")).unwrap(), None);
+    }
+
+    #[test]
+    fn load_progressively() {
+        let (env, _tempdir) = database::new_test_env();
+        let mut wtxn = env.write_txn().unwrap();
+        let db: SongDatabase = env
+            .create_database(&mut wtxn, Some(SONG_LIST_DB_NAME))
+            .unwrap();
+        for i in 0..LOAD_CHUNK_SIZE * 2 + 1 {
+            let id = format!("{:04}", i);
+            db.put(&mut wtxn, &Uid::from(id.as_str()), &new_test_song(&id))
+                .unwrap();
+        }
+        db.remap_data_type::<Bytes>()
+            .put(&mut wtxn, &Uid::from("invalid"), b"")
+            .unwrap();
+        wtxn.commit().unwrap();
+
+        // The entries are read off the main thread, like when loading the app
+        let entries = std::thread::spawn(clone!(
+            #[strong]
+            env,
+            move || SongListEntries::read(&env)
+        ))
+        .join()
+        .unwrap()
+        .unwrap();
+        assert_eq!(entries.len(), LOAD_CHUNK_SIZE * 2 + 1);
+
+        let song_list = SongList::new_loading(env.clone()).unwrap();
+        assert!(song_list.is_loading());
+        assert_eq!(song_list.n_items(), 0);
+
+        let n_appended = Rc::new(Cell::new(0));
+        let n_items_changed_calls = Rc::new(Cell::new(0));
+        song_list.connect_items_changed(clone!(
+            #[strong]
+            n_appended,
+            #[strong]
+            n_items_changed_calls,
+            move |_, _, removed, added| {
+                assert_eq!(removed, 0);
+                n_appended.set(n_appended.get() + added);
+                n_items_changed_calls.set(n_items_changed_calls.get() + 1);
+            }
+        ));
+
+        // Songs changed while loading must not be replaced by their outdated
+        // entries
+        let song = new_test_song("0000");
+        song.set_title("Zero");
+        song_list.insert(song).unwrap();
+        song_list.remove_many(&[&Uid::from("0001")]).unwrap();
+        song_list.insert(new_test_song("invalid")).unwrap();
+
+        let context = glib::MainContext::new();
+        context
+            .with_thread_default(|| context.block_on(song_list.load(entries)))
+            .unwrap()
+            .unwrap();
+
+        assert!(!song_list.is_loading());
+        assert_eq!(n_items_changed_calls.get(), 2 + 3);
+        assert_eq!(n_appended.get(), song_list.n_items());
+        assert_n_items_and_db_count_eq(&song_list, LOAD_CHUNK_SIZE * 2 + 1);
+
+        assert_eq!(
+            song_list.item(0).and_downcast::<Song>().unwrap().title(),
+            "Zero"
+        );
+        assert!(!song_list.contains(&Uid::from("0001")));
+        assert!(song_list.contains(&Uid::from("invalid")));
+        assert!(song_list.contains(&Uid::from("1000")));
+        assert_eq!(quarantine::len(&env).unwrap(), 0);
+        assert!(
+            song_list
+                .imp()
+                .changed_while_loading_ids
+                .borrow()
+                .is_empty()
+        );
+    }
+
+    #[test]
+    #[ignore = "writes and loads 100k songs, which takes a while"]
+    fn load_100k_lazily() {
+        const N_SONGS: usize = 100_000;
+
+        let song_id = |i: usize| Uid::from(format!("{:06}", i).as_str());
+
+        let (env, _tempdir) = database::new_test_env();
+        let write_start_time = Instant::now();
+        env.with_write_txn(|wtxn| {
+            let db: SongDatabase = env.create_database(wtxn, Some(SONG_LIST_DB_NAME))?;
+            for i in 0..N_SONGS {
+                let song = new_test_song(&format!("{:06}", i));
+                song.set_last_heard(DateTime::from(glib::DateTime::from_unix_utc(i as i64)?));
+                db.put(wtxn, song.id_ref(), &song)?;
+            }
+            Ok(())
+        })
+        .unwrap();
+        let write_elapsed = write_start_time.elapsed();
+
+        let load_start_time = Instant::now();
+        let song_list = SongList::load_from_env(env).unwrap();
+        let load_elapsed = load_start_time.elapsed();
+
+        assert_n_items_and_db_count_eq(&song_list, N_SONGS);
+        assert_eq!(n_alive_songs(&song_list), 0);
+
+        let recent_songs = song_list.recent_songs();
+        assert_eq!(recent_songs.n_items(), N_SONGS as u32);
+
+        // Only the accessed page of songs is created, and kept while alive
+        let page_start_time = Instant::now();
+        let page = (0..50)
+            .map(|position| recent_songs.item(position).and_downcast::<Song>().unwrap())
+            .collect::<Vec<_>>();
+        let page_elapsed = page_start_time.elapsed();
+        assert_eq!(page[0].id_ref(), &song_id(N_SONGS - 1));
+        assert_eq!(page[49].id_ref(), &song_id(N_SONGS - 50));
+        assert_eq!(n_alive_songs(&song_list), 50);
+        assert_eq!(
+            recent_songs.item(0).and_downcast::<Song>().unwrap(),
+            page[0]
+        );
+        drop(page);
+        assert_eq!(n_alive_songs(&song_list), 0);
+
+        // Hearing the oldest song again moves it to the top
+        let song = song_list.item(0).and_downcast::<Song>().unwrap();
+        assert_eq!(song.id_ref(), &song_id(0));
+        assert_eq!(
+            recent_songs.item(N_SONGS as u32 - 1).and_downcast::<Song>(),
+            Some(song.clone())
+        );
+        song.set_last_heard(DateTime::now_utc());
+        assert_eq!(
+            recent_songs.item(0).and_downcast::<Song>(),
+            Some(song.clone())
+        );
+        drop(song);
+
+        let change_start_time = Instant::now();
+        song_list.insert(new_test_song("new")).unwrap();
+        song_list.remove_many(&[&song_id(1)]).unwrap();
+        let change_elapsed = change_start_time.elapsed();
+        assert_n_items_and_db_count_eq(&song_list, N_SONGS);
+        assert_eq!(recent_songs.n_items(), N_SONGS as u32);
+        assert_eq!(
+            recent_songs
+                .item(N_SONGS as u32 - 1)
+                .and_downcast::<Song>()
+                .unwrap()
+                .id_ref(),
+            &Uid::from("new")
+        );
+        assert_eq!(n_alive_songs(&song_list), 0);
+
+        tracing::debug!(
+            n_songs = N_SONGS,
+            write = ?write_elapsed,
+            load = ?load_elapsed,
+            page = ?page_elapsed,
+            insert_and_remove = ?change_elapsed,
+            "Loaded songs lazily"
+        );
+    }
+
     #[test]
     fn insert_and_remove() {
         let (env, _tempdir) = database::new_test_env();
@@ -597,6 +1452,102 @@ mod test {
         assert_n_items_and_db_count_eq(&song_list, 0);
     }
 
+    #[test]
+    fn flush() {
+        // Flushes are only queued when there is a main loop
+        let _context_acquire_guard = glib::MainContext::default().acquire().unwrap();
+
+        let (env, _tempdir) = database::new_test_env();
+        let song_list = SongList::load_from_env(env).unwrap();
+
+        let song_1 = new_test_song("1");
+        let song_2 = new_test_song("2");
+        song_list
+            .insert_many(vec![song_1.clone(), song_2.clone()])
+            .unwrap();
+
+        let is_newly_heard_in_db = |song: &Song| {
+            let (env, db) = song_list.db();
+            let rtxn = env.read_txn().unwrap();
+            db.get(&rtxn, song.id_ref())
+                .unwrap()
+                .unwrap()
+                .is_newly_heard()
+        };
+
+        song_1.set_is_newly_heard(true);
+        song_1.set_title("One");
+        song_2.set_is_newly_heard(true);
+        assert!(!is_newly_heard_in_db(&song_1));
+        assert!(!is_newly_heard_in_db(&song_2));
+        assert_eq!(song_list.imp().dirty_songs.borrow().len(), 2);
+
+        song_list.flush().unwrap();
+        assert!(is_newly_heard_in_db(&song_1));
+        assert!(is_newly_heard_in_db(&song_2));
+        assert!(song_list.imp().dirty_songs.borrow().is_empty());
+
+        let (env, db) = song_list.db();
+        let rtxn = env.read_txn().unwrap();
+        assert_eq!(
+            db.get(&rtxn, song_1.id_ref()).unwrap().unwrap().title(),
+            "One"
+        );
+        drop(rtxn);
+
+        // Queued changes of removed songs are dropped
+        song_2.set_is_newly_heard(false);
+        song_list.remove_many(&[song_2.id_ref()]).unwrap();
+        assert!(song_list.imp().dirty_songs.borrow().is_empty());
+        song_list.flush().unwrap();
+        assert_n_items_and_db_count_eq(&song_list, 1);
+    }
+
+    #[test]
+    fn merge() {
+        let (env, _tempdir) = database::new_test_env();
+        let song_list = SongList::load_from_env(env).unwrap();
+
+        let song_1 = new_test_song("1");
+        let song_2 = Song::builder(&Uid::from("2"), "2", "2", "2")
+            .lyrics("Lyrics")
+            .build();
+        song_list
+            .insert_many(vec![song_1.clone(), song_2.clone()])
+            .unwrap();
+
+        assert!(
+            song_list
+                .merge(song_1.id_ref(), &[song_1.id_ref()])
+                .is_err()
+        );
+        assert!(
+            song_list
+                .merge(song_1.id_ref(), &[&Uid::from("unknown")])
+                .is_err()
+        );
+        assert_n_items_and_db_count_eq(&song_list, 2);
+
+        let removed = song_list
+            .merge(song_1.id_ref(), &[song_2.id_ref()])
+            .unwrap();
+        assert_eq!(removed, vec![song_2.clone()]);
+        assert_eq!(song_list.get(song_2.id_ref()), None);
+        assert_eq!(snapshot(&song_list), vec![song_1.clone()]);
+        assert_n_items_and_db_count_eq(&song_list, 1);
+
+        let (env, db) = song_list.db();
+        let rtxn = env.read_txn().unwrap();
+        assert_eq!(
+            db.get(&rtxn, song_1.id_ref())
+                .unwrap()
+                .unwrap()
+                .lyrics()
+                .as_deref(),
+            Some("Lyrics")
+        );
+    }
+
     #[test]
     fn remove_many() {
         let (env, _tempdir) = database::new_test_env();
@@ -627,6 +1578,86 @@ mod test {
         assert_n_items_and_db_count_eq(&song_list, 0);
     }
 
+    #[test]
+    fn remove_many_unread() {
+        let (env, _tempdir) = database::new_test_env();
+        let song_list = SongList::load_from_env(env).unwrap();
+
+        song_list
+            .insert_many(vec![
+                new_test_song("1"),
+                new_test_song("2"),
+                new_test_song("3"),
+            ])
+            .unwrap();
+        assert_eq!(n_alive_songs(&song_list), 0);
+
+        let removed = song_list
+            .remove_many_unread(&[&Uid::from("1"), &Uid::from("3")])
+            .unwrap();
+        assert_eq!(removed, vec![Uid::from("3"), Uid::from("1")]);
+        assert_eq!(n_alive_songs(&song_list), 0);
+        assert_eq!(song_list.last_heard_times(), vec![(Uid::from("2"), None)]);
+        assert_n_items_and_db_count_eq(&song_list, 1);
+    }
+
+    #[test]
+    fn item_placeholder_on_read_failure() {
+        let (env, _tempdir) = database::new_test_env();
+        let song_list = SongList::load_from_env(env).unwrap();
+
+        song_list.insert(new_test_song("1")).unwrap();
+        assert_eq!(n_alive_songs(&song_list), 0);
+
+        let n_read_failed = Rc::new(Cell::new(0));
+        song_list.connect_read_failed(clone!(
+            #[strong]
+            n_read_failed,
+            move |_| {
+                n_read_failed.set(n_read_failed.get() + 1);
+            }
+        ));
+
+        // Make reading the song fail
+        let (env, db) = song_list.db();
+        env.with_write_txn(|wtxn| {
+            db.delete(wtxn, &Uid::from("1"))?;
+            Ok(())
+        })
+        .unwrap();
+
+        let song = song_list.item(0).and_downcast::<Song>().unwrap();
+        assert_eq!(song.id_ref(), &Uid::from("1"));
+        assert_eq!(n_read_failed.get(), 1);
+        assert_eq!(song_list.get(&Uid::from("1")), None);
+        assert_eq!(n_read_failed.get(), 1);
+    }
+
+    #[test]
+    fn summaries() {
+        let (env, _tempdir) = database::new_test_env();
+        let song_list = SongList::load_from_env(env).unwrap();
+
+        let song_1 = new_test_song("1");
+        song_list
+            .insert_many(vec![song_1.clone(), new_test_song("2")])
+            .unwrap();
+
+        // Changes to the songs are included
+        song_1.set_last_heard(DateTime::now_utc());
+
+        let summaries = glib::MainContext::default()
+            .block_on(song_list.summaries())
+            .unwrap();
+        assert_eq!(
+            summaries,
+            vec![
+                SongSummary::new(&song_1),
+                SongSummary::new(&song_list.get(&Uid::from("2")).unwrap()),
+            ]
+        );
+    }
+
     #[test]
     fn remove_many_reversed() {
         let (env, _tempdir) = database::new_test_env();
@@ -0,0 +1,349 @@
+use anyhow::{Context, Result, anyhow, bail};
+use heed::types::Bytes;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    database::{
+        EnvExt, SONG_TRASH_DB_NAME, quarantine,
+        versioned::{self, Versioned, VersionedCodec},
+    },
+    date_time::DateTime,
+    song::{Song, SongV3},
+    uid::{Uid, UidCodec},
+};
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Number of days songs are kept in the trash before being purged
+pub const TRASH_MAX_AGE_DAYS: i64 = 30;
+
+type SongTrashDatabase = heed::Database<UidCodec, VersionedCodec<TrashedSong>>;
+
+/// A song removed from the history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrashedSong {
+    pub song: Song,
+    pub deleted_time: DateTime,
+}
+
+impl Versioned for TrashedSong {
+    // This contains a song, so this is incremented along with the version of
+    // `Song`.
+    const VERSION: u16 = TrashedSongV1::VERSION + Song::VERSION - SongV3::VERSION;
+
+    fn upgrade(version: u16, bytes: &[u8]) -> Result<Self> {
+        match version {
+            TrashedSongV1::VERSION => {
+                versioned::upgrade_from(bytes, |trashed_song_v1: TrashedSongV1| {
+                    trashed_song_v1.into_trashed_song()
+                })
+            }
+            _ => bail!("Unknown version {}", version),
+        }
+    }
+}
+
+/// Layout of [`TrashedSong`] in the database when trashed songs were first
+/// versioned, which contained a song with the layout of [`SongV3`].
+///
+/// This is only used for upgrading the stored trashed songs.
+#[derive(Serialize, Deserialize)]
+pub struct TrashedSongV1 {
+    song: SongV3,
+    deleted_time: DateTime,
+}
+
+impl TrashedSongV1 {
+    pub const VERSION: u16 = 1;
+
+    pub fn into_trashed_song(self) -> Result<TrashedSong> {
+        Ok(TrashedSong {
+            song: self.song.into_song()?,
+            deleted_time: self.deleted_time,
+        })
+    }
+}
+
+/// Songs removed from the history, stored by song id, so they can be restored
+/// until they are purged.
+#[derive(Clone)]
+pub struct SongTrash {
+    env: heed::Env,
+    db: SongTrashDatabase,
+}
+
+impl SongTrash {
+    /// Load from the `song_trash` table in the database
+    pub fn load_from_env(env: heed::Env) -> Result<Self> {
+        let db = env.with_write_txn(|wtxn| {
+            env.create_database(wtxn, Some(SONG_TRASH_DB_NAME))
+                .context("Failed to create song trash db")
+        })?;
+
+        Ok(Self { env, db })
+    }
+
+    /// Returns the trashed songs, from the most to the least recently deleted.
+    pub fn snapshot(&self) -> Result<Vec<TrashedSong>> {
+        let rtxn = self.env.read_txn().context("Failed to create read txn")?;
+        let mut trashed_songs = self
+            .decode_all(&rtxn)?
+            .into_iter()
+            .map(|(_, trashed_song)| trashed_song)
+            .collect::<Vec<_>>();
+        trashed_songs.sort_by(|a, b| b.deleted_time.cmp(&a.deleted_time));
+        Ok(trashed_songs)
+    }
+
+    pub fn contains(&self, song_id: &Uid) -> Result<bool> {
+        let rtxn = self.env.read_txn().context("Failed to create read txn")?;
+        let contains = self
+            .db
+            .remap_data_type::<heed::types::DecodeIgnore>()
+            .get(&rtxn, song_id)
+            .context("Failed to get trashed song from db")?
+            .is_some();
+        Ok(contains)
+    }
+
+    /// Moves the songs to the trash, replacing the existing trashed songs with
+    /// the same ids.
+    pub fn insert_many(&self, songs: &[Song], deleted_time: &DateTime) -> Result<()> {
+        self.env.with_write_txn(|wtxn| {
+            for song in songs {
+                let trashed_song = TrashedSong {
+                    song: song.clone(),
+                    deleted_time: deleted_time.clone(),
+                };
+                self.db
+                    .put(wtxn, song.id_ref(), &trashed_song)
+                    .context("Failed to put trashed song to db")?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Removes the songs with the given ids from the trash, returning the ones
+    /// that were in it.
+    pub fn take_many(&self, song_ids: &[&Uid]) -> Result<Vec<Song>> {
+        self.env.with_write_txn(|wtxn| {
+            let mut songs = Vec::new();
+
+            for song_id in song_ids {
+                if let Some(trashed_song) = self
+                    .db
+                    .get(wtxn, song_id)
+                    .context("Failed to get trashed song from db")?
+                {
+                    self.db
+                        .delete(wtxn, song_id)
+                        .context("Failed to delete trashed song from db")?;
+                    songs.push(trashed_song.song);
+                }
+            }
+
+            Ok(songs)
+        })
+    }
+
+    /// Removes all songs from the trash, returning them.
+    ///
+    /// Songs stored by a newer release are kept.
+    pub fn take_all(&self) -> Result<Vec<Song>> {
+        self.env.with_write_txn(|wtxn| {
+            let mut songs = Vec::new();
+
+            for (song_id, trashed_song) in self.decode_all(wtxn)? {
+                self.db
+                    .delete(wtxn, &song_id)
+                    .context("Failed to delete trashed song from db")?;
+                songs.push(trashed_song.song);
+            }
+
+            Ok(songs)
+        })
+    }
+
+    /// Removes the songs deleted more than [`TRASH_MAX_AGE_DAYS`] before `now`,
+    /// returning them.
+    pub fn take_expired(&self, now: &DateTime) -> Result<Vec<Song>> {
+        self.env.with_write_txn(|wtxn| {
+            let expired = self
+                .decode_all(wtxn)?
+                .into_iter()
+                .filter(|(_, trashed_song)| {
+                    now.difference(&trashed_song.deleted_time).as_seconds()
+                        > TRASH_MAX_AGE_DAYS * SECONDS_PER_DAY
+                })
+                .map(|(_, trashed_song)| trashed_song.song)
+                .collect::<Vec<_>>();
+
+            for song in &expired {
+                self.db
+                    .delete(wtxn, song.id_ref())
+                    .context("Failed to delete trashed song from db")?;
+            }
+
+            if !expired.is_empty() {
+                tracing::debug!("Purged {} songs from trash", expired.len());
+            }
+
+            Ok(expired)
+        })
+    }
+
+    /// Decodes all trashed songs, skipping the ones stored by a newer release,
+    /// which are kept as they are.
+    fn decode_all(&self, txn: &heed::RoTxn<'_>) -> Result<Vec<(Uid, TrashedSong)>> {
+        let mut items = Vec::new();
+
+        for item in self
+            .db
+            .remap_types::<Bytes, Bytes>()
+            .iter(txn)
+            .context("Failed to iter trashed songs from db")?
+        {
+            let (key, value) = item.context("Failed to read trashed song from db")?;
+            if let Some(item) = quarantine::decode::<VersionedCodec<TrashedSong>>(key, value)
+                .map_err(|reason| anyhow!("Failed to decode trashed song: {}", reason))?
+            {
+                items.push(item);
+            }
+        }
+
+        Ok(items)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use heed::{BytesDecode, BytesEncode};
+
+    use crate::{
+        database,
+        song::test::{assert_fixture_song, new_test_song},
+    };
+
+    fn n_songs(trash: &SongTrash) -> u64 {
+        let rtxn = trash.env.read_txn().unwrap();
+        trash.db.len(&rtxn).unwrap()
+    }
+
+    fn date_time(date: &str) -> DateTime {
+        DateTime::from_iso8601(&format!("{}T00:00:00Z", date)).unwrap()
+    }
+
+    fn titles(songs: &[Song]) -> Vec<String> {
+        let mut titles = songs.iter().map(|song| song.title()).collect::<Vec<_>>();
+        titles.sort();
+        titles
+    }
+
+    #[test]
+    fn insert_and_take() {
+        let (env, _tempdir) = database::new_test_env();
+        let trash = SongTrash::load_from_env(env).unwrap();
+
+        trash
+            .insert_many(&[new_test_song("a")], &date_time("2024-01-01"))
+            .unwrap();
+        trash
+            .insert_many(
+                &[new_test_song("b"), new_test_song("c")],
+                &date_time("2024-01-02"),
+            )
+            .unwrap();
+        assert_eq!(n_songs(&trash), 3);
+
+        let snapshot = trash.snapshot().unwrap();
+        assert_eq!(snapshot[2].song.title(), "a");
+        assert_eq!(snapshot[2].deleted_time, date_time("2024-01-01"));
+
+        let taken = trash
+            .take_many(&[&Uid::from("a"), &Uid::from("b"), &Uid::from("d")])
+            .unwrap();
+        assert_eq!(titles(&taken), ["a", "b"]);
+        assert_eq!(n_songs(&trash), 1);
+
+        assert_eq!(titles(&trash.take_all().unwrap()), ["c"]);
+        assert_eq!(n_songs(&trash), 0);
+    }
+
+    #[test]
+    fn take_expired() {
+        let (env, _tempdir) = database::new_test_env();
+        let trash = SongTrash::load_from_env(env).unwrap();
+
+        trash
+            .insert_many(&[new_test_song("a")], &date_time("2024-01-01"))
+            .unwrap();
+        trash
+            .insert_many(&[new_test_song("b")], &date_time("2024-01-20"))
+            .unwrap();
+
+        assert!(
+            trash
+                .take_expired(&date_time("2024-01-31"))
+                .unwrap()
+                .is_empty()
+        );
+
+        let expired = trash.take_expired(&date_time("2024-02-01")).unwrap();
+        assert_eq!(titles(&expired), ["a"]);
+        assert_eq!(n_songs(&trash), 1);
+    }
+
+    #[test]
+    fn keep_newer_version() {
+        let (env, _tempdir) = database::new_test_env();
+        let trash = SongTrash::load_from_env(env).unwrap();
+
+        trash
+            .insert_many(&[new_test_song("a")], &date_time("2024-01-01"))
+            .unwrap();
+        trash
+            .env
+            .with_write_txn(|wtxn| {
+                let mut value = (TrashedSong::VERSION + 1).to_be_bytes().to_vec();
+                value.extend_from_slice(b"unknown layout");
+                trash
+                    .db
+                    .remap_data_type::<Bytes>()
+                    .put(wtxn, &Uid::from("b"), &value)?;
+                Ok(())
+            })
+            .unwrap();
+
+        assert_eq!(trash.snapshot().unwrap().len(), 1);
+        let expired = trash.take_expired(&date_time("2025-01-01")).unwrap();
+        assert_eq!(titles(&expired), ["a"]);
+        assert!(trash.take_all().unwrap().is_empty());
+        assert_eq!(n_songs(&trash), 1);
+    }
+
+    #[test]
+    fn versioned_fixtures() {
+        let bytes: &[u8] = include_bytes!("database/fixtures/trashed_song_v1.bin");
+        assert_eq!(TrashedSong::VERSION, TrashedSongV1::VERSION);
+
+        let trashed_song = VersionedCodec::<TrashedSong>::bytes_decode(bytes).unwrap();
+        assert_fixture_song(&trashed_song.song, 3);
+        assert_eq!(
+            trashed_song.deleted_time,
+            DateTime::from_iso8601("2024-02-01T00:00:00Z").unwrap()
+        );
+
+        let new_bytes = VersionedCodec::<TrashedSong>::bytes_encode(&trashed_song).unwrap();
+        assert_eq!(new_bytes, bytes);
+
+        // Upgrading is only needed once the layout of `Song` changes, but it
+        // must already work for the stored layout
+        let (version, payload) = bytes.split_at(size_of::<u16>());
+        assert_eq!(version, TrashedSongV1::VERSION.to_be_bytes());
+        let upgraded = TrashedSong::upgrade(TrashedSongV1::VERSION, payload).unwrap();
+        assert_fixture_song(&upgraded.song, 3);
+        assert_eq!(upgraded.deleted_time, trashed_song.deleted_time);
+    }
+}
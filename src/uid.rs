@@ -29,10 +29,28 @@ impl Uid {
     /// Generate a new id with `unique_str` made up of real time and a random u32
     /// both encoded in hex.
     pub fn generate() -> Self {
-        Self::from(format!("{:x}-{:x}", glib::real_time(), glib::random_int()))
+        Self::from(generate_unique_str())
+    }
+
+    /// Generate a new id like in `Self::generate()`, but prefixed with
+    /// `prefix` like in `Self::from_prefixed()`.
+    pub fn generate_prefixed(prefix: &str) -> Self {
+        Self::from_prefixed(prefix, &generate_unique_str())
+    }
+
+    /// Whether this was created with the given `prefix` through
+    /// `Self::from_prefixed()`.
+    pub fn has_prefix(&self, prefix: &str) -> bool {
+        self.0
+            .strip_prefix(prefix)
+            .is_some_and(|rest| rest.starts_with('-'))
     }
 }
 
+fn generate_unique_str() -> String {
+    format!("{:x}-{:x}", glib::real_time(), glib::random_int())
+}
+
 pub struct UidCodec;
 
 impl heed::BytesEncode<'_> for UidCodec {
@@ -68,6 +86,16 @@ mod test {
         }
     }
 
+    #[test]
+    fn has_prefix() {
+        assert!(Uid::from_prefixed("AudD", "a").has_prefix("AudD"));
+        assert!(Uid::generate_prefixed("Manual").has_prefix("Manual"));
+        assert!(!Uid::from_prefixed("AudD", "a").has_prefix("Manual"));
+        assert!(!Uid::from_prefixed("AudDx", "a").has_prefix("AudD"));
+        assert!(!Uid::from("AudD").has_prefix("AudD"));
+        assert!(!Uid::generate().has_prefix(""));
+    }
+
     #[test]
     fn equality() {
         assert_eq!(Uid::from("A"), Uid::from("A"));
@@ -1,4 +1,4 @@
-use std::{collections::BTreeSet, future::Future};
+use std::{collections::BTreeSet, future::Future, time::Duration};
 
 use gtk::glib;
 
@@ -50,10 +50,28 @@ pub fn consecutive_groups(ordered_set: &BTreeSet<usize>) -> Vec<(usize, usize)>
     ret
 }
 
+/// Formats the duration as `m:ss`, or `h:mm:ss` if it is at least an hour.
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn format_duration_formats() {
+        assert_eq!(format_duration(Duration::ZERO), "0:00");
+        assert_eq!(format_duration(Duration::from_secs(62)), "1:02");
+        assert_eq!(format_duration(Duration::from_millis(237_247)), "3:57");
+        assert_eq!(format_duration(Duration::from_secs(3605)), "1:00:05");
+    }
+
     #[test]
     fn consecutive_groups_empty() {
         assert_eq!(consecutive_groups(&BTreeSet::new()), vec![]);
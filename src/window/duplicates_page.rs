@@ -0,0 +1,294 @@
+use adw::{prelude::*, subclass::prelude::*};
+use anyhow::Result;
+use gettextrs::gettext;
+use gtk::{
+    gio,
+    glib::{self, WeakRef, clone, closure_local},
+};
+
+use std::cell::RefCell;
+
+use crate::{
+    Application, song::Song, song_clips::SongClips, song_duplicates, song_list::SongList, utils,
+};
+
+mod imp {
+    use glib::subclass::Signal;
+    use once_cell::sync::Lazy;
+
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/seadve/Mousai/ui/duplicates_page.ui")]
+    pub struct DuplicatesPage {
+        #[template_child]
+        pub(super) stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub(super) empty_page: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        pub(super) groups_page: TemplateChild<adw::PreferencesPage>,
+
+        pub(super) song_list: RefCell<Option<(WeakRef<SongList>, glib::SignalHandlerId)>>, // SongList and SongList's items changed handler id
+        pub(super) song_clips: RefCell<Option<SongClips>>,
+
+        pub(super) groups: RefCell<Vec<adw::PreferencesGroup>>,
+        pub(super) update_groups_handle: RefCell<Option<glib::JoinHandle<()>>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for DuplicatesPage {
+        const NAME: &'static str = "MousaiDuplicatesPage";
+        type Type = super::DuplicatesPage;
+        type ParentType = adw::NavigationPage;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for DuplicatesPage {
+        fn dispose(&self) {
+            self.obj().unbind_song_list();
+
+            if let Some(handle) = self.update_groups_handle.take() {
+                handle.abort();
+            }
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    Signal::builder("song-activated")
+                        .param_types([Song::static_type()])
+                        .build(),
+                ]
+            });
+
+            SIGNALS.as_ref()
+        }
+    }
+
+    impl WidgetImpl for DuplicatesPage {}
+    impl NavigationPageImpl for DuplicatesPage {}
+}
+
+glib::wrapper! {
+    pub struct DuplicatesPage(ObjectSubclass<imp::DuplicatesPage>)
+        @extends gtk::Widget, adw::NavigationPage,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl DuplicatesPage {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    pub fn connect_song_activated<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &Song) + 'static,
+    {
+        self.connect_closure(
+            "song-activated",
+            false,
+            closure_local!(|obj: &Self, song: &Song| {
+                f(obj, song);
+            }),
+        )
+    }
+
+    /// The duplicates are found again whenever the list changes, so merged or
+    /// removed songs disappear from the page.
+    pub fn bind_song_list(&self, song_list: &SongList) {
+        let handler_id = song_list.connect_items_changed(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_, _index, _removed, _added| {
+                obj.queue_update_groups();
+            }
+        ));
+
+        self.imp()
+            .song_list
+            .replace(Some((song_list.downgrade(), handler_id)));
+
+        self.queue_update_groups();
+    }
+
+    pub fn unbind_song_list(&self) {
+        if let Some((song_list, handler_id)) = self.imp().song_list.take()
+            && let Some(song_list) = song_list.upgrade()
+        {
+            song_list.disconnect(handler_id);
+        }
+    }
+
+    /// Must only be called once.
+    pub fn bind_song_clips(&self, song_clips: &SongClips) {
+        self.imp().song_clips.replace(Some(song_clips.clone()));
+    }
+
+    fn song_list(&self) -> Option<SongList> {
+        self.imp()
+            .song_list
+            .borrow()
+            .as_ref()
+            .and_then(|(song_list, _)| song_list.upgrade())
+    }
+
+    fn merge(&self, songs: &[Song]) {
+        let Some(song_list) = self.song_list() else {
+            return;
+        };
+
+        let (song, duplicates) = songs.split_first().expect("group must not be empty");
+        let duplicate_ids = duplicates
+            .iter()
+            .map(|duplicate| duplicate.id_ref())
+            .collect::<Vec<_>>();
+
+        let removed = match song_list.merge(song.id_ref(), &duplicate_ids) {
+            Ok(removed) => removed,
+            Err(err) => {
+                tracing::error!("Failed to merge songs: {:?}", err);
+                Application::get().add_message_toast(&gettext("Failed to merge songs"));
+                return;
+            }
+        };
+
+        if let Some(song_clips) = self.imp().song_clips.borrow().as_ref() {
+            for duplicate in &removed {
+                if let Err(err) = song_clips.transfer(duplicate.id_ref(), song.id_ref()) {
+                    tracing::warn!("Failed to transfer song clip: {:?}", err);
+                }
+            }
+        }
+
+        Application::get().add_message_toast(&gettext("Songs merged"));
+    }
+
+    /// Finds the duplicates again off the main thread, replacing the update
+    /// that is still running, if any.
+    fn queue_update_groups(&self) {
+        let imp = self.imp();
+
+        if let Some(handle) = imp.update_groups_handle.take() {
+            handle.abort();
+        }
+
+        let handle = utils::spawn(
+            glib::Priority::DEFAULT_IDLE,
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                async move {
+                    if let Err(err) = obj.update_groups().await {
+                        tracing::error!("Failed to find duplicates: {:?}", err);
+                    }
+                    obj.imp().update_groups_handle.replace(None);
+                }
+            ),
+        );
+        imp.update_groups_handle.replace(Some(handle));
+    }
+
+    async fn update_groups(&self) -> Result<()> {
+        let imp = self.imp();
+
+        let Some(song_list) = self.song_list() else {
+            return Ok(());
+        };
+
+        // Only the fields needed to find the duplicates are decoded, and the
+        // songs are only read for the duplicates that are shown.
+        let summaries = song_list.summaries().await?;
+        let duplicates = gio::spawn_blocking(move || song_duplicates::find_duplicates(&summaries))
+            .await
+            .unwrap_or_else(|panic| std::panic::resume_unwind(panic));
+
+        for group in imp.groups.take() {
+            imp.groups_page.remove(&group);
+        }
+
+        for song_ids in duplicates {
+            let songs = song_ids
+                .iter()
+                .filter_map(|song_id| song_list.get(song_id))
+                .collect::<Vec<_>>();
+
+            // The songs may have been removed while finding the duplicates
+            if songs.len() < 2 {
+                continue;
+            }
+
+            let group = self.create_group(songs);
+            imp.groups_page.add(&group);
+            imp.groups.borrow_mut().push(group);
+        }
+
+        if imp.groups.borrow().is_empty() {
+            imp.stack.set_visible_child(&*imp.empty_page);
+        } else {
+            imp.stack.set_visible_child(&*imp.groups_page);
+        }
+
+        Ok(())
+    }
+
+    fn create_group(&self, songs: Vec<Song>) -> adw::PreferencesGroup {
+        let group = adw::PreferencesGroup::builder()
+            .title(glib::markup_escape_text(&songs[0].artist_title_text()))
+            .build();
+
+        for song in songs.iter().cloned() {
+            let row = adw::ActionRow::builder()
+                .title(glib::markup_escape_text(&song.title()))
+                .subtitle(glib::markup_escape_text(&row_subtitle(&song)))
+                .activatable(true)
+                .build();
+            row.add_suffix(&gtk::Image::from_icon_name("go-next-symbolic"));
+            row.connect_activated(clone!(
+                #[weak(rename_to = obj)]
+                self,
+                #[strong]
+                song,
+                move |_| {
+                    obj.emit_by_name::<()>("song-activated", &[&song]);
+                }
+            ));
+            group.add(&row);
+        }
+
+        let merge_button = gtk::Button::builder()
+            .label(gettext("_Merge"))
+            .use_underline(true)
+            .valign(gtk::Align::Center)
+            .build();
+        merge_button.connect_clicked(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.merge(&songs);
+            }
+        ));
+        group.set_header_suffix(Some(&merge_button));
+
+        group
+    }
+}
+
+fn row_subtitle(song: &Song) -> String {
+    let last_heard = song.last_heard().map_or_else(
+        || gettext("Unknown"),
+        |last_heard| last_heard.to_local().fuzzy_display().to_string(),
+    );
+
+    [song.artist(), song.album(), last_heard]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" · ")
+}
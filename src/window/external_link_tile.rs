@@ -1,10 +1,19 @@
 use gettextrs::gettext;
-use gtk::{gio, glib, prelude::*, subclass::prelude::*};
+use gtk::{
+    gio,
+    glib::{self, clone},
+    prelude::*,
+    subclass::prelude::*,
+};
 
-use std::{cell::OnceCell, str::FromStr};
+use std::{
+    cell::{OnceCell, RefCell},
+    str::FromStr,
+};
 
 use crate::{
     Application, external_link::ExternalLink, external_links::ExternalLinkKey, i18n::gettext_f,
+    link_template::LinkTemplate, song::Song,
 };
 
 mod imp {
@@ -14,12 +23,17 @@ mod imp {
     #[properties(wrapper_type = super::ExternalLinkTile)]
     #[template(resource = "/io/github/seadve/Mousai/ui/external_link_tile.ui")]
     pub struct ExternalLinkTile {
-        /// Link shown by Self
+        /// Link shown by Self, or `None` if Self shows a user-defined link
         #[property(get, set, construct_only)]
-        pub(super) external_link: OnceCell<ExternalLink>,
+        pub(super) external_link: RefCell<Option<ExternalLink>>,
 
+        #[template_child]
+        pub(super) icon: TemplateChild<gtk::Image>,
         #[template_child]
         pub(super) label: TemplateChild<gtk::Label>,
+
+        /// Name and URI of the user-defined link shown by Self
+        pub(super) link_template_name_and_uri: OnceCell<(String, String)>,
     }
 
     #[glib::object_subclass]
@@ -44,7 +58,9 @@ mod imp {
 
             let obj = self.obj();
 
-            let link = obj.external_link();
+            let Some(link) = obj.external_link() else {
+                return;
+            };
             let raw_key = link.key();
 
             let Ok(key) = ExternalLinkKey::from_str(raw_key) else {
@@ -65,6 +81,11 @@ mod imp {
                     obj.set_tooltip_text(Some(&gettext("Browse on AudD")));
                     obj.add_css_class("audd");
                 }
+                ExternalLinkKey::MusicBrainzUrl => {
+                    self.label.set_label(&gettext("MusicBrainz"));
+                    obj.set_tooltip_text(Some(&gettext("Browse on MusicBrainz")));
+                    obj.add_css_class("musicbrainz");
+                }
                 ExternalLinkKey::SpotifyUrl => {
                     self.label.set_label(&gettext("Spotify"));
                     obj.set_tooltip_text(Some(&gettext("Listen on Spotify")));
@@ -96,6 +117,29 @@ impl ExternalLinkTile {
             .build()
     }
 
+    pub fn for_link_template(link_template: &LinkTemplate, song: &Song) -> Self {
+        let this = glib::Object::new::<Self>();
+
+        let imp = this.imp();
+        imp.label.set_label(&link_template.name);
+        if let Some(ref icon_name) = link_template.icon_name {
+            imp.icon.set_icon_name(Some(icon_name));
+            imp.icon.set_visible(true);
+        }
+        this.set_tooltip_text(Some(&gettext_f(
+            // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+            "Open {name}",
+            &[("name", &link_template.name)],
+        )));
+        this.add_css_class("custom");
+
+        imp.link_template_name_and_uri
+            .set((link_template.name.clone(), link_template.uri_for(song)))
+            .unwrap();
+
+        this
+    }
+
     pub fn can_handle(link: &ExternalLink) -> bool {
         ExternalLinkKey::from_str(link.key())
             .inspect_err(|_| {
@@ -105,29 +149,55 @@ impl ExternalLinkTile {
     }
 
     pub fn handle_activation(&self) {
-        let link = self.external_link();
-        let raw_key = link.key().to_string();
-        let raw_value = link.value();
-
-        let Ok(key) = ExternalLinkKey::from_str(&raw_key) else {
-            unreachable!(
-                "external link tile with an unhandleable key `{}` should not have been constructed and thus activated",
-                raw_key
-            );
+        let (raw_key, uri, app_uri) = if let Some(link) = self.external_link() {
+            let uri = link
+                .web_uri()
+                .expect("external link tile should not be constructed with an unhandleable key");
+            let app_uri = ExternalLinkKey::from_str(link.key())
+                .ok()
+                .filter(|&key| prefers_app(key))
+                .and_then(|_| link.native_uri())
+                .filter(|app_uri| has_uri_handler(app_uri));
+            (link.key().to_string(), uri, app_uri)
+        } else {
+            let (name, uri) = self
+                .imp()
+                .link_template_name_and_uri
+                .get()
+                .cloned()
+                .expect("tile must either have an external link or a link template");
+            (name, uri, None)
         };
 
-        let uri = match key {
-            ExternalLinkKey::AppleMusicUrl
-            | ExternalLinkKey::AudDUrl
-            | ExternalLinkKey::SpotifyUrl => raw_value.to_string(),
-            ExternalLinkKey::YoutubeSearchTerm => {
-                format!(
-                    "https://www.youtube.com/results?search_query={}",
-                    glib::Uri::escape_string(raw_value, None, true)
-                )
-            }
-        };
+        if let Some(app_uri) = app_uri {
+            // Fall back to the browser if the app fails to open the link
+            self.launch_uri(
+                app_uri.clone(),
+                clone!(
+                    #[weak(rename_to = obj)]
+                    self,
+                    move || {
+                        tracing::debug!("Falling back to web uri for `{}`", app_uri);
+                        obj.launch_uri_or_notify(uri, raw_key);
+                    }
+                ),
+            );
+        } else {
+            self.launch_uri_or_notify(uri, raw_key);
+        }
+    }
+
+    fn launch_uri_or_notify(&self, uri: String, raw_key: String) {
+        self.launch_uri(uri, move || {
+            Application::get().add_message_toast(&gettext_f(
+                // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+                "Failed to launch {key}",
+                &[("key", &raw_key)],
+            ));
+        });
+    }
 
+    fn launch_uri(&self, uri: String, on_error: impl FnOnce() + 'static) {
         if let Err(err) = glib::Uri::is_valid(&uri, glib::UriFlags::ENCODED) {
             tracing::warn!("Trying to launch an invalid Uri: {:?}", err);
         }
@@ -140,13 +210,27 @@ impl ExternalLinkTile {
             move |res| {
                 if let Err(err) = res {
                     tracing::warn!("Failed to launch default for uri `{}`: {:?}", uri, err);
-                    Application::get().add_message_toast(&gettext_f(
-                        // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
-                        "Failed to launch {key}",
-                        &[("key", &raw_key)],
-                    ));
+                    on_error();
                 }
             },
         );
     }
 }
+
+/// Whether the user prefers to open links of the key in the service's own app
+fn prefers_app(key: ExternalLinkKey) -> bool {
+    let settings = Application::get().settings();
+
+    match key {
+        ExternalLinkKey::AppleMusicUrl => settings.open_apple_music_links_in_app(),
+        ExternalLinkKey::SpotifyUrl => settings.open_spotify_links_in_app(),
+        ExternalLinkKey::AudDUrl
+        | ExternalLinkKey::MusicBrainzUrl
+        | ExternalLinkKey::YoutubeSearchTerm => false,
+    }
+}
+
+fn has_uri_handler(uri: &str) -> bool {
+    glib::Uri::peek_scheme(uri)
+        .is_some_and(|scheme| gio::AppInfo::default_for_uri_scheme(&scheme).is_some())
+}
@@ -6,22 +6,29 @@ use gtk::{
     subclass::prelude::*,
 };
 
-use std::cell::{Cell, OnceCell, RefCell};
+use std::{
+    cell::{Cell, OnceCell, RefCell},
+    rc::Rc,
+};
 
 use crate::{
     Application,
     config::APP_ID,
+    date_time::DateTime,
     i18n::ngettext_f,
     player::Player,
-    recognizer::Recognizer,
+    recognizer::{Candidate, Recognizer},
     song::Song,
+    song_clips::SongClips,
     song_filter::SongFilter,
     song_list::SongList,
     song_sorter::SongSorter,
+    song_trash::SongTrash,
     uid::Uid,
     window::{
-        AdaptiveMode, lyrics_page::LyricsPage, recognized_page::RecognizedPage,
-        recognizer_status::RecognizerStatus, song_page::SongPage, song_tile::SongTile,
+        AdaptiveMode, duplicates_page::DuplicatesPage, lyrics_page::LyricsPage,
+        recognized_page::RecognizedPage, recognizer_status::RecognizerStatus, song_page::SongPage,
+        song_tile::SongTile, trash_page::TrashPage,
     },
 };
 
@@ -38,6 +45,9 @@ const RECOGNIZED_PAGE_SONG_ACTIVATED_HANDLER_ID_KEY: &str =
 const RECOGNIZED_PAGE_ADAPTIVE_MODE_BINDING_KEY: &str =
     "mousai-recognized-page-adaptive-mode-binding";
 
+const DUPLICATES_PAGE_SONG_ACTIVATED_HANDLER_ID_KEY: &str =
+    "mousai-duplicates-page-song-activated-handler-id";
+
 const GRID_LIST_ITEM_BINDINGS_KEY: &str = "mousai-grid-list-item-bindings";
 const GRID_LIST_ITEM_EXPRESSION_WATCHES_KEY: &str = "mousai-grid-list-item-expression-watches";
 
@@ -87,12 +97,16 @@ mod imp {
         #[template_child]
         pub(super) grid: TemplateChild<gtk::GridView>,
         #[template_child]
+        pub(super) content_loading_page: TemplateChild<adw::StatusPage>,
+        #[template_child]
         pub(super) content_empty_page: TemplateChild<adw::StatusPage>,
         #[template_child]
         pub(super) content_empty_search_result_page: TemplateChild<adw::StatusPage>,
 
         pub(super) player: OnceCell<WeakRef<Player>>,
         pub(super) song_list: OnceCell<WeakRef<SongList>>,
+        pub(super) song_clips: OnceCell<SongClips>,
+        pub(super) song_trash: OnceCell<SongTrash>,
         pub(super) filter_model: OnceCell<WeakRef<gtk::FilterListModel>>,
         pub(super) selection_model: OnceCell<WeakRef<gtk::MultiSelection>>,
 
@@ -121,6 +135,14 @@ mod imp {
                 obj.set_selection_mode_active(!is_selection_mode_active);
             });
 
+            klass.install_action("history-view.show-duplicates", None, |obj, _, _| {
+                obj.push_duplicates_page();
+            });
+
+            klass.install_action("history-view.show-trash", None, |obj, _, _| {
+                obj.push_trash_page();
+            });
+
             klass.install_action("history-view.select-all", None, |obj, _, _| {
                 obj.select_all();
             });
@@ -293,26 +315,107 @@ impl HistoryView {
         recognized_page.bind_player(&self.player());
         recognized_page.bind_songs(songs);
 
+        self.push_recognized_page_inner(
+            &recognized_page,
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |_, song| {
+                    obj.push_song_page(song);
+                }
+            ),
+        );
+    }
+
+    /// Pushes a `RecognizedPage` for choosing among the given candidates to
+    /// the navigation stack.
+    ///
+    /// Once a candidate is chosen, `on_chosen` is called with its song, then
+    /// the page is replaced with a `SongPage` for it. If the page is popped
+    /// without a choice instead, `on_dismissed` is called.
+    pub fn push_candidates_page(
+        &self,
+        candidates: &[Candidate],
+        on_chosen: impl Fn(&Song) + 'static,
+        on_dismissed: impl Fn() + 'static,
+    ) {
+        let imp = self.imp();
+
+        let recognized_page = RecognizedPage::new();
+        recognized_page.bind_player(&self.player());
+        recognized_page.bind_candidates(candidates);
+
+        let is_chosen = Rc::new(Cell::new(false));
+
+        let popped_handler_id = Rc::new(RefCell::new(None));
+        let handler_id = imp.navigation_view.connect_popped(clone!(
+            #[weak]
+            recognized_page,
+            #[strong]
+            is_chosen,
+            #[strong]
+            popped_handler_id,
+            move |navigation_view, page| {
+                if page != recognized_page.upcast_ref::<adw::NavigationPage>() {
+                    return;
+                }
+
+                if !is_chosen.get() {
+                    on_dismissed();
+                }
+
+                if let Some(handler_id) = popped_handler_id.take() {
+                    navigation_view.disconnect(handler_id);
+                }
+            }
+        ));
+        popped_handler_id.replace(Some(handler_id));
+
+        self.push_recognized_page_inner(
+            &recognized_page,
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |recognized_page, song| {
+                    let imp = obj.imp();
+
+                    // Only the first choice counts, as the page is being
+                    // replaced afterwards.
+                    if imp.navigation_view.visible_page().as_ref()
+                        != Some(recognized_page.upcast_ref())
+                    {
+                        return;
+                    }
+
+                    is_chosen.set(true);
+                    on_chosen(song);
+
+                    imp.navigation_view.pop();
+                    obj.push_song_page(song);
+                }
+            ),
+        );
+    }
+
+    fn push_recognized_page_inner(
+        &self,
+        recognized_page: &RecognizedPage,
+        on_song_activated: impl Fn(&RecognizedPage, &Song) + 'static,
+    ) {
         unsafe {
             recognized_page.set_data(
                 RECOGNIZED_PAGE_SONG_ACTIVATED_HANDLER_ID_KEY,
-                recognized_page.connect_song_activated(clone!(
-                    #[weak(rename_to = obj)]
-                    self,
-                    move |_, song| {
-                        obj.push_song_page(song);
-                    }
-                )),
+                recognized_page.connect_song_activated(on_song_activated),
             );
             recognized_page.set_data(
                 RECOGNIZED_PAGE_ADAPTIVE_MODE_BINDING_KEY,
-                self.bind_property("adaptive-mode", &recognized_page, "adaptive-mode")
+                self.bind_property("adaptive-mode", recognized_page, "adaptive-mode")
                     .sync_create()
                     .build(),
             );
         }
 
-        imp.navigation_view.push(&recognized_page);
+        self.imp().navigation_view.push(recognized_page);
     }
 
     /// Pushes a `SongPage` for the given song to the navigation stack.
@@ -339,6 +442,7 @@ impl HistoryView {
         let song_page = SongPage::new();
         song_page.bind_player(&self.player());
         song_page.bind_song_list(&self.song_list());
+        song_page.bind_song_clips(self.song_clips());
         song_page.set_song(song);
 
         unsafe {
@@ -437,6 +541,60 @@ impl HistoryView {
         imp.navigation_view.push(&lyrics_page);
     }
 
+    /// Pushes a `DuplicatesPage` for reviewing possible duplicates in the
+    /// history to the navigation stack.
+    pub fn push_duplicates_page(&self) {
+        let imp = self.imp();
+
+        // Return if the last widget is already a `DuplicatesPage`
+        if imp
+            .navigation_view
+            .visible_page()
+            .is_some_and(|visible_page| visible_page.is::<DuplicatesPage>())
+        {
+            return;
+        }
+
+        let duplicates_page = DuplicatesPage::new();
+        duplicates_page.bind_song_list(&self.song_list());
+        duplicates_page.bind_song_clips(self.song_clips());
+
+        unsafe {
+            duplicates_page.set_data(
+                DUPLICATES_PAGE_SONG_ACTIVATED_HANDLER_ID_KEY,
+                duplicates_page.connect_song_activated(clone!(
+                    #[weak(rename_to = obj)]
+                    self,
+                    move |_, song| {
+                        obj.push_song_page(song);
+                    }
+                )),
+            );
+        }
+
+        imp.navigation_view.push(&duplicates_page);
+    }
+
+    /// Pushes a `TrashPage` for restoring removed songs to the navigation
+    /// stack.
+    pub fn push_trash_page(&self) {
+        let imp = self.imp();
+
+        // Return if the last widget is already a `TrashPage`
+        if imp
+            .navigation_view
+            .visible_page()
+            .is_some_and(|visible_page| visible_page.is::<TrashPage>())
+        {
+            return;
+        }
+
+        let trash_page = TrashPage::new();
+        trash_page.bind_models(&self.song_list(), self.song_trash());
+
+        imp.navigation_view.push(&trash_page);
+    }
+
     /// Returns true if a page has been popped
     pub fn pop_page(&self) -> bool {
         self.imp().navigation_view.pop()
@@ -447,6 +605,22 @@ impl HistoryView {
         self.imp().player.set(player.downgrade()).unwrap();
     }
 
+    /// Must only be called once
+    pub fn bind_song_clips(&self, song_clips: &SongClips) {
+        self.imp()
+            .song_clips
+            .set(song_clips.clone())
+            .unwrap_or_else(|_| panic!("song clips must be bound only once"));
+    }
+
+    /// Must only be called once
+    pub fn bind_song_trash(&self, song_trash: &SongTrash) {
+        self.imp()
+            .song_trash
+            .set(song_trash.clone())
+            .unwrap_or_else(|_| panic!("song trash must be bound only once"));
+    }
+
     /// Must only be called once
     pub fn bind_song_list(&self, song_list: &SongList) {
         let imp = self.imp();
@@ -458,11 +632,23 @@ impl HistoryView {
                 obj.update_content_stack_visible_child();
             }
         ));
+        song_list.connect_is_loading_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.update_content_stack_visible_child();
+            }
+        ));
 
         let filter = SongFilter::new();
         let sorter = SongSorter::new();
 
-        let filter_model = gtk::FilterListModel::new(Some(song_list.clone()), Some(filter.clone()));
+        // Recent songs are already sorted by last heard, which is what the
+        // sorter sorts by without a search, and are only created when
+        // accessed. So, it is only sorted while searching, as the sort model
+        // would otherwise create and keep all songs.
+        let filter_model =
+            gtk::FilterListModel::new(Some(song_list.recent_songs()), Some(filter.clone()));
         filter_model.connect_items_changed(clone!(
             #[weak(rename_to = obj)]
             self,
@@ -471,23 +657,31 @@ impl HistoryView {
             }
         ));
 
+        let sort_model = gtk::SortListModel::new(Some(filter_model.clone()), None::<SongSorter>);
+
         imp.search_entry.connect_search_changed(clone!(
             #[weak(rename_to = obj)]
             self,
             #[weak]
             filter,
             #[weak]
-            sorter,
+            sort_model,
             move |search_entry| {
                 let text = search_entry.text();
-                filter.set_search(text.trim());
-                sorter.set_search(text.trim());
+                let search = text.trim();
+                filter.set_search(search);
+
+                if search.is_empty() {
+                    sort_model.set_sorter(None::<&SongSorter>);
+                } else {
+                    sorter.set_search(search);
+                    sort_model.set_sorter(Some(&sorter));
+                }
+
                 obj.update_content_stack_visible_child();
             }
         ));
 
-        let sort_model = gtk::SortListModel::new(Some(filter_model.clone()), Some(sorter));
-
         // FIXME save selection even when the song are filtered from FilterListModel
         let selection_model = gtk::MultiSelection::new(Some(sort_model));
         selection_model.connect_selection_changed(clone!(
@@ -580,6 +774,20 @@ impl HistoryView {
             .expect("player must not be dropped")
     }
 
+    fn song_clips(&self) -> &SongClips {
+        self.imp()
+            .song_clips
+            .get()
+            .expect("song clips must be bound")
+    }
+
+    fn song_trash(&self) -> &SongTrash {
+        self.imp()
+            .song_trash
+            .get()
+            .expect("song trash must be bound")
+    }
+
     fn song_list(&self) -> SongList {
         self.imp()
             .song_list
@@ -589,19 +797,31 @@ impl HistoryView {
             .expect("song list must not be dropped")
     }
 
-    /// Adds songs with ids given to purgatory, and add `SongPage`s that contain them to the purgatory.
+    /// Moves the songs with ids given to the trash, and adds them to the
+    /// purgatory, so they can be restored from the undo toast.
     fn remove_songs(&self, song_ids: &[&Uid]) -> Result<()> {
         let imp = self.imp();
 
-        let mut removed_songs = self
-            .song_list()
-            .remove_many(song_ids)
-            .context("Failed to remove songs from history")?;
+        let song_list = self.song_list();
+
+        let songs = song_ids
+            .iter()
+            .filter_map(|song_id| song_list.get(song_id))
+            .collect::<Vec<_>>();
         debug_assert_eq!(
-            removed_songs.len(),
+            songs.len(),
             song_ids.len(),
-            "all corresponding songs of the ids must be removed"
+            "all corresponding songs of the ids must be in the history"
         );
+
+        // Trash first, so the songs are not lost if it fails
+        self.song_trash()
+            .insert_many(&songs, &DateTime::now_utc())
+            .context("Failed to move songs to trash")?;
+
+        let mut removed_songs = song_list
+            .remove_many(song_ids)
+            .context("Failed to remove songs from history")?;
         imp.songs_purgatory.borrow_mut().append(&mut removed_songs);
 
         Ok(())
@@ -673,10 +893,7 @@ impl HistoryView {
                 #[weak(rename_to = obj)]
                 self,
                 move |_| {
-                    if let Err(err) = obj
-                        .song_list()
-                        .insert_many(obj.imp().songs_purgatory.take())
-                    {
+                    if let Err(err) = obj.undo_remove_songs() {
                         tracing::error!("Failed to undo remove song: {:?}", err);
                         Application::get().add_message_toast(&gettext("Failed to undo"));
                     }
@@ -688,7 +905,9 @@ impl HistoryView {
                 self,
                 move |_| {
                     let imp = obj.imp();
-                    imp.songs_purgatory.borrow_mut().clear();
+
+                    // The songs can still be restored from the trash.
+                    imp.songs_purgatory.take();
                     imp.undo_remove_song_toast.take();
                 }
             ));
@@ -703,8 +922,8 @@ impl HistoryView {
             let n_removed = imp.songs_purgatory.borrow().len();
             toast.set_title(&ngettext_f(
                 // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
-                "Removed {n_removed} song",
-                "Removed {n_removed} songs",
+                "Moved {n_removed} song to trash",
+                "Moved {n_removed} songs to trash",
                 n_removed as u32,
                 &[("n_removed", &n_removed.to_string())],
             ));
@@ -714,12 +933,28 @@ impl HistoryView {
         }
     }
 
+    /// Moves the songs in the purgatory back from the trash to the history.
+    fn undo_remove_songs(&self) -> Result<()> {
+        let songs = self.imp().songs_purgatory.take();
+        let song_ids = songs.iter().map(|song| song.id_ref()).collect::<Vec<_>>();
+
+        self.song_list()
+            .insert_many(songs.clone())
+            .context("Failed to insert songs to history")?;
+        self.song_trash()
+            .take_many(&song_ids)
+            .context("Failed to take songs from trash")?;
+
+        Ok(())
+    }
+
     fn show_recognizer_results(&self, recognizer: &Recognizer) -> Result<()> {
         let song_list = self.song_list();
 
-        let songs = recognizer
+        let recordings = recognizer
             .take_recognized_saved_recordings()
-            .context("Failed to take recognized saved recordings")?
+            .context("Failed to take recognized saved recordings")?;
+        let songs = recordings
             .iter()
             .filter_map(
                 |recording| match recording.recognize_result().map(|r| r.0) {
@@ -742,7 +977,14 @@ impl HistoryView {
             return Ok(());
         }
 
-        for song in &songs {
+        // Songs recorded in incognito mode are only shown.
+        let songs_to_insert = recordings
+            .iter()
+            .filter(|recording| !recording.is_incognito())
+            .filter_map(|recording| recording.recognize_result()?.0.ok())
+            .collect::<Vec<_>>();
+
+        for song in &songs_to_insert {
             // If the song is not found in the history, set it as newly heard
             // (That's why an always true value is used after `or`). If it is in the
             // history and it was newly heard, pass that state to the new value.
@@ -755,7 +997,7 @@ impl HistoryView {
         }
 
         song_list
-            .insert_many(songs.clone())
+            .insert_many(songs_to_insert)
             .context("Failed to insert songs to history")?;
 
         self.push_recognized_page(&songs);
@@ -789,7 +1031,17 @@ impl HistoryView {
 
         let search_text = imp.search_entry.text();
 
+        // The song list is only bound once the db is opened, and it is
+        // populated progressively afterwards
         if imp
+            .song_list
+            .get()
+            .and_then(|song_list| song_list.upgrade())
+            .is_none_or(|song_list| song_list.is_loading() && song_list.n_items() == 0)
+        {
+            imp.content_stack
+                .set_visible_child(&imp.content_loading_page.get());
+        } else if imp
             .filter_model
             .get()
             .and_then(|filter_model| filter_model.upgrade())
@@ -995,6 +1247,16 @@ fn unbind_page(page: &adw::NavigationPage) {
             adaptive_mode_binding.unbind();
         }
         recognized_page.unbind_player();
+    } else if let Some(duplicates_page) = page.downcast_ref::<DuplicatesPage>() {
+        unsafe {
+            let song_activated_handler_id = duplicates_page
+                .steal_data::<glib::SignalHandlerId>(DUPLICATES_PAGE_SONG_ACTIVATED_HANDLER_ID_KEY)
+                .unwrap();
+            duplicates_page.disconnect(song_activated_handler_id);
+        }
+        duplicates_page.unbind_song_list();
+    } else if page.downcast_ref::<TrashPage>().is_some() {
+        // Nothing to unbind
     } else if page.downcast_ref::<LyricsPage>().is_some() {
         // Nothing to unbind
     } else {
@@ -1013,7 +1275,7 @@ mod test {
 
     use std::sync::Once;
 
-    use crate::{RESOURCES_FILE, database};
+    use crate::{RESOURCES_FILE, database, song::test::new_test_song};
 
     static GRESOURCES_INIT: Once = Once::new();
 
@@ -1024,10 +1286,6 @@ mod test {
         });
     }
 
-    fn new_test_song(id: &str) -> Song {
-        Song::builder(&Uid::from(id), id, id, id).build()
-    }
-
     #[track_caller]
     fn assert_navigation_stack_n_pages(view: &HistoryView, expected_n_pages: u32) {
         assert_eq!(
@@ -1,4 +1,5 @@
 mod album_cover;
+mod duplicates_page;
 mod external_link_tile;
 mod history_view;
 mod information_row;
@@ -10,8 +11,10 @@ mod recognized_page_tile;
 mod recognizer_status;
 mod recognizer_view;
 mod song_bar;
+mod song_edit_dialog;
 mod song_page;
 mod song_tile;
+mod trash_page;
 mod waveform;
 
 use adw::{prelude::*, subclass::prelude::*};
@@ -22,17 +25,29 @@ use gtk::{
     glib::{self, clone},
 };
 
-use std::cell::OnceCell;
+use std::{
+    cell::{OnceCell, RefCell},
+    time::Duration,
+};
 
-use self::{history_view::HistoryView, recognizer_view::RecognizerView, song_bar::SongBar};
+use self::{
+    history_view::HistoryView, recognizer_view::RecognizerView, song_bar::SongBar,
+    song_edit_dialog::SongEditDialog,
+};
 use crate::{
     Application,
     config::PROFILE,
+    date_time::DateTime,
+    i18n::ngettext_f,
+    listening_session::ListeningSessions,
     player::{Player, PlayerState},
     preferences_dialog::PreferencesDialog,
+    radio_monitor::RadioMonitor,
     recognizer::{RecognizeError, RecognizeErrorKind, Recognizer, RecognizerState, Recordings},
     song::Song,
+    song_clips::SongClips,
     song_list::SongList,
+    song_trash::SongTrash,
 };
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq, glib::Enum)]
@@ -64,7 +79,9 @@ mod imp {
 
         pub(super) player: Player,
         pub(super) recognizer: Recognizer,
+        pub(super) radio_monitor: RadioMonitor,
         pub(super) song_history: OnceCell<SongList>,
+        pub(super) listening_sessions: RefCell<ListeningSessions>,
     }
 
     #[glib::object_subclass]
@@ -103,6 +120,26 @@ mod imp {
                 }
             });
 
+            klass.install_action("win.add-song", None, |obj, _, _| {
+                let dialog = SongEditDialog::new_add();
+                dialog.connect_song_added(clone!(
+                    #[weak]
+                    obj,
+                    move |_, song| {
+                        if let Err(err) = obj.song_history().insert(song.clone()) {
+                            tracing::error!("Failed to add song to history: {:?}", err);
+                            obj.add_message_toast(&gettext("Failed to add song to history"));
+                            return;
+                        }
+
+                        let main_view = obj.imp().main_view.get();
+                        main_view.push_song_page(song);
+                        main_view.scroll_to_top();
+                    }
+                ));
+                dialog.present(Some(obj));
+            });
+
             klass.install_action("win.toggle-search", None, |obj, _, _| {
                 let search_bar = obj.imp().main_view.search_bar();
                 search_bar.set_search_mode(!search_bar.is_search_mode());
@@ -138,6 +175,7 @@ mod imp {
             obj.update_stack();
             obj.update_toggle_playback_action();
             obj.update_toggle_search_action();
+            obj.update_history_actions();
         }
     }
 
@@ -172,20 +210,51 @@ impl Window {
             .build()
     }
 
-    pub fn bind_models(&self, song_history: &SongList, recordings: &Recordings) {
+    pub fn bind_models(
+        &self,
+        song_history: &SongList,
+        recordings: &Recordings,
+        song_clips: &SongClips,
+        song_trash: &SongTrash,
+    ) {
         let imp = self.imp();
 
         imp.song_history
             .set(song_history.clone())
             .expect("song history must be bound only once");
+        song_history.connect_flush_failed(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.add_message_toast(&gettext("Failed to save changes to history"));
+            }
+        ));
+        song_history.connect_read_failed(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.add_message_toast(&gettext("Failed to load some songs from history"));
+            }
+        ));
 
         imp.main_view.bind_song_list(song_history);
+        imp.main_view.bind_song_clips(song_clips);
+        imp.main_view.bind_song_trash(song_trash);
         imp.recognizer.bind_saved_recordings(recordings);
+        imp.recognizer.bind_song_clips(song_clips);
 
         // Recognizer must have saved recordings first
         imp.main_view.bind_recognizer(&imp.recognizer);
         imp.recognizer_view.bind_recognizer(&imp.recognizer);
 
+        self.update_history_actions();
+
+        self.add_action(&gio::PropertyAction::new(
+            "toggle-incognito",
+            &imp.recognizer,
+            "is-incognito",
+        ));
+
         imp.recognizer.connect_state_notify(clone!(
             #[weak(rename_to = obj)]
             self,
@@ -197,21 +266,13 @@ impl Window {
             #[weak(rename_to = obj)]
             self,
             move |_, song| {
-                let history = obj.song_history();
-
-                // If the song is not found in the history, set it as newly heard
-                // (That's why an always true value is used after `or`). If it is in the
-                // history and it was newly heard, pass that state to the new value.
-                if history
-                    .get(song.id_ref())
-                    .is_none_or(|prev| prev.is_newly_heard())
-                {
-                    song.set_is_newly_heard(true);
+                if !obj.insert_recognized_song(song) {
+                    return;
                 }
 
-                if let Err(err) = history.insert(song.clone()) {
-                    tracing::error!("Failed to insert song to history: {:?}", err);
-                    obj.add_message_toast(&gettext("Failed to insert song to history"));
+                // Keep hearing the same song from flooding the UI.
+                if obj.record_listening_session(song) {
+                    tracing::debug!("Song was recognized again in the same listening session");
                     return;
                 }
 
@@ -220,6 +281,32 @@ impl Window {
                 main_view.scroll_to_top();
             }
         ));
+        imp.recognizer.connect_candidates_recognized(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_, candidates| {
+                let main_view = obj.imp().main_view.get();
+                main_view.push_candidates_page(
+                    candidates,
+                    clone!(
+                        #[weak]
+                        obj,
+                        move |song| {
+                            obj.imp().recognizer.choose_candidate(song);
+                            obj.insert_recognized_song(song);
+                        }
+                    ),
+                    clone!(
+                        #[weak]
+                        obj,
+                        move || {
+                            obj.imp().recognizer.dismiss_candidates();
+                        }
+                    ),
+                );
+                main_view.scroll_to_top();
+            }
+        ));
         imp.recognizer.connect_recording_saved(clone!(
             #[weak(rename_to = obj)]
             self,
@@ -227,6 +314,84 @@ impl Window {
                 obj.present_recording_saved_message(cause);
             }
         ));
+        imp.recognizer.connect_usage_limit_near(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_, remaining_calls| {
+                if remaining_calls == 0 {
+                    obj.add_message_toast(&gettext("Daily recognition limit reached"));
+                    return;
+                }
+
+                obj.add_message_toast(&ngettext_f(
+                    // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+                    "Only {n} recognition left today",
+                    "Only {n} recognitions left today",
+                    remaining_calls,
+                    &[("n", &remaining_calls.to_string())],
+                ));
+            }
+        ));
+
+        imp.radio_monitor.connect_song_recognized(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_, song| {
+                obj.insert_recognized_song(song);
+            }
+        ));
+        imp.radio_monitor
+            .bind_settings(Application::get().settings());
+    }
+
+    /// Inserts the song to the history, returning whether it succeeded.
+    ///
+    /// In incognito mode, the song is not inserted and this always succeeds.
+    fn insert_recognized_song(&self, song: &Song) -> bool {
+        if self.imp().recognizer.is_incognito() {
+            tracing::debug!("Incognito mode is active; not inserting song to history");
+            return true;
+        }
+
+        let history = self.song_history();
+
+        // If the song is not found in the history, set it as newly heard
+        // (That's why an always true value is used after `or`). If it is in the
+        // history and it was newly heard, pass that state to the new value.
+        if history
+            .get(song.id_ref())
+            .is_none_or(|prev| prev.is_newly_heard())
+        {
+            song.set_is_newly_heard(true);
+        }
+
+        // Keep the user's corrections when the song is heard again.
+        if let Some(prev) = history.get(song.id_ref())
+            && prev.is_edited()
+        {
+            song.edit(prev.metadata());
+        }
+
+        if let Err(err) = history.insert(song.clone()) {
+            tracing::error!("Failed to insert song to history: {:?}", err);
+            self.add_message_toast(&gettext("Failed to insert song to history"));
+            return false;
+        }
+
+        true
+    }
+
+    /// Records the recognition of the song to its listening session,
+    /// returning whether it is a repeat within the session.
+    fn record_listening_session(&self, song: &Song) -> bool {
+        let gap = Duration::from_secs(Application::get().settings().listening_session_gap().into());
+        let heard_time = song.last_heard().unwrap_or_else(DateTime::now_utc);
+
+        self.imp()
+            .listening_sessions
+            .borrow_mut()
+            .record(song.id_ref(), &heard_time, gap)
+            .is_repeat()
     }
 
     pub fn add_toast(&self, toast: adw::Toast) {
@@ -380,7 +545,10 @@ impl Window {
                         move |_, id| {
                             debug_assert_eq!(id, OPEN_RESPONSE_ID);
 
-                            let dialog = PreferencesDialog::new(Application::get().settings());
+                            let dialog = PreferencesDialog::new(
+                                Application::get().settings(),
+                                Application::get().credentials(),
+                            );
                             dialog.present(Some(&obj));
 
                             let is_focused = dialog.focus_aud_d_api_token_row();
@@ -428,6 +596,14 @@ impl Window {
         self.action_set_enabled("win.toggle-playback", self.imp().player.song().is_some());
     }
 
+    /// Actions that insert to the history are only enabled once the models are
+    /// bound, as the db is loaded after the window is shown.
+    fn update_history_actions(&self) {
+        let is_bound = self.imp().song_history.get().is_some();
+        self.action_set_enabled("win.toggle-recognize", is_bound);
+        self.action_set_enabled("win.add-song", is_bound);
+    }
+
     fn update_toggle_search_action(&self) {
         let imp = self.imp();
         let is_main_page_visible =
@@ -2,7 +2,7 @@ use adw::{
     prelude::*,
     subclass::{navigation_page::NavigationPageImpl, prelude::*},
 };
-use gettextrs::ngettext;
+use gettextrs::{gettext, ngettext};
 use gtk::{
     gdk,
     glib::{self, clone, closure_local},
@@ -11,7 +11,13 @@ use gtk::{
 use std::cell::{Cell, OnceCell, RefCell};
 
 use super::{AdaptiveMode, recognized_page_tile::RecognizedPageTile};
-use crate::{i18n::ngettext_f, player::Player, song::Song};
+use crate::{
+    i18n::{gettext_f, ngettext_f},
+    player::Player,
+    recognizer::Candidate,
+    song::Song,
+    utils,
+};
 
 mod imp {
     use glib::{WeakRef, subclass::Signal};
@@ -165,27 +171,56 @@ impl RecognizedPage {
             n_songs as u32,
         ));
 
-        let player = self
-            .imp()
+        for song in songs {
+            self.append_tile(song);
+        }
+    }
+
+    /// Shows the candidates, ranked from the most to the least likely match,
+    /// for the user to choose the song that was actually playing.
+    pub fn bind_candidates(&self, candidates: &[Candidate]) {
+        if candidates.is_empty() {
+            tracing::warn!("Tried to bind empty candidate list");
+        }
+
+        let imp = self.imp();
+
+        self.set_title(&gettext("Choose Song"));
+        imp.heading.set_label(&gettext("Multiple Possible Matches"));
+        imp.body.set_label(&gettext(
+            "Choose the song that was playing to add it to history",
+        ));
+
+        for candidate in candidates {
+            let tile = self.append_tile(&candidate.song);
+            tile.set_caption(&candidate_caption(candidate));
+        }
+    }
+
+    fn append_tile(&self, song: &Song) -> RecognizedPageTile {
+        let imp = self.imp();
+
+        let player = imp
             .player
             .get()
             .expect("player must be bound")
             .upgrade()
             .expect("player must not be dropped");
-        for song in songs {
-            let tile = RecognizedPageTile::new(song);
-            tile.bind_player(&player);
-            tile.connect_activated(clone!(
-                #[weak(rename_to = obj)]
-                self,
-                move |tile| {
-                    obj.emit_by_name::<()>("song-activated", &[&tile.song()]);
-                }
-            ));
-
-            imp.carousel.append(&tile);
-            imp.tiles.borrow_mut().push(tile);
-        }
+
+        let tile = RecognizedPageTile::new(song);
+        tile.bind_player(&player);
+        tile.connect_activated(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |tile| {
+                obj.emit_by_name::<()>("song-activated", &[&tile.song()]);
+            }
+        ));
+
+        imp.carousel.append(&tile);
+        imp.tiles.borrow_mut().push(tile.clone());
+
+        tile
     }
 
     fn go_to_tile_relative(&self, delta: i32) {
@@ -215,3 +250,26 @@ impl RecognizedPage {
         imp.carousel.set_spacing(spacing);
     }
 }
+
+fn candidate_caption(candidate: &Candidate) -> String {
+    let confidence = candidate.confidence.map(|confidence| {
+        gettext_f(
+            // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+            "{percent}% Match",
+            &[("percent", &format!("{:.0}", confidence * 100.0))],
+        )
+    });
+    let timecode = candidate.timecode.map(|timecode| {
+        gettext_f(
+            // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+            "At {timecode}",
+            &[("timecode", &utils::format_duration(timecode))],
+        )
+    });
+
+    match (confidence, timecode) {
+        (Some(confidence), Some(timecode)) => format!("{} · {}", confidence, timecode),
+        (Some(caption), None) | (None, Some(caption)) => caption,
+        (None, None) => gettext("Possible Match"),
+    }
+}
@@ -153,4 +153,15 @@ impl RecognizedPageTile {
     pub fn unbind_player(&self) {
         self.imp().song_tile.unbind_player();
     }
+
+    /// Shows the given caption instead of when the song was last heard.
+    pub fn set_caption(&self, caption: &str) {
+        let imp = self.imp();
+
+        if let Some(binding) = imp.binding.take() {
+            binding.unbind();
+        }
+
+        imp.last_heard_label.set_label(caption);
+    }
 }
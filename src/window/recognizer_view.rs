@@ -5,10 +5,16 @@ use gtk::{
     subclass::prelude::*,
 };
 
-use std::cell::OnceCell;
+use std::cell::{OnceCell, RefCell};
 
 use super::waveform::Waveform;
-use crate::recognizer::{Recognizer, RecognizerState};
+use crate::{
+    Application,
+    device::{self, AppStream},
+    recognizer::{Recognizer, RecognizerState},
+    settings::AudioSourceType,
+    utils,
+};
 
 mod imp {
     use super::*;
@@ -24,9 +30,18 @@ mod imp {
         pub(super) waveform: TemplateChild<Waveform>,
         #[template_child]
         pub(super) offline_mode_status_revealer: TemplateChild<gtk::Revealer>,
+        #[template_child]
+        pub(super) incognito_status_revealer: TemplateChild<gtk::Revealer>,
+        #[template_child]
+        pub(super) app_stream_revealer: TemplateChild<gtk::Revealer>,
+        #[template_child]
+        pub(super) app_stream_drop_down: TemplateChild<gtk::DropDown>,
 
         pub(super) recognizing_animation: OnceCell<adw::TimedAnimation>,
         pub(super) recognizer: OnceCell<Recognizer>,
+
+        pub(super) app_streams: RefCell<Vec<AppStream>>,
+        pub(super) app_stream_drop_down_handler_id: OnceCell<glib::SignalHandlerId>,
     }
 
     #[glib::object_subclass]
@@ -45,6 +60,23 @@ mod imp {
     }
 
     impl ObjectImpl for RecognizerView {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let obj = self.obj();
+
+            let handler_id = self.app_stream_drop_down.connect_selected_notify(clone!(
+                #[weak]
+                obj,
+                move |drop_down| {
+                    obj.on_app_stream_drop_down_selected_notify(drop_down);
+                }
+            ));
+            self.app_stream_drop_down_handler_id
+                .set(handler_id)
+                .unwrap();
+        }
+
         fn dispose(&self) {
             self.dispose_template();
         }
@@ -74,6 +106,15 @@ impl RecognizerView {
             }
         ));
 
+        recognizer
+            .bind_property(
+                "is-incognito",
+                &*self.imp().incognito_status_revealer,
+                "reveal-child",
+            )
+            .sync_create()
+            .build();
+
         recognizer.connect_state_notify(clone!(
             #[weak(rename_to = obj)]
             self,
@@ -82,6 +123,14 @@ impl RecognizerView {
             }
         ));
 
+        recognizer.connect_app_stream_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.update_app_stream_drop_down();
+            }
+        ));
+
         recognizer.connect_recording_peak_changed(clone!(
             #[weak(rename_to = obj)]
             self,
@@ -139,6 +188,66 @@ impl RecognizerView {
         }
     }
 
+    fn on_app_stream_drop_down_selected_notify(&self, drop_down: &gtk::DropDown) {
+        let app_stream = self
+            .imp()
+            .app_streams
+            .borrow()
+            .get(drop_down.selected() as usize)
+            .cloned();
+
+        if app_stream.is_some() {
+            self.recognizer().set_app_stream(app_stream);
+        }
+    }
+
+    async fn refresh_app_streams(&self) {
+        let app_streams = device::find_app_streams().await.unwrap_or_else(|err| {
+            tracing::warn!("Failed to find app streams: {:?}", err);
+            Vec::new()
+        });
+        self.imp().app_streams.replace(app_streams);
+
+        self.update_app_stream_drop_down();
+    }
+
+    fn update_app_stream_drop_down(&self) {
+        let imp = self.imp();
+
+        let app_stream = self.recognizer().app_stream();
+
+        let mut app_streams = imp.app_streams.borrow_mut();
+
+        // The recognizer may have picked a stream before our list is refreshed
+        if let Some(ref app_stream) = app_stream
+            && !app_streams.contains(app_stream)
+        {
+            app_streams.push(app_stream.clone());
+        }
+
+        let display_names = app_streams
+            .iter()
+            .map(|app_stream| app_stream.display_name())
+            .collect::<Vec<_>>();
+        let model = gtk::StringList::new(
+            &display_names
+                .iter()
+                .map(|display_name| display_name.as_str())
+                .collect::<Vec<_>>(),
+        );
+        let selected = app_stream
+            .and_then(|app_stream| app_streams.iter().position(|s| *s == app_stream))
+            .map_or(gtk::INVALID_LIST_POSITION, |position| position as u32);
+
+        drop(app_streams);
+
+        let handler_id = imp.app_stream_drop_down_handler_id.get().unwrap();
+        imp.app_stream_drop_down.block_signal(handler_id);
+        imp.app_stream_drop_down.set_model(Some(&model));
+        imp.app_stream_drop_down.set_selected(selected);
+        imp.app_stream_drop_down.unblock_signal(handler_id);
+    }
+
     fn update_ui(&self) {
         let imp = self.imp();
 
@@ -147,11 +256,29 @@ impl RecognizerView {
                 imp.waveform.clear_peaks();
                 self.recognizing_animation().pause();
                 imp.title.set_label(&gettext("Listening…"));
+
+                let is_app_audio = Application::get().settings().audio_source_type()
+                    == AudioSourceType::ApplicationAudio;
+                imp.app_stream_revealer.set_reveal_child(is_app_audio);
+
+                if is_app_audio {
+                    utils::spawn(
+                        glib::Priority::default(),
+                        clone!(
+                            #[weak(rename_to = obj)]
+                            self,
+                            async move {
+                                obj.refresh_app_streams().await;
+                            }
+                        ),
+                    );
+                }
             }
             RecognizerState::Recognizing => {
                 imp.waveform.clear_peaks();
                 self.recognizing_animation().play();
                 imp.title.set_label(&gettext("Recognizing…"));
+                imp.app_stream_revealer.set_reveal_child(false);
             }
             RecognizerState::Null => {
                 imp.waveform.clear_peaks();
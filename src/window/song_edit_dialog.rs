@@ -0,0 +1,344 @@
+use adw::{prelude::*, subclass::prelude::*};
+use gettextrs::gettext;
+use gtk::glib::{self, clone, closure_local};
+
+use std::cell::RefCell;
+
+use crate::{date_time::DateTime, external_links::ExternalLinkKey, song::Song};
+
+mod imp {
+    use glib::subclass::Signal;
+    use once_cell::sync::Lazy;
+
+    use super::*;
+
+    #[derive(Default, glib::Properties, gtk::CompositeTemplate)]
+    #[properties(wrapper_type = super::SongEditDialog)]
+    #[template(resource = "/io/github/seadve/Mousai/ui/song_edit_dialog.ui")]
+    pub struct SongEditDialog {
+        /// Song to edit, or `None` to add a new one
+        #[property(get, set, construct_only)]
+        pub(super) song: RefCell<Option<Song>>,
+
+        #[template_child]
+        pub(super) cancel_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub(super) save_button: TemplateChild<gtk::Button>,
+        #[template_child]
+        pub(super) title_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) artist_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) album_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) release_date_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) apple_music_url_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) aud_d_url_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) spotify_url_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) youtube_search_term_row: TemplateChild<adw::EntryRow>,
+        #[template_child]
+        pub(super) last_heard_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub(super) last_heard_date_button: TemplateChild<gtk::MenuButton>,
+        #[template_child]
+        pub(super) last_heard_calendar: TemplateChild<gtk::Calendar>,
+        #[template_child]
+        pub(super) last_heard_hour_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) last_heard_minute_row: TemplateChild<adw::SpinRow>,
+        #[template_child]
+        pub(super) revert_group: TemplateChild<adw::PreferencesGroup>,
+        #[template_child]
+        pub(super) revert_button_row: TemplateChild<adw::ButtonRow>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for SongEditDialog {
+        const NAME: &'static str = "MousaiSongEditDialog";
+        type Type = super::SongEditDialog;
+        type ParentType = adw::Dialog;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    #[glib::derived_properties]
+    impl ObjectImpl for SongEditDialog {
+        fn constructed(&self) {
+            self.parent_constructed();
+
+            let obj = self.obj();
+
+            self.cancel_button.connect_clicked(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.close();
+                }
+            ));
+            self.save_button.connect_clicked(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.save();
+                    obj.close();
+                }
+            ));
+            self.revert_button_row.connect_activated(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    if let Some(song) = obj.song() {
+                        song.revert_edits();
+                    }
+                    obj.close();
+                }
+            ));
+            self.last_heard_calendar.connect_day_selected(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.update_last_heard_date_button_label();
+                }
+            ));
+
+            for row in [&*self.title_row, &*self.artist_row] {
+                row.connect_changed(clone!(
+                    #[weak]
+                    obj,
+                    move |_| {
+                        obj.update_save_button_sensitivity();
+                    }
+                ));
+            }
+
+            if obj.song().is_some() {
+                obj.load_song();
+            } else {
+                obj.setup_add_mode();
+            }
+            obj.update_save_button_sensitivity();
+        }
+
+        fn signals() -> &'static [Signal] {
+            static SIGNALS: Lazy<Vec<Signal>> = Lazy::new(|| {
+                vec![
+                    Signal::builder("song-added")
+                        .param_types([Song::static_type()])
+                        .build(),
+                ]
+            });
+
+            SIGNALS.as_ref()
+        }
+    }
+
+    impl WidgetImpl for SongEditDialog {}
+    impl AdwDialogImpl for SongEditDialog {}
+}
+
+glib::wrapper! {
+    pub struct SongEditDialog(ObjectSubclass<imp::SongEditDialog>)
+        @extends gtk::Widget, adw::Dialog,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl SongEditDialog {
+    /// Creates a dialog for editing the metadata of `song`.
+    pub fn new(song: &Song) -> Self {
+        glib::Object::builder().property("song", song).build()
+    }
+
+    /// Creates a dialog for adding a song to history without recognizing it.
+    pub fn new_add() -> Self {
+        glib::Object::new()
+    }
+
+    /// Emitted once the song is built in add mode. It is up to the handler
+    /// to insert it to history.
+    pub fn connect_song_added<F>(&self, f: F) -> glib::SignalHandlerId
+    where
+        F: Fn(&Self, &Song) + 'static,
+    {
+        self.connect_closure(
+            "song-added",
+            false,
+            closure_local!(|obj: &Self, song: &Song| {
+                f(obj, song);
+            }),
+        )
+    }
+
+    fn emit_song_added(&self, song: &Song) {
+        self.emit_by_name::<()>("song-added", &[song]);
+    }
+
+    fn link_rows(&self) -> [(ExternalLinkKey, adw::EntryRow); 4] {
+        let imp = self.imp();
+        [
+            (
+                ExternalLinkKey::AppleMusicUrl,
+                imp.apple_music_url_row.get(),
+            ),
+            (ExternalLinkKey::AudDUrl, imp.aud_d_url_row.get()),
+            (ExternalLinkKey::SpotifyUrl, imp.spotify_url_row.get()),
+            (
+                ExternalLinkKey::YoutubeSearchTerm,
+                imp.youtube_search_term_row.get(),
+            ),
+        ]
+    }
+
+    fn load_song(&self) {
+        let imp = self.imp();
+
+        let song = self.song().expect("song must be set when editing");
+        let metadata = song.metadata();
+
+        imp.title_row.set_text(&metadata.title);
+        imp.artist_row.set_text(&metadata.artist);
+        imp.album_row.set_text(&metadata.album);
+        imp.release_date_row
+            .set_text(metadata.release_date.as_deref().unwrap_or_default());
+
+        for (key, row) in self.link_rows() {
+            row.set_text(
+                metadata
+                    .external_links
+                    .get(key.as_ref())
+                    .map_or("", |value| value.as_str()),
+            );
+        }
+
+        imp.revert_group.set_visible(song.is_edited());
+    }
+
+    fn setup_add_mode(&self) {
+        let imp = self.imp();
+
+        self.set_title(&gettext("Add Song"));
+        imp.save_button.set_label(&gettext("_Add"));
+
+        let now = glib::DateTime::now_local().unwrap();
+        imp.last_heard_calendar.select_day(&now);
+        imp.last_heard_hour_row.set_value(now.hour() as f64);
+        imp.last_heard_minute_row.set_value(now.minute() as f64);
+        imp.last_heard_group.set_visible(true);
+
+        imp.revert_group.set_visible(false);
+
+        self.update_last_heard_date_button_label();
+    }
+
+    fn update_last_heard_date_button_label(&self) {
+        let imp = self.imp();
+
+        let date = imp.last_heard_calendar.date();
+        imp.last_heard_date_button
+            .set_label(&date.format("%x").unwrap_or_default());
+    }
+
+    fn last_heard(&self) -> DateTime {
+        let imp = self.imp();
+
+        let date = imp.last_heard_calendar.date();
+        let last_heard = glib::DateTime::from_local(
+            date.year(),
+            date.month(),
+            date.day_of_month(),
+            imp.last_heard_hour_row.value() as i32,
+            imp.last_heard_minute_row.value() as i32,
+            0.0,
+        )
+        .and_then(|dt| dt.to_utc());
+
+        match last_heard {
+            Ok(last_heard) => DateTime::from(last_heard),
+            Err(err) => {
+                tracing::warn!("Failed to create last heard date time: {:?}", err);
+                DateTime::now_utc()
+            }
+        }
+    }
+
+    fn add(&self) {
+        let imp = self.imp();
+
+        let mut builder = Song::manually_added_builder(
+            imp.title_row.text().trim(),
+            imp.artist_row.text().trim(),
+            imp.album_row.text().trim(),
+        );
+
+        let release_date = imp.release_date_row.text();
+        let release_date = release_date.trim();
+        if !release_date.is_empty() {
+            builder.release_date(release_date);
+        }
+
+        for (key, row) in self.link_rows() {
+            let value = row.text().trim().to_string();
+            if !value.is_empty() {
+                builder.external_link(key, value);
+            }
+        }
+
+        let song = builder.build();
+        song.set_last_heard(self.last_heard());
+
+        self.emit_song_added(&song);
+    }
+
+    fn save(&self) {
+        let imp = self.imp();
+
+        let Some(song) = self.song() else {
+            self.add();
+            return;
+        };
+
+        // Start from the current metadata so links with keys that cannot be
+        // edited here are kept.
+        let mut metadata = song.metadata();
+        metadata.title = imp.title_row.text().trim().to_string();
+        metadata.artist = imp.artist_row.text().trim().to_string();
+        metadata.album = imp.album_row.text().trim().to_string();
+        metadata.release_date = Some(imp.release_date_row.text().trim().to_string())
+            .filter(|release_date| !release_date.is_empty());
+
+        for (key, row) in self.link_rows() {
+            let value = row.text().trim().to_string();
+            if value.is_empty() {
+                metadata.external_links.shift_remove(key.as_ref());
+            } else {
+                metadata
+                    .external_links
+                    .insert(key.as_ref().to_string(), value);
+            }
+        }
+
+        if metadata == song.metadata() {
+            tracing::debug!("Song metadata was not changed");
+            return;
+        }
+
+        song.edit(metadata);
+    }
+
+    fn update_save_button_sensitivity(&self) {
+        let imp = self.imp();
+
+        imp.save_button.set_sensitive(
+            !imp.title_row.text().trim().is_empty() && !imp.artist_row.text().trim().is_empty(),
+        );
+    }
+}
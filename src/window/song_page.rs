@@ -2,8 +2,12 @@ use adw::{
     prelude::*,
     subclass::{navigation_page::NavigationPageImpl, prelude::*},
 };
+use anyhow::{Context, Result};
 use gettextrs::gettext;
-use gtk::glib::{self, clone, closure_local};
+use gtk::{
+    gio,
+    glib::{self, clone, closure_local},
+};
 
 use std::cell::{Cell, RefCell};
 
@@ -13,12 +17,16 @@ use super::{
     external_link_tile::ExternalLinkTile,
     information_row::InformationRow,
     playback_button::{PlaybackButton, PlaybackButtonMode},
+    song_edit_dialog::SongEditDialog,
 };
 use crate::{
     Application,
+    link_template::LinkTemplate,
     player::{Player, PlayerState},
     song::Song,
+    song_clips::SongClips,
     song_list::SongList,
+    utils,
 };
 
 const NORMAL_ALBUM_COVER_PIXEL_SIZE: i32 = 180;
@@ -54,6 +62,22 @@ mod imp {
         #[template_child]
         pub(super) release_date_row: TemplateChild<InformationRow>,
         #[template_child]
+        pub(super) genre_row: TemplateChild<InformationRow>,
+        #[template_child]
+        pub(super) label_row: TemplateChild<InformationRow>,
+        #[template_child]
+        pub(super) duration_row: TemplateChild<InformationRow>,
+        #[template_child]
+        pub(super) timecode_row: TemplateChild<InformationRow>,
+        #[template_child]
+        pub(super) isrc_row: TemplateChild<InformationRow>,
+        #[template_child]
+        pub(super) source_row: TemplateChild<InformationRow>,
+        #[template_child]
+        pub(super) recorded_clip_row: TemplateChild<adw::ActionRow>,
+        #[template_child]
+        pub(super) recorded_clip_play_button: TemplateChild<gtk::Button>,
+        #[template_child]
         pub(super) lyrics_row: TemplateChild<adw::ActionRow>,
         #[template_child]
         pub(super) external_links_box: TemplateChild<gtk::FlowBox>,
@@ -61,6 +85,11 @@ mod imp {
         pub(super) player: RefCell<Option<(WeakRef<Player>, glib::SignalHandlerId)>>, // Player and Player's state notify handler id
         pub(super) song_list: RefCell<Option<(WeakRef<SongList>, glib::SignalHandlerId)>>, // SongList and SongList's items changed handler id
         pub(super) song_binding_group: glib::BindingGroup,
+        pub(super) song_notify_handler_id: RefCell<Option<glib::SignalHandlerId>>,
+        pub(super) link_templates_changed_handler_id: RefCell<Option<glib::SignalHandlerId>>,
+
+        pub(super) song_clips: RefCell<Option<SongClips>>,
+        pub(super) clip_media: RefCell<Option<gtk::MediaFile>>,
     }
 
     #[glib::object_subclass]
@@ -79,6 +108,19 @@ mod imp {
                     .set_text(&song.artist_title_text());
                 Application::get().add_message_toast(&gettext("Copied to clipboard"));
             });
+
+            klass.install_action("song-page.edit-song", None, |obj, _, _| {
+                let song = obj.song().expect("song should be set");
+                let dialog = SongEditDialog::new(&song);
+                dialog.present(Some(obj));
+            });
+
+            klass.install_action_async("song-page.export-clip", None, |obj, _, _| async move {
+                if let Err(err) = obj.export_clip().await {
+                    tracing::error!("Failed to export clip: {:?}", err);
+                    Application::get().add_message_toast(&gettext("Failed to export clip"));
+                }
+            });
         }
 
         fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
@@ -118,6 +160,13 @@ mod imp {
                     obj.toggle_playback();
                 }
             ));
+            self.recorded_clip_play_button.connect_clicked(clone!(
+                #[weak]
+                obj,
+                move |_| {
+                    obj.toggle_clip_playback();
+                }
+            ));
 
             self.lyrics_row.connect_activated(clone!(
                 #[weak]
@@ -132,6 +181,18 @@ mod imp {
                 external_link_tile.handle_activation();
             });
 
+            let handler_id = Application::get()
+                .settings()
+                .connect_link_templates_changed(clone!(
+                    #[weak]
+                    obj,
+                    move |_| {
+                        obj.update_information();
+                    }
+                ));
+            self.link_templates_changed_handler_id
+                .replace(Some(handler_id));
+
             self.song_binding_group
                 .bind("lyrics", &*self.lyrics_row, "visible")
                 .transform_to(|_, lyrics: Option<String>| Some(lyrics.is_some()))
@@ -147,6 +208,15 @@ mod imp {
 
             obj.unbind_player();
             obj.unbind_song_list();
+            obj.stop_clip_playback();
+
+            if let Some(handler_id) = self.song_notify_handler_id.take() {
+                obj.song().unwrap().disconnect(handler_id);
+            }
+
+            if let Some(handler_id) = self.link_templates_changed_handler_id.take() {
+                Application::get().settings().disconnect(handler_id);
+            }
         }
     }
 
@@ -163,6 +233,25 @@ mod imp {
 
             self.song_binding_group.set_source(song.as_ref());
 
+            if let Some(handler_id) = self.song_notify_handler_id.take() {
+                obj.song().unwrap().disconnect(handler_id);
+            }
+            if let Some(ref song) = song {
+                // The metadata may be edited while the page is shown.
+                let handler_id = song.connect_notify_local(
+                    None,
+                    clone!(
+                        #[weak]
+                        obj,
+                        move |_, _| {
+                            obj.update_information();
+                            obj.update_page_title();
+                        }
+                    ),
+                );
+                self.song_notify_handler_id.replace(Some(handler_id));
+            }
+
             // Only crossfade when album art is not loaded to avoid
             // unnecessary crossfading when the album art can be
             // loaded immediately.
@@ -174,6 +263,8 @@ mod imp {
             self.album_cover.set_song(song.as_ref());
 
             self.song.replace(song);
+            obj.stop_clip_playback();
+            obj.update_recorded_clip_ui();
             obj.update_playback_ui();
             obj.update_remove_button_sensitivity();
             obj.update_information();
@@ -279,6 +370,13 @@ impl SongPage {
         }
     }
 
+    /// Must only be called once.
+    pub fn bind_song_clips(&self, song_clips: &SongClips) {
+        self.imp().song_clips.replace(Some(song_clips.clone()));
+
+        self.update_recorded_clip_ui();
+    }
+
     fn player(&self) -> Player {
         self.imp()
             .player
@@ -303,6 +401,131 @@ impl SongPage {
         }
     }
 
+    fn song_clip(&self) -> Option<glib::Bytes> {
+        let song = self.song()?;
+        let song_clips = self.imp().song_clips.borrow();
+
+        song_clips
+            .as_ref()?
+            .get(song.id_ref())
+            .unwrap_or_else(|err| {
+                tracing::error!("Failed to get song clip: {:?}", err);
+                None
+            })
+    }
+
+    fn toggle_clip_playback(&self) {
+        let imp = self.imp();
+
+        if let Some(ref media) = *imp.clip_media.borrow()
+            && !media.is_ended()
+        {
+            if media.is_playing() {
+                media.pause();
+            } else {
+                media.play();
+            }
+            return;
+        }
+
+        let Some(clip) = self.song_clip() else {
+            return;
+        };
+
+        let player = self.player();
+        if player.state() == PlayerState::Playing {
+            player.pause();
+        }
+
+        let media = gtk::MediaFile::for_input_stream(&gio::MemoryInputStream::from_bytes(&clip));
+        media.connect_playing_notify(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.update_clip_play_button();
+            }
+        ));
+        media.play();
+        imp.clip_media.replace(Some(media));
+    }
+
+    fn stop_clip_playback(&self) {
+        if let Some(media) = self.imp().clip_media.take() {
+            media.pause();
+        }
+
+        self.update_clip_play_button();
+    }
+
+    async fn export_clip(&self) -> Result<()> {
+        let song = self.song().context("No song")?;
+        let clip = self.song_clip().context("Song has no clip")?;
+
+        let dialog = gtk::FileDialog::builder()
+            .title(gettext("Export Clip"))
+            .initial_name(format!(
+                "{}.ogg",
+                song.artist_title_text().replace('/', "-")
+            ))
+            .modal(true)
+            .build();
+        let file = match dialog
+            .save_future(self.root().and_downcast_ref::<gtk::Window>())
+            .await
+        {
+            Ok(file) => file,
+            Err(err) if err.matches(gtk::DialogError::Dismissed) => return Ok(()),
+            Err(err) => return Err(err).context("Failed to choose file"),
+        };
+
+        file.replace_contents_future(clip, None, false, gio::FileCreateFlags::REPLACE_DESTINATION)
+            .await
+            .map_err(|(_, err)| err)
+            .context("Failed to write clip")?;
+
+        tracing::debug!(uri = %file.uri(), "Exported clip");
+
+        Ok(())
+    }
+
+    fn update_clip_play_button(&self) {
+        let imp = self.imp();
+
+        let is_playing = imp
+            .clip_media
+            .borrow()
+            .as_ref()
+            .is_some_and(|media| media.is_playing());
+
+        if is_playing {
+            imp.recorded_clip_play_button
+                .set_icon_name("media-playback-pause-symbolic");
+            imp.recorded_clip_play_button
+                .set_tooltip_text(Some(&gettext("Pause Recorded Clip")));
+        } else {
+            imp.recorded_clip_play_button
+                .set_icon_name("media-playback-start-symbolic");
+            imp.recorded_clip_play_button
+                .set_tooltip_text(Some(&gettext("Play Recorded Clip")));
+        }
+    }
+
+    fn update_recorded_clip_ui(&self) {
+        let imp = self.imp();
+
+        let has_clip = self.song().is_some_and(|song| {
+            imp.song_clips.borrow().as_ref().is_some_and(|song_clips| {
+                song_clips.contains(song.id_ref()).unwrap_or_else(|err| {
+                    tracing::error!("Failed to check song clip: {:?}", err);
+                    false
+                })
+            })
+        });
+
+        imp.recorded_clip_row.set_visible(has_clip);
+        self.action_set_enabled("song-page.export-clip", has_clip);
+    }
+
     fn update_playback_ui(&self) {
         let imp = self.imp();
         let song = self.song();
@@ -353,20 +576,44 @@ impl SongPage {
         let song = self.song();
         let song = song.as_ref();
 
-        imp.external_links_box.bind_model(
-            song.map(|song| {
-                let filter = gtk::CustomFilter::new(|item| {
-                    let link = item.downcast_ref().unwrap();
-                    ExternalLinkTile::can_handle(link)
-                });
-                gtk::FilterListModel::new(Some(song.external_links()), Some(filter))
-            })
-            .as_ref(),
-            |item| {
+        let external_links_model = song.map(|song| {
+            let filter = gtk::CustomFilter::new(|item| {
                 let link = item.downcast_ref().unwrap();
-                ExternalLinkTile::new(link).upcast()
-            },
-        );
+                ExternalLinkTile::can_handle(link)
+            });
+            let external_links =
+                gtk::FilterListModel::new(Some(song.external_links()), Some(filter));
+
+            // User-defined links are shown after the ones from the provider.
+            let link_templates = gio::ListStore::new::<glib::BoxedAnyObject>();
+            link_templates.extend(
+                LinkTemplate::load_all(Application::get().settings())
+                    .into_iter()
+                    .map(glib::BoxedAnyObject::new),
+            );
+
+            let models = gio::ListStore::new::<gio::ListModel>();
+            models.append(&external_links);
+            models.append(&link_templates);
+            gtk::FlattenListModel::new(Some(models))
+        });
+        let owned_song = song.cloned();
+        imp.external_links_box
+            .bind_model(external_links_model.as_ref(), move |item| {
+                if let Some(link) = item.downcast_ref() {
+                    ExternalLinkTile::new(link).upcast()
+                } else {
+                    let link_template = item.downcast_ref::<glib::BoxedAnyObject>().unwrap();
+                    let song = owned_song
+                        .as_ref()
+                        .expect("song must be set if there are items");
+                    ExternalLinkTile::for_link_template(
+                        &link_template.borrow::<LinkTemplate>(),
+                        song,
+                    )
+                    .upcast()
+                }
+            });
 
         imp.last_heard_row.set_value(
             song.map(|song| {
@@ -383,6 +630,27 @@ impl SongPage {
             song.and_then(|song| song.release_date())
                 .unwrap_or_default(),
         );
+        imp.genre_row
+            .set_value(song.and_then(|song| song.genre()).unwrap_or_default());
+        imp.label_row
+            .set_value(song.and_then(|song| song.label()).unwrap_or_default());
+        imp.duration_row.set_value(
+            song.and_then(|song| song.duration())
+                .map(utils::format_duration)
+                .unwrap_or_default(),
+        );
+        imp.timecode_row.set_value(
+            song.and_then(|song| song.timecode())
+                .map(utils::format_duration)
+                .unwrap_or_default(),
+        );
+        imp.isrc_row
+            .set_value(song.and_then(|song| song.isrc()).unwrap_or_default());
+        imp.source_row.set_value(
+            song.filter(|song| song.is_manually_added())
+                .map(|_| gettext("Added Manually"))
+                .unwrap_or_default(),
+        );
     }
 
     fn update_page_title(&self) {
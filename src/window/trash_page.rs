@@ -0,0 +1,268 @@
+use adw::{prelude::*, subclass::prelude::*};
+use anyhow::{Context, Result};
+use gettextrs::gettext;
+use gtk::glib::{self, WeakRef, clone};
+
+use std::cell::{OnceCell, RefCell};
+
+use crate::{
+    Application,
+    i18n::{gettext_f, ngettext_f},
+    song_list::SongList,
+    song_trash::{SongTrash, TrashedSong},
+};
+
+mod imp {
+    use super::*;
+
+    #[derive(Default, gtk::CompositeTemplate)]
+    #[template(resource = "/io/github/seadve/Mousai/ui/trash_page.ui")]
+    pub struct TrashPage {
+        #[template_child]
+        pub(super) stack: TemplateChild<gtk::Stack>,
+        #[template_child]
+        pub(super) empty_page: TemplateChild<adw::StatusPage>,
+        #[template_child]
+        pub(super) songs_page: TemplateChild<adw::PreferencesPage>,
+        #[template_child]
+        pub(super) songs_group: TemplateChild<adw::PreferencesGroup>,
+
+        pub(super) song_list: OnceCell<WeakRef<SongList>>,
+        pub(super) song_trash: OnceCell<SongTrash>,
+
+        pub(super) rows: RefCell<Vec<adw::ActionRow>>,
+    }
+
+    #[glib::object_subclass]
+    impl ObjectSubclass for TrashPage {
+        const NAME: &'static str = "MousaiTrashPage";
+        type Type = super::TrashPage;
+        type ParentType = adw::NavigationPage;
+
+        fn class_init(klass: &mut Self::Class) {
+            klass.bind_template();
+
+            klass.install_action("trash-page.restore-all", None, |obj, _, _| {
+                let trashed_songs = match obj.song_trash().snapshot() {
+                    Ok(trashed_songs) => trashed_songs,
+                    Err(err) => {
+                        tracing::error!("Failed to get trashed songs: {:?}", err);
+                        Application::get().add_message_toast(&gettext("Failed to restore songs"));
+                        return;
+                    }
+                };
+
+                obj.restore(&trashed_songs);
+            });
+
+            klass.install_action("trash-page.empty", None, |obj, _, _| {
+                obj.present_empty_dialog();
+            });
+        }
+
+        fn instance_init(obj: &glib::subclass::InitializingObject<Self>) {
+            obj.init_template();
+        }
+    }
+
+    impl ObjectImpl for TrashPage {}
+    impl WidgetImpl for TrashPage {}
+    impl NavigationPageImpl for TrashPage {}
+}
+
+glib::wrapper! {
+    pub struct TrashPage(ObjectSubclass<imp::TrashPage>)
+        @extends gtk::Widget, adw::NavigationPage,
+        @implements gtk::Accessible, gtk::Buildable, gtk::ConstraintTarget;
+}
+
+impl TrashPage {
+    pub fn new() -> Self {
+        glib::Object::new()
+    }
+
+    /// Must only be called once.
+    pub fn bind_models(&self, song_list: &SongList, song_trash: &SongTrash) {
+        let imp = self.imp();
+
+        imp.song_list.set(song_list.downgrade()).unwrap();
+        imp.song_trash
+            .set(song_trash.clone())
+            .unwrap_or_else(|_| panic!("song trash must be bound only once"));
+
+        self.update_rows();
+    }
+
+    fn song_trash(&self) -> &SongTrash {
+        self.imp()
+            .song_trash
+            .get()
+            .expect("song trash must be bound")
+    }
+
+    /// Moves the songs back to the history.
+    ///
+    /// Songs that were added back to the history since they were removed are
+    /// kept as they are in the history.
+    fn restore(&self, trashed_songs: &[TrashedSong]) {
+        let Some(song_list) = self
+            .imp()
+            .song_list
+            .get()
+            .and_then(|song_list| song_list.upgrade())
+        else {
+            return;
+        };
+
+        match restore_songs(&song_list, self.song_trash(), trashed_songs) {
+            Ok(n_restored) => {
+                Application::get().add_message_toast(&ngettext_f(
+                    // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+                    "Restored {n_restored} song",
+                    "Restored {n_restored} songs",
+                    n_restored as u32,
+                    &[("n_restored", &n_restored.to_string())],
+                ));
+            }
+            Err(err) => {
+                tracing::error!("Failed to restore songs: {:?}", err);
+                Application::get().add_message_toast(&gettext("Failed to restore songs"));
+            }
+        }
+
+        self.update_rows();
+    }
+
+    fn present_empty_dialog(&self) {
+        const CANCEL_RESPONSE_ID: &str = "cancel";
+        const EMPTY_RESPONSE_ID: &str = "empty";
+
+        let dialog = adw::AlertDialog::builder()
+            .heading(gettext("Empty Trash?"))
+            .body(gettext(
+                "All songs in the trash will be permanently removed and can no longer be restored",
+            ))
+            .default_response(CANCEL_RESPONSE_ID)
+            .close_response(CANCEL_RESPONSE_ID)
+            .build();
+        dialog.add_response(CANCEL_RESPONSE_ID, &gettext("Cancel"));
+        dialog.add_response(EMPTY_RESPONSE_ID, &gettext("_Empty Trash"));
+        dialog.set_response_appearance(EMPTY_RESPONSE_ID, adw::ResponseAppearance::Destructive);
+        dialog.connect_response(
+            Some(EMPTY_RESPONSE_ID),
+            clone!(
+                #[weak(rename_to = obj)]
+                self,
+                move |_, _| {
+                    if let Err(err) = Application::get().empty_trash() {
+                        tracing::error!("Failed to empty trash: {:?}", err);
+                        Application::get().add_message_toast(&gettext("Failed to empty trash"));
+                    }
+
+                    obj.update_rows();
+                }
+            ),
+        );
+        dialog.present(Some(self));
+    }
+
+    fn update_rows(&self) {
+        let imp = self.imp();
+
+        for row in imp.rows.take() {
+            imp.songs_group.remove(&row);
+        }
+
+        let trashed_songs = self.song_trash().snapshot().unwrap_or_else(|err| {
+            tracing::error!("Failed to get trashed songs: {:?}", err);
+            Vec::new()
+        });
+
+        for trashed_song in trashed_songs {
+            let row = self.create_row(trashed_song);
+            imp.songs_group.add(&row);
+            imp.rows.borrow_mut().push(row);
+        }
+
+        let is_empty = imp.rows.borrow().is_empty();
+
+        if is_empty {
+            imp.stack.set_visible_child(&*imp.empty_page);
+        } else {
+            imp.stack.set_visible_child(&*imp.songs_page);
+        }
+
+        self.action_set_enabled("trash-page.restore-all", !is_empty);
+        self.action_set_enabled("trash-page.empty", !is_empty);
+    }
+
+    fn create_row(&self, trashed_song: TrashedSong) -> adw::ActionRow {
+        let row = adw::ActionRow::builder()
+            .title(glib::markup_escape_text(&trashed_song.song.title()))
+            .subtitle(glib::markup_escape_text(&row_subtitle(&trashed_song)))
+            .build();
+
+        let restore_button = gtk::Button::builder()
+            .icon_name("edit-undo-symbolic")
+            .tooltip_text(gettext("Restore"))
+            .valign(gtk::Align::Center)
+            .css_classes(["flat"])
+            .build();
+        restore_button.connect_clicked(clone!(
+            #[weak(rename_to = obj)]
+            self,
+            move |_| {
+                obj.restore(std::slice::from_ref(&trashed_song));
+            }
+        ));
+        row.add_suffix(&restore_button);
+
+        row
+    }
+}
+
+/// Inserts the songs that are not in the history yet back to it, then removes
+/// all of them from the trash, returning the number of inserted songs.
+fn restore_songs(
+    song_list: &SongList,
+    song_trash: &SongTrash,
+    trashed_songs: &[TrashedSong],
+) -> Result<usize> {
+    let songs = trashed_songs
+        .iter()
+        .map(|trashed_song| &trashed_song.song)
+        .filter(|song| !song_list.contains(song.id_ref()))
+        .cloned()
+        .collect::<Vec<_>>();
+    let n_restored = songs.len();
+
+    // Insert first, so the songs are not lost if it fails
+    song_list
+        .insert_many(songs)
+        .context("Failed to insert songs to history")?;
+
+    let song_ids = trashed_songs
+        .iter()
+        .map(|trashed_song| trashed_song.song.id_ref())
+        .collect::<Vec<_>>();
+    song_trash
+        .take_many(&song_ids)
+        .context("Failed to take songs from trash")?;
+
+    Ok(n_restored)
+}
+
+fn row_subtitle(trashed_song: &TrashedSong) -> String {
+    let deleted_time = trashed_song.deleted_time.to_local().fuzzy_display();
+    let deleted_time = gettext_f(
+        // Translators: Do NOT translate the contents between '{' and '}', this is a variable name.
+        "Removed {deleted_time}",
+        &[("deleted_time", &deleted_time)],
+    );
+
+    [trashed_song.song.artist(), deleted_time]
+        .into_iter()
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join(" · ")
+}